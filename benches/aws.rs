@@ -9,6 +9,7 @@ use aws_sigv4::sign::v4::SigningParams;
 use criterion::criterion_group;
 use criterion::criterion_main;
 use criterion::Criterion;
+use reqsign::AwsContentHashStrategy;
 use reqsign::AwsCredential;
 use reqsign::AwsV4Signer;
 
@@ -38,6 +39,51 @@ pub fn bench(c: &mut Criterion) {
         })
     });
 
+    group.bench_function("reqsign_empty_body", |b| {
+        let cred = AwsCredential {
+            access_key_id: "access_key_id".to_string(),
+            secret_access_key: "secret_access_key".to_string(),
+            ..Default::default()
+        };
+
+        // Hash of the empty body is taken from constant instead of computed.
+        let s =
+            AwsV4Signer::new("s3", "test").content_hash_strategy(AwsContentHashStrategy::Compute);
+
+        b.iter(|| {
+            let mut req = http::Request::new("");
+            *req.method_mut() = http::Method::DELETE;
+            *req.uri_mut() = "http://127.0.0.1:9000/hello"
+                .parse()
+                .expect("url must be valid");
+
+            s.sign_with_body(&mut req, b"", &cred)
+                .expect("must success")
+        })
+    });
+
+    group.bench_function("reqsign_sign_all", |b| {
+        let cred = AwsCredential {
+            access_key_id: "access_key_id".to_string(),
+            secret_access_key: "secret_access_key".to_string(),
+            ..Default::default()
+        };
+
+        let s = AwsV4Signer::new("s3", "test");
+
+        b.iter(|| {
+            let mut reqs: Vec<_> = (0..100)
+                .map(|i| {
+                    http::Request::delete(format!("http://127.0.0.1:9000/hello-{i}"))
+                        .body("")
+                        .expect("request must be valid")
+                })
+                .collect();
+
+            s.sign_all(&mut reqs, &cred).expect("must success")
+        })
+    });
+
     group.bench_function("aws_sigv4", |b| {
         let mut ss = SigningSettings::default();
         ss.percent_encoding_mode = PercentEncodingMode::Single;
@@ -1,16 +1,24 @@
+#[cfg(not(feature = "no_network"))]
 use std::fs;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+#[cfg(not(feature = "no_network"))]
 use anyhow::anyhow;
 use anyhow::Result;
 use log::debug;
 use reqwest::Client;
+#[cfg(not(feature = "no_network"))]
 use serde::Deserialize;
 
 use super::config::Config;
+use crate::constants::DEFAULT_USER_AGENT;
+use crate::metrics::Metrics;
+use crate::metrics::SharedMetrics;
+#[cfg(not(feature = "no_network"))]
 use crate::time::format_rfc3339;
 use crate::time::now;
+#[cfg(not(feature = "no_network"))]
 use crate::time::parse_rfc3339;
 use crate::time::DateTime;
 
@@ -49,12 +57,17 @@ impl Credential {
 }
 
 /// Loader will load credential from different methods.
+///
+/// With `no_network` feature enabled, only static credentials will be loaded.
 #[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "no_network", allow(dead_code))]
 pub struct Loader {
     client: Client,
     config: Config,
+    user_agent: String,
 
     credential: Arc<Mutex<Option<Credential>>>,
+    metrics: SharedMetrics,
 }
 
 impl Loader {
@@ -63,20 +76,50 @@ impl Loader {
         Self {
             client,
             config,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
 
             credential: Arc::default(),
+            metrics: SharedMetrics::default(),
         }
     }
 
+    /// Set the `User-Agent` of requests sent to STS.
+    ///
+    /// Default to `reqsign/{version}`.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
+
+    /// Specify the metrics to record cache hits and refreshes of credential.
+    ///
+    /// Default to [`NoopMetrics`](crate::NoopMetrics).
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics.into();
+        self
+    }
+
     /// Load credential.
     pub async fn load(&self) -> Result<Option<Credential>> {
         // Return cached credential if it's valid.
         match self.credential.lock().expect("lock poisoned").clone() {
-            Some(cred) if cred.is_valid() => return Ok(Some(cred)),
+            Some(cred) if cred.is_valid() => {
+                self.metrics.credential_cache_hit("aliyun");
+                return Ok(Some(cred));
+            }
             _ => (),
         }
 
-        let cred = if let Some(cred) = self.load_inner().await? {
+        let cred = self.load_inner().await;
+        self.metrics
+            .credential_refreshed("aliyun", matches!(cred, Ok(Some(_))));
+        let cred = if let Some(cred) = cred? {
             cred
         } else {
             return Ok(None);
@@ -96,6 +139,7 @@ impl Loader {
             return Ok(Some(cred));
         }
 
+        #[cfg(not(feature = "no_network"))]
         if let Ok(Some(cred)) = self
             .load_via_assume_role_with_oidc()
             .await
@@ -122,6 +166,7 @@ impl Loader {
         }
     }
 
+    #[cfg(not(feature = "no_network"))]
     async fn load_via_assume_role_with_oidc(&self) -> Result<Option<Credential>> {
         let (token_file, role_arn, provider_arn) = match (
             &self.config.oidc_token_file,
@@ -140,10 +185,14 @@ impl Loader {
         // Construct request to Aliyun STS Service.
         let url = format!("{}/?Action=AssumeRoleWithOIDC&OIDCProviderArn={}&RoleArn={}&RoleSessionName={}&Format=JSON&Version=2015-04-01&Timestamp={}&OIDCToken={}", self.get_sts_endpoint(), provider_arn, role_arn, role_session_name, format_rfc3339(now()), token);
 
-        let req = self.client.get(&url).header(
-            http::header::CONTENT_TYPE.as_str(),
-            "application/x-www-form-urlencoded",
-        );
+        let req = self
+            .client
+            .get(&url)
+            .header(
+                http::header::CONTENT_TYPE.as_str(),
+                "application/x-www-form-urlencoded",
+            )
+            .header(http::header::USER_AGENT, &self.user_agent);
 
         let resp = req.send().await?;
         if resp.status() != http::StatusCode::OK {
@@ -164,14 +213,19 @@ impl Loader {
         Ok(Some(cred))
     }
 
+    #[cfg(not(feature = "no_network"))]
     fn get_sts_endpoint(&self) -> String {
         match &self.config.sts_endpoint {
+            Some(defined_sts_endpoint) if defined_sts_endpoint.contains("://") => {
+                defined_sts_endpoint.trim_end_matches('/').to_string()
+            }
             Some(defined_sts_endpoint) => format!("https://{}", defined_sts_endpoint),
             None => "https://sts.aliyuncs.com".to_string(),
         }
     }
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default)]
 struct AssumeRoleWithOidcResponse {
@@ -179,6 +233,7 @@ struct AssumeRoleWithOidcResponse {
     credentials: AssumeRoleWithOidcCredentials,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithOidcCredentials {
@@ -188,7 +243,7 @@ struct AssumeRoleWithOidcCredentials {
     expiration: String,
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "no_network")))]
 mod tests {
     use std::env;
     use std::str::FromStr;
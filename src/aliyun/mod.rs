@@ -3,6 +3,7 @@
 //! Only OSS has been supported.
 
 mod oss;
+pub use oss::EndpointType as AliyunOssEndpointType;
 pub use oss::Signer as AliyunOssSigner;
 
 mod config;
@@ -12,4 +13,13 @@ mod credential;
 pub use credential::Credential as AliyunCredential;
 pub use credential::Loader as AliyunLoader;
 
+crate::client::signing_client!(
+    /// Signing client for Aliyun OSS, which loads credential by [`AliyunLoader`] and
+    /// signs requests by [`AliyunOssSigner`].
+    AliyunOssSigningClient,
+    "aliyun",
+    AliyunLoader,
+    AliyunOssSigner
+);
+
 mod constants;
@@ -13,6 +13,7 @@ use log::debug;
 use once_cell::sync::Lazy;
 use percent_encoding::utf8_percent_encode;
 
+use super::constants::OSS_QUERY_ENCODE_SET;
 use super::credential::Credential;
 use crate::ctx::SigningContext;
 use crate::ctx::SigningMethod;
@@ -24,9 +25,35 @@ use crate::time::DateTime;
 
 const CONTENT_MD5: &str = "content-md5";
 
+/// Type of the OSS endpoint.
+///
+/// - [Regions and endpoints](https://www.alibabacloud.com/help/en/oss/user-guide/regions-and-endpoints)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EndpointType {
+    /// Public endpoint like `oss-cn-hangzhou.aliyuncs.com`.
+    #[default]
+    Public,
+    /// Internal endpoint like `oss-cn-hangzhou-internal.aliyuncs.com`, which
+    /// can only be accessed inside the same region.
+    Internal,
+    /// Transfer acceleration endpoint `oss-accelerate.aliyuncs.com`.
+    Accelerate,
+}
+
 /// Singer for Aliyun OSS.
+///
+/// The canonicalized resource is always built from the bucket of signer, so
+/// requests can be sent to any endpoint of the bucket:
+///
+/// - Virtual hosted style like `https://bucket.oss-cn-hangzhou.aliyuncs.com/object`
+/// - Path style like `https://oss-cn-hangzhou.aliyuncs.com/bucket/object`
+/// - CNAME like `https://static.example.com/object`, see [`Signer::with_cname`]
 pub struct Signer {
     bucket: String,
+    cname: bool,
+    endpoint_type: EndpointType,
+
+    time: Option<DateTime>,
 }
 
 impl Signer {
@@ -34,9 +61,59 @@ impl Signer {
     pub fn new(bucket: &str) -> Self {
         Self {
             bucket: bucket.to_owned(),
+            cname: false,
+            endpoint_type: EndpointType::default(),
+
+            time: None,
+        }
+    }
+
+    /// Specify whether requests are sent to a custom domain (CNAME) bound to
+    /// the bucket.
+    ///
+    /// If enabled, the request path will always be treated as the object key.
+    pub fn with_cname(mut self, cname: bool) -> Self {
+        self.cname = cname;
+        self
+    }
+
+    /// Specify the type of endpoint that used by [`Signer::endpoint`].
+    ///
+    /// Default to [`EndpointType::Public`].
+    pub fn with_endpoint_type(mut self, endpoint_type: EndpointType) -> Self {
+        self.endpoint_type = endpoint_type;
+        self
+    }
+
+    /// Build the virtual hosted style endpoint of the bucket in given region.
+    ///
+    /// For example, `https://bucket.oss-cn-hangzhou-internal.aliyuncs.com`
+    /// for [`EndpointType::Internal`] in `cn-hangzhou`.
+    pub fn endpoint(&self, region: &str) -> String {
+        let region = region.strip_prefix("oss-").unwrap_or(region);
+        match self.endpoint_type {
+            EndpointType::Public => format!("https://{}.oss-{region}.aliyuncs.com", self.bucket),
+            EndpointType::Internal => {
+                format!("https://{}.oss-{region}-internal.aliyuncs.com", self.bucket)
+            }
+            EndpointType::Accelerate => {
+                format!("https://{}.oss-accelerate.aliyuncs.com", self.bucket)
+            }
         }
     }
 
+    /// Specify the signing time.
+    ///
+    /// # Note
+    ///
+    /// We should always take current time to sign requests.
+    /// Only use this function for testing.
+    #[cfg(test)]
+    pub fn time(mut self, time: DateTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+
     /// Building a signing context.
     fn build(
         &self,
@@ -44,16 +121,21 @@ impl Signer {
         method: SigningMethod,
         cred: &Credential,
     ) -> Result<SigningContext> {
-        let now = time::now();
-        let mut ctx = req.build()?;
+        let now = self.time.unwrap_or_else(time::now);
+        let mut ctx = build_context(req)?;
+
+        // Date provided by caller takes precedence.
+        if method == SigningMethod::Header && ctx.headers.get(DATE).is_none() {
+            ctx.headers.insert(DATE, format_http_date(now).parse()?);
+        }
 
-        let string_to_sign = string_to_sign(&mut ctx, cred, now, method, &self.bucket)?;
+        let path_style = self.is_path_style(&ctx);
+        let string_to_sign = string_to_sign(&mut ctx, cred, now, method, &self.bucket, path_style)?;
         let signature =
             base64_hmac_sha1(cred.access_key_secret.as_bytes(), string_to_sign.as_bytes());
 
         match method {
             SigningMethod::Header => {
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
                 ctx.headers.insert(AUTHORIZATION, {
                     let mut value: HeaderValue =
                         format!("OSS {}:{}", cred.access_key_id, signature).parse()?;
@@ -63,7 +145,9 @@ impl Signer {
                 });
             }
             SigningMethod::Query(expire) => {
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                if ctx.headers.get(DATE).is_none() {
+                    ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                }
                 ctx.query_push("OSSAccessKeyId", &cred.access_key_id);
                 ctx.query_push(
                     "Expires",
@@ -81,6 +165,22 @@ impl Signer {
         Ok(ctx)
     }
 
+    /// Check if the request is a path style request whose path already
+    /// contains the bucket.
+    fn is_path_style(&self, ctx: &SigningContext) -> bool {
+        if self.cname {
+            return false;
+        }
+
+        let host = ctx.authority.host();
+        if host.starts_with(&format!("{}.", self.bucket)) {
+            return false;
+        }
+
+        ctx.path == format!("/{}", self.bucket)
+            || ctx.path.starts_with(&format!("/{}/", self.bucket))
+    }
+
     /// Signing request with header.
     pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<()> {
         let ctx = self.build(req, SigningMethod::Header, cred)?;
@@ -99,6 +199,36 @@ impl Signer {
 
         req.apply(ctx)
     }
+
+    /// Pass the request through without signing for public-read buckets.
+    ///
+    /// No `Authorization` header will be added, while headers of the request
+    /// are kept as is. Anonymous requests must be sent via this method
+    /// explicitly, [`Signer::sign`] never falls back to it, so that missing
+    /// credentials won't produce unsigned requests silently.
+    pub fn sign_anonymous(&self, req: &mut impl SignableRequest) -> Result<()> {
+        let ctx = build_context(req)?;
+        req.apply(ctx)
+    }
+}
+
+/// Build the signing context of request.
+fn build_context(req: &mut impl SignableRequest) -> Result<SigningContext> {
+    // OSS treats `+` in query as is, for example in base64 encoded `callback`.
+    let mut ctx = req.build_with_literal_plus()?;
+    // Query params are decoded while building context, encode them back
+    // so that values like `callback` could be sent and signed as is.
+    ctx.query = ctx
+        .query
+        .into_iter()
+        .map(|(k, v)| {
+            (
+                utf8_percent_encode(&k, &OSS_QUERY_ENCODE_SET).to_string(),
+                utf8_percent_encode(&v, &OSS_QUERY_ENCODE_SET).to_string(),
+            )
+        })
+        .collect();
+    Ok(ctx)
 }
 
 /// Construct string to sign.
@@ -119,6 +249,7 @@ fn string_to_sign(
     now: DateTime,
     method: SigningMethod,
     bucket: &str,
+    path_style: bool,
 ) -> Result<String> {
     let mut s = String::new();
     s.write_str(ctx.method.as_str())?;
@@ -129,7 +260,7 @@ fn string_to_sign(
     s.write_str("\n")?;
     match method {
         SigningMethod::Header => {
-            writeln!(&mut s, "{}", format_http_date(now))?;
+            writeln!(&mut s, "{}", ctx.header_get_or_default(&DATE)?)?;
         }
         SigningMethod::Query(expires) => {
             writeln!(
@@ -149,7 +280,7 @@ fn string_to_sign(
     write!(
         &mut s,
         "{}",
-        canonicalize_resource(ctx, bucket, method, cred)
+        canonicalize_resource(ctx, bucket, path_style, method, cred)
     )?;
 
     debug!("string to sign: {}", &s);
@@ -174,7 +305,7 @@ fn canonicalize_header(
     }
 
     Ok(SigningContext::header_to_string(
-        ctx.header_to_vec_with_prefix("x-oss-"),
+        ctx.header_to_vec_with_prefix("x-oss-")?,
         ":",
         "\n",
     ))
@@ -188,6 +319,7 @@ fn canonicalize_header(
 fn canonicalize_resource(
     ctx: &mut SigningContext,
     bucket: &str,
+    path_style: bool,
     method: SigningMethod,
     cred: &Credential,
 ) -> String {
@@ -206,10 +338,23 @@ fn canonicalize_resource(
     // OSS requires that the query string be percent-decoded.
     let params_str = SigningContext::query_to_percent_decoded_string(params, "=", "&");
 
-    if params_str.is_empty() {
+    // Path style requests already have bucket in path.
+    let resource = if path_style {
+        ctx.path_percent_decoded().to_string()
+    } else {
         format!("/{bucket}{}", ctx.path_percent_decoded())
+    };
+    // Bucket level resource must end with `/`.
+    let resource = if resource == format!("/{bucket}") {
+        format!("{resource}/")
+    } else {
+        resource
+    };
+
+    if params_str.is_empty() {
+        resource
     } else {
-        format!("/{bucket}{}?{params_str}", ctx.path_percent_decoded())
+        format!("{resource}?{params_str}")
     }
 }
 
@@ -304,3 +449,197 @@ static SUB_RESOURCES: Lazy<HashSet<&'static str>> = Lazy::new(|| {
         "metaQuery",
     ])
 });
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::time::parse_rfc3339;
+
+    fn test_signer(bucket: &str) -> Signer {
+        Signer::new(bucket).time(parse_rfc3339("2022-03-13T07:20:04Z").expect("must be valid"))
+    }
+
+    fn test_credential() -> Credential {
+        Credential {
+            access_key_id: "access_key_id".to_string(),
+            access_key_secret: "access_key_secret".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test_case(
+        test_signer("test"),
+        "https://test.oss-cn-hangzhou.aliyuncs.com/object.txt",
+        "oHFWnugc8cZtS4sE6gfIMXNfyH0=";
+        "virtual hosted style"
+    )]
+    #[test_case(
+        test_signer("test").with_endpoint_type(EndpointType::Internal),
+        "https://test.oss-cn-hangzhou-internal.aliyuncs.com/object.txt",
+        "oHFWnugc8cZtS4sE6gfIMXNfyH0=";
+        "internal endpoint"
+    )]
+    #[test_case(
+        test_signer("test"),
+        "https://oss-cn-hangzhou-internal.aliyuncs.com/test/object.txt",
+        "oHFWnugc8cZtS4sE6gfIMXNfyH0=";
+        "path style"
+    )]
+    #[test_case(
+        test_signer("test"),
+        "http://[::1]:9000/test/object.txt",
+        "oHFWnugc8cZtS4sE6gfIMXNfyH0=";
+        "ipv6 path style with port"
+    )]
+    #[test_case(
+        test_signer("test"),
+        "http://[::1]/test/object.txt",
+        "oHFWnugc8cZtS4sE6gfIMXNfyH0=";
+        "ipv6 path style without port"
+    )]
+    #[test_case(
+        test_signer("test").with_cname(true),
+        "https://static.example.com/object.txt",
+        "oHFWnugc8cZtS4sE6gfIMXNfyH0=";
+        "cname"
+    )]
+    #[test_case(
+        test_signer("test").with_cname(true),
+        "https://static.example.com/test/object.txt",
+        "5v8XiW+9iUKu8KuQAM2nRCP9jzw=";
+        "cname with bucket prefixed key"
+    )]
+    fn test_sign(signer: Signer, uri: &str, expected: &str) -> Result<()> {
+        let mut req = Request::builder().uri(uri).body(())?;
+        signer.sign(&mut req, &test_credential())?;
+
+        assert_eq!(
+            format!("OSS access_key_id:{expected}"),
+            req.headers()[AUTHORIZATION]
+        );
+        assert_eq!("Sun, 13 Mar 2022 07:20:04 GMT", req.headers()[DATE]);
+        assert_eq!(uri, req.uri().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_with_caller_date() -> Result<()> {
+        let mut req = Request::builder()
+            .uri("https://test.oss-cn-hangzhou.aliyuncs.com/object.txt")
+            .header(DATE, "Sun, 13 Mar 2022 07:20:04 GMT")
+            .body(())?;
+        // Signing time is ignored since caller has provided the date.
+        Signer::new("test").sign(&mut req, &test_credential())?;
+
+        assert_eq!(
+            "OSS access_key_id:oHFWnugc8cZtS4sE6gfIMXNfyH0=",
+            req.headers()[AUTHORIZATION]
+        );
+        assert_eq!("Sun, 13 Mar 2022 07:20:04 GMT", req.headers()[DATE]);
+        Ok(())
+    }
+
+    /// Callback params are base64 encoded JSON, which contains `+`, `/` and `=`.
+    ///
+    /// The first case is the example in [OSS callback](https://help.aliyun.com/document_detail/31989.html).
+    #[test_case(
+        "eyJjYWxsYmFja1VybCI6IjEyMS40My4xMTMuODoyMzQ1Ni9pbmRleC5odG1sIiwgICJjYWxsYmFja0JvZHkiOiJidWNrZXQ9JHtidWNrZXR9Jm9iamVjdD0ke29iamVjdH0mZXRhZz0ke2V0YWd9JnNpemU9JHtzaXplfSZtaW1lVHlwZT0ke21pbWVUeXBlfSZpbWFnZUluZm8uaGVpZ2h0PSR7aW1hZ2VJbmZvLmhlaWdodH0maW1hZ2VJbmZvLndpZHRoPSR7aW1hZ2VJbmZvLndpZHRofSZpbWFnZUluZm8uZm9ybWF0PSR7aW1hZ2VJbmZvLmZvcm1hdH0mbXlfdmFyPSR7eDpteV92YXJ9In0=",
+        "eyJ4Om15X3ZhciI6ImZvcmNhbGxiYWNrIn0=",
+        "eyJjYWxsYmFja1VybCI6IjEyMS40My4xMTMuODoyMzQ1Ni9pbmRleC5odG1sIiwgICJjYWxsYmFja0JvZHkiOiJidWNrZXQ9JHtidWNrZXR9Jm9iamVjdD0ke29iamVjdH0mZXRhZz0ke2V0YWd9JnNpemU9JHtzaXplfSZtaW1lVHlwZT0ke21pbWVUeXBlfSZpbWFnZUluZm8uaGVpZ2h0PSR7aW1hZ2VJbmZvLmhlaWdodH0maW1hZ2VJbmZvLndpZHRoPSR7aW1hZ2VJbmZvLndpZHRofSZpbWFnZUluZm8uZm9ybWF0PSR7aW1hZ2VJbmZvLmZvcm1hdH0mbXlfdmFyPSR7eDpteV92YXJ9In0%3D",
+        "eyJ4Om15X3ZhciI6ImZvcmNhbGxiYWNrIn0%3D",
+        "E7qbqEo3+aQrgJEchBHGxqq8YMw=";
+        "official example"
+    )]
+    #[test_case(
+        "eyJjYWxsYmFja1VybCI6Imh0dHBzOi8vZXhhbXBsZS5jb20vY2FsbGJhY2siLCJjYWxsYmFja0JvZHkiOiJvYmplY3Q9JHtvYmplY3R9JnNpemU9JHtzaXplfSJ9",
+        "eyJ4OnZhciI6In5%2Bfj4%2BPj8%2FPyJ9",
+        "eyJjYWxsYmFja1VybCI6Imh0dHBzOi8vZXhhbXBsZS5jb20vY2FsbGJhY2siLCJjYWxsYmFja0JvZHkiOiJvYmplY3Q9JHtvYmplY3R9JnNpemU9JHtzaXplfSJ9",
+        "eyJ4OnZhciI6In5%2Bfj4%2BPj8%2FPyJ9",
+        "0NvwfVzWixSn0vXDMWuodHhlpiQ=";
+        "percent encoded"
+    )]
+    #[test_case(
+        "eyJjYWxsYmFja1VybCI6Imh0dHBzOi8vZXhhbXBsZS5jb20vY2FsbGJhY2siLCJjYWxsYmFja0JvZHkiOiJvYmplY3Q9JHtvYmplY3R9JnNpemU9JHtzaXplfSJ9",
+        "eyJ4OnZhciI6In5+fj4+Pj8/PyJ9",
+        "eyJjYWxsYmFja1VybCI6Imh0dHBzOi8vZXhhbXBsZS5jb20vY2FsbGJhY2siLCJjYWxsYmFja0JvZHkiOiJvYmplY3Q9JHtvYmplY3R9JnNpemU9JHtzaXplfSJ9",
+        "eyJ4OnZhciI6In5%2Bfj4%2BPj8%2FPyJ9",
+        "0NvwfVzWixSn0vXDMWuodHhlpiQ=";
+        "raw plus"
+    )]
+    fn test_sign_with_callback(
+        callback: &str,
+        callback_var: &str,
+        expected_callback: &str,
+        expected_callback_var: &str,
+        expected_signature: &str,
+    ) -> Result<()> {
+        let mut req = Request::put(format!(
+            "https://test.oss-cn-hangzhou.aliyuncs.com/object.txt?callback={callback}&callback-var={callback_var}"
+        ))
+        .header(CONTENT_TYPE, "text/plain")
+        .body(())?;
+        test_signer("test").sign(&mut req, &test_credential())?;
+
+        assert_eq!(
+            format!("OSS access_key_id:{expected_signature}"),
+            req.headers()[AUTHORIZATION]
+        );
+        // Callback params must keep their base64 value, `+` must not be
+        // decoded as space.
+        assert_eq!(
+            Some(
+                format!("callback={expected_callback}&callback-var={expected_callback_var}")
+                    .as_str()
+            ),
+            req.uri().query()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_endpoint() {
+        let signer = Signer::new("test");
+        assert_eq!(
+            "https://test.oss-cn-hangzhou.aliyuncs.com",
+            signer.endpoint("cn-hangzhou")
+        );
+
+        let signer = signer.with_endpoint_type(EndpointType::Internal);
+        assert_eq!(
+            "https://test.oss-cn-hangzhou-internal.aliyuncs.com",
+            signer.endpoint("oss-cn-hangzhou")
+        );
+
+        let signer = signer.with_endpoint_type(EndpointType::Accelerate);
+        assert_eq!(
+            "https://test.oss-accelerate.aliyuncs.com",
+            signer.endpoint("cn-hangzhou")
+        );
+    }
+
+    #[test]
+    fn test_sign_anonymous() -> Result<()> {
+        let mut req = Request::get(
+            "https://test.oss-cn-hangzhou.aliyuncs.com/object.txt?x-oss-process=image%2Fresize%2Cw_100",
+        )
+        .header("x-oss-meta-owner", "reqsign")
+        .header("x-oss-request-payer", "requester")
+        .body(())?;
+
+        test_signer("test").sign_anonymous(&mut req)?;
+        assert!(req.headers().get(AUTHORIZATION).is_none());
+        assert!(req.headers().get(DATE).is_none());
+        assert_eq!("reqsign", req.headers()["x-oss-meta-owner"]);
+        assert_eq!("requester", req.headers()["x-oss-request-payer"]);
+        assert_eq!(
+            "https://test.oss-cn-hangzhou.aliyuncs.com/object.txt?x-oss-process=image%2Fresize%2Cw_100",
+            req.uri().to_string()
+        );
+
+        Ok(())
+    }
+}
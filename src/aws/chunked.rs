@@ -0,0 +1,174 @@
+//! Chunked upload (`aws-chunked`) support for AWS SigV4.
+//!
+//! - [Signature Calculations for the Authorization Header: Transferring Payload in Multiple Chunks](https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-streaming.html)
+//! - [Signature Calculation: Including Trailing Headers](https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-streaming-trailers.html)
+
+use std::fmt::Write;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use super::credential::payload_sha256;
+use super::credential::EMPTY_STRING_SHA256;
+use crate::hash::hex_hmac_sha256;
+use crate::hash::hex_sha256;
+
+/// Type of the streaming payload, which will be used as `x-amz-content-sha256`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamingPayload {
+    /// `STREAMING-AWS4-HMAC-SHA256-PAYLOAD`: every chunk is signed.
+    Signed,
+    /// `STREAMING-AWS4-HMAC-SHA256-PAYLOAD-TRAILER`: every chunk and the
+    /// trailing headers are signed.
+    SignedWithTrailer,
+    /// `STREAMING-UNSIGNED-PAYLOAD-TRAILER`: chunks and trailing headers are
+    /// sent without signatures.
+    UnsignedWithTrailer,
+}
+
+impl StreamingPayload {
+    /// The value of `x-amz-content-sha256` for this payload.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamingPayload::Signed => "STREAMING-AWS4-HMAC-SHA256-PAYLOAD",
+            StreamingPayload::SignedWithTrailer => "STREAMING-AWS4-HMAC-SHA256-PAYLOAD-TRAILER",
+            StreamingPayload::UnsignedWithTrailer => "STREAMING-UNSIGNED-PAYLOAD-TRAILER",
+        }
+    }
+
+    /// Does this payload carry trailing headers?
+    pub fn has_trailer(&self) -> bool {
+        !matches!(self, StreamingPayload::Signed)
+    }
+
+    fn is_signed(&self) -> bool {
+        !matches!(self, StreamingPayload::UnsignedWithTrailer)
+    }
+}
+
+/// ChunkSigner encodes chunks of payload into `aws-chunked` framing.
+///
+/// Built by [`Signer::sign_chunked`](super::v4::Signer::sign_chunked). Every
+/// chunk's signature is chained to the previous one, so chunks must be
+/// encoded in order.
+pub struct ChunkSigner {
+    payload: StreamingPayload,
+    signing_key: Vec<u8>,
+    time: String,
+    scope: String,
+    signature: String,
+    finished: bool,
+}
+
+impl ChunkSigner {
+    pub(super) fn new(
+        payload: StreamingPayload,
+        signing_key: Vec<u8>,
+        time: String,
+        scope: String,
+        seed_signature: String,
+    ) -> Self {
+        Self {
+            payload,
+            signing_key,
+            time,
+            scope,
+            signature: seed_signature,
+            finished: false,
+        }
+    }
+
+    /// Get the signature of the last signed part.
+    ///
+    /// Before any chunk has been encoded, this is the seed signature in
+    /// `Authorization` header.
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Encode a chunk of payload.
+    ///
+    /// All chunks except the last one must have at least 8 KiB data, use
+    /// [`ChunkSigner::finish`] to write the last empty chunk.
+    pub fn encode_chunk(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        if self.finished {
+            return Err(anyhow!("chunk signer has been finished"));
+        }
+        if data.is_empty() {
+            return Err(anyhow!("empty chunk is not allowed, use finish instead"));
+        }
+
+        let mut buf = self.chunk_header(data)?.into_bytes();
+        buf.extend_from_slice(data);
+        buf.extend_from_slice(b"\r\n");
+        Ok(buf)
+    }
+
+    /// Encode the final empty chunk and the trailing headers.
+    ///
+    /// `trailers` must match the `x-amz-trailer` header of the request, and
+    /// must be empty for [`StreamingPayload::Signed`].
+    pub fn finish(&mut self, trailers: &[(&str, &str)]) -> Result<Vec<u8>> {
+        if self.finished {
+            return Err(anyhow!("chunk signer has been finished"));
+        }
+        if !self.payload.has_trailer() && !trailers.is_empty() {
+            return Err(anyhow!(
+                "trailers are not allowed for {}",
+                self.payload.as_str()
+            ));
+        }
+        self.finished = true;
+
+        let mut s = self.chunk_header(&[])?;
+        if !self.payload.has_trailer() {
+            s.push_str("\r\n");
+            return Ok(s.into_bytes());
+        }
+
+        // Trailing headers are signed with `\n` as separator but sent with `\r\n`.
+        let mut canonical_trailers = String::new();
+        for (k, v) in trailers {
+            let k = k.to_lowercase();
+            let v = v.trim();
+            writeln!(canonical_trailers, "{k}:{v}")?;
+            write!(s, "{k}:{v}\r\n")?;
+        }
+
+        if self.payload.is_signed() {
+            let string_to_sign = format!(
+                "AWS4-HMAC-SHA256-TRAILER\n{}\n{}\n{}\n{}",
+                self.time,
+                self.scope,
+                self.signature,
+                hex_sha256(canonical_trailers.as_bytes())
+            );
+            self.signature = hex_hmac_sha256(&self.signing_key, string_to_sign.as_bytes());
+            write!(s, "x-amz-trailer-signature:{}\r\n", self.signature)?;
+        }
+        s.push_str("\r\n");
+
+        Ok(s.into_bytes())
+    }
+
+    /// Build the chunk header like `10000;chunk-signature=<signature>\r\n`.
+    fn chunk_header(&mut self, data: &[u8]) -> Result<String> {
+        let mut s = format!("{:x}", data.len());
+
+        if self.payload.is_signed() {
+            let string_to_sign = format!(
+                "AWS4-HMAC-SHA256-PAYLOAD\n{}\n{}\n{}\n{}\n{}",
+                self.time,
+                self.scope,
+                self.signature,
+                EMPTY_STRING_SHA256,
+                payload_sha256(data)
+            );
+            self.signature = hex_hmac_sha256(&self.signing_key, string_to_sign.as_bytes());
+            write!(s, ";chunk-signature={}", self.signature)?;
+        }
+        s.push_str("\r\n");
+
+        Ok(s)
+    }
+}
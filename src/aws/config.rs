@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-use std::env;
 #[cfg(not(target_arch = "wasm32"))]
 use std::fs;
 
@@ -15,6 +13,11 @@ use log::debug;
 use super::constants::*;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::dirs::expand_homedir;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::time::parse_rfc3339;
+use crate::time::DateTime;
+use crate::EnvSnapshot;
+use crate::Warning;
 
 /// Config for aws services.
 #[derive(Clone)]
@@ -49,6 +52,14 @@ pub struct Config {
     /// - profile config: `sts_regional_endpoints`
     /// - default to `legacy`
     pub sts_regional_endpoints: String,
+    /// `sts_endpoint` will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_ENDPOINT_URL_STS`]
+    ///
+    /// The value should be a full url like `https://sts.amazonaws.com`. If not
+    /// set, the endpoint will be decided by `region` and `sts_regional_endpoints`.
+    pub sts_endpoint: Option<String>,
     /// `access_key_id` will be loaded from
     ///
     /// - this field if it's `is_some`
@@ -67,6 +78,13 @@ pub struct Config {
     /// - env value: [`AWS_SESSION_TOKEN`]
     /// - profile config: `aws_session_token`
     pub session_token: Option<String>,
+    /// `expires_in` of the credential above, will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    /// - profile credentials: `x_security_token_expires` or `expiration`
+    ///
+    /// Credentials with expiration will be treated as temporary credentials.
+    pub expires_in: Option<DateTime>,
     /// `role_arn` value will be load from:
     ///
     /// - this field if it's `is_some`.
@@ -84,17 +102,66 @@ pub struct Config {
     /// - this field if it's `is_some`.
     /// - profile config: `external_id`
     pub external_id: Option<String>,
+    /// `duration_seconds` of the assumed role session, will be loaded from:
+    ///
+    /// - this field if it's `is_some`.
+    /// - profile config: `duration_seconds`
+    ///
+    /// Must be between 900 (15 minutes) and 43200 (12 hours), and not
+    /// larger than the max session duration of the role.
+    pub duration_seconds: Option<u32>,
+    /// Inline session `policy` passed to `AssumeRole`, used to scope down
+    /// the permissions of the assumed role session.
+    pub policy: Option<String>,
+    /// ARNs of managed `policy_arns` passed to `AssumeRole`, used to scope
+    /// down the permissions of the assumed role session.
+    pub policy_arns: Vec<String>,
     /// `web_identity_token_file` value will be loaded from:
     ///
     /// - this field if it's `is_some`
     /// - env value: [`AWS_WEB_IDENTITY_TOKEN_FILE`]
     /// - profile config: `web_identity_token_file`
     pub web_identity_token_file: Option<String>,
+    /// `credential_process` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - profile config: `credential_process`
+    ///
+    /// The command will be executed via shell, and must print credentials in
+    /// [process credentials](https://docs.aws.amazon.com/sdkref/latest/guide/feature-process-credentials.html)
+    /// format to stdout.
+    pub credential_process: Option<String>,
     /// `ec2_metadata_disabled` value will be loaded from:
     ///
     /// - this field
     /// - env value: [`AWS_EC2_METADATA_DISABLED`]
     pub ec2_metadata_disabled: bool,
+    /// `container_credentials_relative_uri` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_CREDENTIALS_RELATIVE_URI`]
+    ///
+    /// The path is relative to the ECS container endpoint `http://169.254.170.2`.
+    pub container_credentials_relative_uri: Option<String>,
+    /// `container_credentials_full_uri` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_CREDENTIALS_FULL_URI`]
+    ///
+    /// Only loopback, ECS and EKS container hosts are allowed by default.
+    pub container_credentials_full_uri: Option<String>,
+    /// `container_authorization_token` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_AUTHORIZATION_TOKEN`]
+    pub container_authorization_token: Option<String>,
+    /// `container_authorization_token_file` value will be loaded from:
+    ///
+    /// - this field if it's `is_some`
+    /// - env value: [`AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE`]
+    ///
+    /// Takes precedence over `container_authorization_token`.
+    pub container_authorization_token_file: Option<String>,
 }
 
 impl Default for Config {
@@ -105,22 +172,179 @@ impl Default for Config {
             profile: "default".to_string(),
             region: None,
             sts_regional_endpoints: "legacy".to_string(),
+            sts_endpoint: None,
             access_key_id: None,
             secret_access_key: None,
             session_token: None,
+            expires_in: None,
             role_arn: None,
             role_session_name: "reqsign".to_string(),
             external_id: None,
+            duration_seconds: None,
+            policy: None,
+            policy_arns: Vec::new(),
             web_identity_token_file: None,
+            credential_process: None,
             ec2_metadata_disabled: false,
+            container_credentials_relative_uri: None,
+            container_credentials_full_uri: None,
+            container_authorization_token: None,
+            container_authorization_token_file: None,
         }
     }
 }
 
 impl Config {
+    /// Merge `other` over this config, `other` takes precedence.
+    ///
+    /// Fields of `other` win if they are `is_some` (or not empty), while
+    /// other fields win if they are different from the default value.
+    ///
+    /// Static credential fields (`access_key_id`, `secret_access_key`,
+    /// `session_token` and `expires_in`) are merged as a unit: all of them
+    /// are taken from `other` if its `access_key_id` is set, so a secret key
+    /// from one source is never paired with the access key of another.
+    ///
+    /// For example, the following config will take credentials from env
+    /// first, and then fallback to profile:
+    ///
+    /// ```no_run
+    /// use reqsign::AwsConfig;
+    ///
+    /// let config = AwsConfig::default()
+    ///     .from_profile()
+    ///     .merge(AwsConfig::default().from_env());
+    /// ```
+    pub fn merge(self, other: Config) -> Config {
+        let default = Config::default();
+        let Config {
+            config_file,
+            shared_credentials_file,
+            profile,
+            region,
+            sts_regional_endpoints,
+            sts_endpoint,
+            access_key_id,
+            secret_access_key,
+            session_token,
+            expires_in,
+            role_arn,
+            role_session_name,
+            external_id,
+            duration_seconds,
+            policy,
+            policy_arns,
+            web_identity_token_file,
+            credential_process,
+            ec2_metadata_disabled,
+            container_credentials_relative_uri,
+            container_credentials_full_uri,
+            container_authorization_token,
+            container_authorization_token_file,
+        } = other;
+
+        let (access_key_id, secret_access_key, session_token, expires_in) =
+            if access_key_id.is_some() {
+                (access_key_id, secret_access_key, session_token, expires_in)
+            } else {
+                (
+                    self.access_key_id,
+                    self.secret_access_key,
+                    self.session_token,
+                    self.expires_in,
+                )
+            };
+
+        let pick = |high: String, low: String, default: &str| {
+            if high != default {
+                high
+            } else {
+                low
+            }
+        };
+
+        Config {
+            config_file: pick(config_file, self.config_file, &default.config_file),
+            shared_credentials_file: pick(
+                shared_credentials_file,
+                self.shared_credentials_file,
+                &default.shared_credentials_file,
+            ),
+            profile: pick(profile, self.profile, &default.profile),
+            region: region.or(self.region),
+            sts_regional_endpoints: pick(
+                sts_regional_endpoints,
+                self.sts_regional_endpoints,
+                &default.sts_regional_endpoints,
+            ),
+            sts_endpoint: sts_endpoint.or(self.sts_endpoint),
+            access_key_id,
+            secret_access_key,
+            session_token,
+            expires_in,
+            role_arn: role_arn.or(self.role_arn),
+            role_session_name: pick(
+                role_session_name,
+                self.role_session_name,
+                &default.role_session_name,
+            ),
+            external_id: external_id.or(self.external_id),
+            duration_seconds: duration_seconds.or(self.duration_seconds),
+            policy: policy.or(self.policy),
+            policy_arns: if policy_arns.is_empty() {
+                self.policy_arns
+            } else {
+                policy_arns
+            },
+            web_identity_token_file: web_identity_token_file.or(self.web_identity_token_file),
+            credential_process: credential_process.or(self.credential_process),
+            ec2_metadata_disabled: ec2_metadata_disabled || self.ec2_metadata_disabled,
+            container_credentials_relative_uri: container_credentials_relative_uri
+                .or(self.container_credentials_relative_uri),
+            container_credentials_full_uri: container_credentials_full_uri
+                .or(self.container_credentials_full_uri),
+            container_authorization_token: container_authorization_token
+                .or(self.container_authorization_token),
+            container_authorization_token_file: container_authorization_token_file
+                .or(self.container_authorization_token_file),
+        }
+    }
+
+    /// Check this config for deprecated or ignored settings.
+    ///
+    /// The returned warnings are not fatal, but users should migrate before
+    /// those settings stop working.
+    pub fn validate_with_warnings(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+
+        match self.sts_regional_endpoints.as_str() {
+            "regional" => {}
+            "legacy" => {
+                let use_sts = self.role_arn.is_some() || self.web_identity_token_file.is_some();
+                if use_sts && self.sts_endpoint.is_none() {
+                    warnings.push(Warning::new(
+                        "sts_regional_endpoints",
+                        "the global sts endpoint is deprecated by AWS, set it to `regional` instead",
+                    ));
+                }
+            }
+            v => warnings.push(Warning::new(
+                "sts_regional_endpoints",
+                format!("unknown value `{v}` is treated as `legacy`, use `regional` or `legacy`"),
+            )),
+        }
+
+        warnings
+    }
+
     /// Load config from env.
-    pub fn from_env(mut self) -> Self {
-        let envs = env::vars().collect::<HashMap<_, _>>();
+    pub fn from_env(self) -> Self {
+        self.from_env_with(&EnvSnapshot::from_env())
+    }
+
+    /// Load config from the given env snapshot.
+    pub fn from_env_with(mut self, env: &EnvSnapshot) -> Self {
+        let envs = env.envs();
 
         if let Some(v) = envs.get(AWS_CONFIG_FILE) {
             self.config_file = v.to_string();
@@ -137,6 +361,9 @@ impl Config {
         if let Some(v) = envs.get(AWS_STS_REGIONAL_ENDPOINTS) {
             self.sts_regional_endpoints = v.to_string();
         }
+        if let Some(v) = envs.get(AWS_ENDPOINT_URL_STS) {
+            self.sts_endpoint = Some(v.to_string());
+        }
         if let Some(v) = envs.get(AWS_ACCESS_KEY_ID) {
             self.access_key_id = Some(v.to_string())
         }
@@ -158,6 +385,18 @@ impl Config {
         if let Some(v) = envs.get(AWS_EC2_METADATA_DISABLED) {
             self.ec2_metadata_disabled = v == "true";
         }
+        if let Some(v) = envs.get(AWS_CONTAINER_CREDENTIALS_RELATIVE_URI) {
+            self.container_credentials_relative_uri = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_CONTAINER_CREDENTIALS_FULL_URI) {
+            self.container_credentials_full_uri = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_CONTAINER_AUTHORIZATION_TOKEN) {
+            self.container_authorization_token = Some(v.to_string());
+        }
+        if let Some(v) = envs.get(AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE) {
+            self.container_authorization_token_file = Some(v.to_string());
+        }
         self
     }
 
@@ -166,21 +405,30 @@ impl Config {
     /// If the env var AWS_PROFILE is set, this profile will be used,
     /// otherwise the contents of `self.profile` will be used.
     #[cfg(not(target_arch = "wasm32"))]
-    pub fn from_profile(mut self) -> Self {
+    pub fn from_profile(self) -> Self {
+        self.from_profile_with(&EnvSnapshot::from_env())
+    }
+
+    /// Load config from profile (and shared profile) with the given env
+    /// snapshot.
+    ///
+    /// See [`Config::from_profile`] for details.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_profile_with(mut self, env: &EnvSnapshot) -> Self {
         // self.profile is checked by the two load methods.
-        if let Ok(profile) = env::var(AWS_PROFILE) {
-            self.profile = profile;
+        if let Some(profile) = env.env_var(AWS_PROFILE) {
+            self.profile = profile.to_string();
         }
 
         // make sure we're getting profile info from the correct place.
         // Respecting these env vars also makes it possible to unit test
         // this method.
-        if let Ok(config_file) = env::var(AWS_CONFIG_FILE) {
-            self.config_file = config_file;
+        if let Some(config_file) = env.env_var(AWS_CONFIG_FILE) {
+            self.config_file = config_file.to_string();
         }
 
-        if let Ok(shared_credentials_file) = env::var(AWS_SHARED_CREDENTIALS_FILE) {
-            self.shared_credentials_file = shared_credentials_file;
+        if let Some(shared_credentials_file) = env.env_var(AWS_SHARED_CREDENTIALS_FILE) {
+            self.shared_credentials_file = shared_credentials_file.to_string();
         }
 
         // Ignore all errors happened internally.
@@ -200,6 +448,8 @@ impl Config {
     /// - `aws_access_key_id`
     /// - `aws_secret_access_key`
     /// - `aws_session_token`
+    /// - `x_security_token_expires` or `expiration`, which is written by tools
+    ///   that export temporary credentials.
     #[cfg(not(target_arch = "wasm32"))]
     fn load_via_profile_shared_credentials_file(&mut self) -> Result<()> {
         let path = expand_homedir(&self.shared_credentials_file)
@@ -222,6 +472,15 @@ impl Config {
         if let Some(v) = props.get("aws_session_token") {
             self.session_token = Some(v.to_string())
         }
+        if let Some(v) = props
+            .get("x_security_token_expires")
+            .or_else(|| props.get("expiration"))
+        {
+            match parse_rfc3339(v) {
+                Ok(v) => self.expires_in = Some(v),
+                Err(err) => debug!("parse expiration {v} of profile failed: {err:?}"),
+            }
+        }
 
         Ok(())
     }
@@ -264,9 +523,21 @@ impl Config {
         if let Some(v) = props.get("role_session_name") {
             self.role_session_name = v.to_string()
         }
+        if let Some(v) = props.get("external_id") {
+            self.external_id = Some(v.to_string())
+        }
+        if let Some(v) = props.get("duration_seconds") {
+            match v.parse() {
+                Ok(v) => self.duration_seconds = Some(v),
+                Err(err) => debug!("ignore invalid duration_seconds {v}: {err}"),
+            }
+        }
         if let Some(v) = props.get("web_identity_token_file") {
             self.web_identity_token_file = Some(v.to_string())
         }
+        if let Some(v) = props.get("credential_process") {
+            self.credential_process = Some(v.to_string())
+        }
 
         Ok(())
     }
@@ -327,6 +598,41 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_config_from_profile_shared_credentials_with_expiration() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let file_path = format!(
+            "{}/testdata/services/aws/temporary_credential",
+            std::env::current_dir()?.to_string_lossy()
+        );
+
+        for (profile, token, expiration) in [
+            ("default", "temporary_session_token", "2099-01-01T00:00:00Z"),
+            ("expired", "expired_session_token", "2020-01-01T00:00:00Z"),
+        ] {
+            temp_env::with_vars(
+                [
+                    (AWS_PROFILE, Some(profile)),
+                    (AWS_CONFIG_FILE, None),
+                    (AWS_SHARED_CREDENTIALS_FILE, Some(file_path.as_str())),
+                ],
+                || {
+                    let config = Config::default().from_profile();
+
+                    assert_eq!(config.session_token.as_deref(), Some(token));
+                    assert_eq!(
+                        config.expires_in,
+                        Some(parse_rfc3339(expiration).expect("must be valid time"))
+                    );
+                },
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(not(target_arch = "wasm32"))]
     fn test_config_from_profile_config() -> Result<()> {
@@ -345,6 +651,16 @@ mod tests {
         writeln!(tmp_file, "aws_access_key_id = PROFILE1ACCESSKEYID")?;
         writeln!(tmp_file, "aws_secret_access_key = PROFILE1SECRETACCESSKEY")?;
         writeln!(tmp_file, "aws_session_token = PROFILE1SESSIONTOKEN")?;
+        writeln!(
+            tmp_file,
+            "role_arn = arn:aws:iam::123456789012:role/PROFILE1ROLE"
+        )?;
+        writeln!(tmp_file, "external_id = PROFILE1EXTERNALID")?;
+        writeln!(tmp_file, "duration_seconds = 7200")?;
+        writeln!(
+            tmp_file,
+            "credential_process = /opt/bin/awscreds --profile1"
+        )?;
 
         temp_env::with_vars(
             [
@@ -368,9 +684,161 @@ mod tests {
                     config.session_token,
                     Some("PROFILE1SESSIONTOKEN".to_owned())
                 );
+                assert_eq!(
+                    config.role_arn,
+                    Some("arn:aws:iam::123456789012:role/PROFILE1ROLE".to_owned())
+                );
+                assert_eq!(config.external_id, Some("PROFILE1EXTERNALID".to_owned()));
+                assert_eq!(config.duration_seconds, Some(7200));
+                assert_eq!(
+                    config.credential_process,
+                    Some("/opt/bin/awscreds --profile1".to_owned())
+                );
+            },
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_merge() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let tmp_dir = tempdir()?;
+        let file_path = tmp_dir.path().join("credentials");
+        let mut tmp_file = File::create(&file_path)?;
+        writeln!(tmp_file, "[default]")?;
+        writeln!(tmp_file, "aws_access_key_id = FILEACCESSKEYID")?;
+        writeln!(tmp_file, "aws_secret_access_key = FILESECRETACCESSKEY")?;
+        writeln!(tmp_file, "aws_session_token = FILESESSIONTOKEN")?;
+
+        temp_env::with_vars(
+            [
+                (AWS_PROFILE, None),
+                (AWS_CONFIG_FILE, Some("/not/exist")),
+                (
+                    AWS_SHARED_CREDENTIALS_FILE,
+                    Some(file_path.to_str().unwrap()),
+                ),
+                (AWS_ACCESS_KEY_ID, Some("ENVACCESSKEYID")),
+                (AWS_SECRET_ACCESS_KEY, None),
+                (AWS_SESSION_TOKEN, None),
+                (AWS_REGION, Some("us-west-2")),
+                (AWS_ROLE_ARN, None),
+                (AWS_ROLE_SESSION_NAME, None),
+                (AWS_STS_REGIONAL_ENDPOINTS, None),
+                (AWS_ENDPOINT_URL_STS, None),
+                (AWS_WEB_IDENTITY_TOKEN_FILE, None),
+                (AWS_EC2_METADATA_DISABLED, None),
+            ],
+            || {
+                let file = Config {
+                    region: Some("us-east-1".to_string()),
+                    role_session_name: "file".to_string(),
+                    ..Default::default()
+                }
+                .from_profile();
+                let env = Config::default().from_env();
+                let config = file.clone().merge(env);
+
+                // Env wins if set.
+                assert_eq!(Some("ENVACCESSKEYID".to_string()), config.access_key_id);
+                assert_eq!(Some("us-west-2".to_string()), config.region);
+                // Credential is taken from env as a unit, never mixed with file.
+                assert_eq!(None, config.secret_access_key);
+                assert_eq!(None, config.session_token);
+
+                // Fallback to file as a unit if env doesn't have access key.
+                let config = file.merge(Config {
+                    secret_access_key: Some("ENVSECRETACCESSKEY".to_string()),
+                    ..Default::default()
+                });
+                assert_eq!(Some("FILEACCESSKEYID".to_string()), config.access_key_id);
+                assert_eq!(
+                    Some("FILESECRETACCESSKEY".to_string()),
+                    config.secret_access_key
+                );
+                assert_eq!(Some("FILESESSIONTOKEN".to_string()), config.session_token);
+                // Default value of env doesn't override the lower one.
+                assert_eq!("file", config.role_session_name);
+                assert_eq!("default", config.profile);
+                assert_eq!("/not/exist", config.config_file);
+                assert_eq!(None, config.role_arn);
             },
         );
 
         Ok(())
     }
+
+    #[test]
+    fn test_validate_with_warnings() {
+        let config = Config::default();
+        assert!(config.validate_with_warnings().is_empty());
+
+        // Global sts endpoint is only deprecated if sts is used.
+        let config = Config {
+            role_arn: Some("arn:aws:iam::123456789012:role/test".to_string()),
+            ..Default::default()
+        };
+        let warnings = config.validate_with_warnings();
+        assert_eq!(1, warnings.len());
+        assert_eq!("sts_regional_endpoints", warnings[0].field);
+
+        let config = Config {
+            role_arn: Some("arn:aws:iam::123456789012:role/test".to_string()),
+            sts_regional_endpoints: "regional".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate_with_warnings().is_empty());
+
+        let config = Config {
+            sts_regional_endpoints: "Regional".to_string(),
+            ..Default::default()
+        };
+        let warnings = config.validate_with_warnings();
+        assert_eq!(1, warnings.len());
+        assert!(warnings[0].message.contains("`Regional`"));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_config_from_env_with_concurrently() -> Result<()> {
+        let tmp_dir = tempdir()?;
+        let file_path = tmp_dir.path().join("config");
+        let mut tmp_file = File::create(&file_path)?;
+        writeln!(tmp_file, "[profile profile1]")?;
+        writeln!(tmp_file, "region = us-west-1")?;
+        writeln!(tmp_file, "[profile profile2]")?;
+        writeln!(tmp_file, "region = us-west-2")?;
+        let file_path = file_path.to_string_lossy().to_string();
+
+        std::thread::scope(|s| {
+            let handles = ["1", "2"].map(|id| {
+                let env = EnvSnapshot::new()
+                    .with_env(AWS_ACCESS_KEY_ID, format!("access_key_id_{id}"))
+                    .with_env(AWS_PROFILE, format!("profile{id}"))
+                    .with_env(AWS_CONFIG_FILE, &file_path)
+                    .with_env(AWS_SHARED_CREDENTIALS_FILE, "/not/exist");
+                s.spawn(move || {
+                    (0..100)
+                        .map(|_| {
+                            Config::default()
+                                .from_env_with(&env)
+                                .from_profile_with(&env)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            });
+
+            for (id, handle) in ["1", "2"].into_iter().zip(handles) {
+                for config in handle.join().expect("thread must succeed") {
+                    assert_eq!(Some(format!("access_key_id_{id}")), config.access_key_id);
+                    assert_eq!(format!("profile{id}"), config.profile);
+                    assert_eq!(Some(format!("us-west-{id}")), config.region);
+                }
+            }
+        });
+
+        Ok(())
+    }
 }
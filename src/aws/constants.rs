@@ -5,6 +5,13 @@ use percent_encoding::NON_ALPHANUMERIC;
 pub const X_AMZ_CONTENT_SHA_256: &str = "x-amz-content-sha256";
 pub const X_AMZ_DATE: &str = "x-amz-date";
 pub const X_AMZ_SECURITY_TOKEN: &str = "x-amz-security-token";
+pub const X_AMZ_DECODED_CONTENT_LENGTH: &str = "x-amz-decoded-content-length";
+pub const X_AMZ_TRAILER: &str = "x-amz-trailer";
+pub const X_AMZ_BUCKET_REGION: &str = "x-amz-bucket-region";
+pub const X_AMZ_GLACIER_VERSION: &str = "x-amz-glacier-version";
+
+/// The API version required by Glacier in `x-amz-glacier-version`.
+pub const GLACIER_API_VERSION: &str = "2012-06-01";
 
 // Env values used in aws services.
 pub const AWS_ACCESS_KEY_ID: &str = "AWS_ACCESS_KEY_ID";
@@ -19,6 +26,17 @@ pub const AWS_ROLE_ARN: &str = "AWS_ROLE_ARN";
 pub const AWS_ROLE_SESSION_NAME: &str = "AWS_ROLE_SESSION_NAME";
 pub const AWS_STS_REGIONAL_ENDPOINTS: &str = "AWS_STS_REGIONAL_ENDPOINTS";
 pub const AWS_EC2_METADATA_DISABLED: &str = "AWS_EC2_METADATA_DISABLED";
+pub const AWS_ENDPOINT_URL_STS: &str = "AWS_ENDPOINT_URL_STS";
+pub const AWS_CONTAINER_CREDENTIALS_RELATIVE_URI: &str = "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI";
+pub const AWS_CONTAINER_CREDENTIALS_FULL_URI: &str = "AWS_CONTAINER_CREDENTIALS_FULL_URI";
+pub const AWS_CONTAINER_AUTHORIZATION_TOKEN: &str = "AWS_CONTAINER_AUTHORIZATION_TOKEN";
+pub const AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE: &str = "AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE";
+
+// Range of `DurationSeconds` accepted by STS `AssumeRole`.
+#[cfg(not(feature = "no_network"))]
+pub const STS_MIN_DURATION_SECONDS: u32 = 900;
+#[cfg(not(feature = "no_network"))]
+pub const STS_MAX_DURATION_SECONDS: u32 = 43200;
 
 /// AsciiSet for [AWS UriEncode](https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html)
 ///
@@ -30,6 +48,11 @@ pub static AWS_URI_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
     .remove(b'_')
     .remove(b'~');
 
+/// AsciiSet for [AWS UriEncode](https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html)
+///
+/// But keep the already percent-encoded characters untouched.
+pub static AWS_URI_ENCODE_ONCE_SET: AsciiSet = AWS_URI_ENCODE_SET.remove(b'%');
+
 /// AsciiSet for [AWS UriEncode](https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-header-based-auth.html)
 ///
 /// But used in query.
@@ -1,28 +1,75 @@
+use std::borrow::Cow;
 use std::fmt::Debug;
+#[cfg(not(feature = "no_network"))]
 use std::fmt::Write;
 use std::fs;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use anyhow::Result;
 use async_trait::async_trait;
+#[cfg(not(feature = "no_network"))]
+use http::header::AUTHORIZATION;
+#[cfg(not(feature = "no_network"))]
 use http::header::CONTENT_LENGTH;
+#[cfg(not(feature = "no_network"))]
+use http::header::USER_AGENT;
 use log::debug;
+#[cfg(not(feature = "no_network"))]
+use percent_encoding::utf8_percent_encode;
+#[cfg(not(feature = "no_network"))]
 use quick_xml::de;
 use reqwest::Client;
+#[cfg(any(not(target_arch = "wasm32"), not(feature = "no_network")))]
 use serde::Deserialize;
 
 use super::config::Config;
+#[cfg(not(target_arch = "wasm32"))]
+use super::constants::AWS_ACCESS_KEY_ID;
+#[cfg(not(feature = "no_network"))]
+use super::constants::AWS_QUERY_ENCODE_SET;
+#[cfg(not(target_arch = "wasm32"))]
+use super::constants::AWS_SECRET_ACCESS_KEY;
+#[cfg(not(feature = "no_network"))]
+use super::constants::STS_MAX_DURATION_SECONDS;
+#[cfg(not(feature = "no_network"))]
+use super::constants::STS_MIN_DURATION_SECONDS;
+#[cfg(not(feature = "no_network"))]
 use super::constants::X_AMZ_CONTENT_SHA_256;
+#[cfg(not(feature = "no_network"))]
 use super::v4::Signer;
+use crate::constants::DEFAULT_USER_AGENT;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::dirs::expand_homedir;
+use crate::hash::hex_sha256;
+use crate::metrics::Metrics;
+use crate::metrics::SharedMetrics;
+#[cfg(not(feature = "no_network"))]
+use crate::retry::RetryPolicy;
+use crate::time::format_rfc3339;
 use crate::time::now;
+#[cfg(any(not(target_arch = "wasm32"), not(feature = "no_network")))]
 use crate::time::parse_rfc3339;
 use crate::time::DateTime;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::EnvSnapshot;
 
 pub const EMPTY_STRING_SHA256: &str =
     "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
 
+/// Hex encoded sha256 of the payload.
+///
+/// Empty payload takes [`EMPTY_STRING_SHA256`] directly without hashing.
+pub fn payload_sha256(payload: &[u8]) -> Cow<'static, str> {
+    if payload.is_empty() {
+        Cow::Borrowed(EMPTY_STRING_SHA256)
+    } else {
+        Cow::Owned(hex_sha256(payload))
+    }
+}
+
 /// Credential that holds the access_key and secret_key.
 #[derive(Default, Clone)]
 #[cfg_attr(test, derive(Debug))]
@@ -35,9 +82,36 @@ pub struct Credential {
     pub session_token: Option<String>,
     /// Expiration time for this credential.
     pub expires_in: Option<DateTime>,
+    /// ARN of the identity that this credential belongs to, like the
+    /// `AssumedRoleUser` returned by STS.
+    ///
+    /// Only available if the credential source returns it, `None` for
+    /// static credentials.
+    pub arn: Option<String>,
+    /// Account id of the identity that this credential belongs to.
+    ///
+    /// Parsed from [`Credential::arn`] if not returned by the credential
+    /// source.
+    pub account_id: Option<String>,
 }
 
 impl Credential {
+    /// Create a [`CredentialBuilder`] to build static long-term credentials.
+    ///
+    /// ```
+    /// use reqsign::AwsCredential;
+    ///
+    /// let cred = AwsCredential::builder()
+    ///     .access_key("access_key_id")
+    ///     .secret_key("secret_access_key")
+    ///     .build()
+    ///     .expect("credential must be valid");
+    /// assert_eq!("access_key_id", cred.access_key_id);
+    /// ```
+    pub fn builder() -> CredentialBuilder {
+        CredentialBuilder::default()
+    }
+
     /// is current cred is valid?
     pub fn is_valid(&self) -> bool {
         if (self.access_key_id.is_empty() || self.secret_access_key.is_empty())
@@ -55,6 +129,149 @@ impl Credential {
 
         true
     }
+
+    /// Override the expiration time of this credential.
+    ///
+    /// This is useful when the credential provider doesn't return the
+    /// expiration, or returns an overly-optimistic one.
+    pub fn with_expires_in(mut self, expires_in: DateTime) -> Self {
+        self.expires_in = Some(expires_in);
+        self
+    }
+
+    /// Set the identity ARN of this credential, `account_id` will be parsed
+    /// from the ARN if not set.
+    #[cfg_attr(feature = "no_network", allow(dead_code))]
+    pub(crate) fn with_arn(mut self, arn: Option<String>) -> Self {
+        if self.account_id.is_none() {
+            self.account_id = arn.as_deref().and_then(account_id_from_arn);
+        }
+        self.arn = arn;
+        self
+    }
+}
+
+/// CredentialBuilder builds a [`Credential`] from static keys.
+///
+/// Created by [`Credential::builder`].
+#[derive(Default, Clone)]
+pub struct CredentialBuilder {
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    session_token: Option<String>,
+    expires_in: Option<DateTime>,
+}
+
+impl CredentialBuilder {
+    /// Set the access key id, required.
+    pub fn access_key(mut self, access_key: impl Into<String>) -> Self {
+        self.access_key = Some(access_key.into());
+        self
+    }
+
+    /// Set the secret access key, required.
+    pub fn secret_key(mut self, secret_key: impl Into<String>) -> Self {
+        self.secret_key = Some(secret_key.into());
+        self
+    }
+
+    /// Set the session token of temporary credentials.
+    pub fn session_token(mut self, session_token: impl Into<String>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    /// Set the expiration time of this credential.
+    pub fn expires_in(mut self, expires_in: DateTime) -> Self {
+        self.expires_in = Some(expires_in);
+        self
+    }
+
+    /// Build the credential, access key and secret key must be set and not
+    /// be empty.
+    pub fn build(self) -> std::result::Result<Credential, CredentialBuildError> {
+        let access_key_id = self
+            .access_key
+            .filter(|v| !v.is_empty())
+            .ok_or(CredentialBuildError::MissingAccessKey)?;
+        let secret_access_key = self
+            .secret_key
+            .filter(|v| !v.is_empty())
+            .ok_or(CredentialBuildError::MissingSecretKey)?;
+
+        Ok(Credential {
+            access_key_id,
+            secret_access_key,
+            session_token: self.session_token,
+            expires_in: self.expires_in,
+            ..Default::default()
+        })
+    }
+}
+
+/// Error returned by [`CredentialBuilder::build`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialBuildError {
+    /// Access key id is not set or empty.
+    MissingAccessKey,
+    /// Secret access key is not set or empty.
+    MissingSecretKey,
+}
+
+impl std::fmt::Display for CredentialBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CredentialBuildError::MissingAccessKey => write!(f, "access key is required"),
+            CredentialBuildError::MissingSecretKey => write!(f, "secret key is required"),
+        }
+    }
+}
+
+impl std::error::Error for CredentialBuildError {}
+
+/// Parse the account id from ARN like `arn:aws:sts::123456789012:assumed-role/demo/session`.
+#[cfg_attr(feature = "no_network", allow(dead_code))]
+fn account_id_from_arn(arn: &str) -> Option<String> {
+    let mut parts = arn.splitn(6, ':');
+    if parts.next() != Some("arn") {
+        return None;
+    }
+    let account_id = parts.nth(3)?;
+    (!account_id.is_empty()).then(|| account_id.to_string())
+}
+
+/// Status of a credential source, returned by [`DefaultLoader::describe_available`].
+///
+/// Secret values will never be included in `detail`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceStatus {
+    /// Name of the source, like `env` or `ec2_metadata`.
+    pub name: &'static str,
+    /// Is this source configured and ready to be used?
+    pub available: bool,
+    /// Human-readable description of the status.
+    pub detail: String,
+}
+
+impl SourceStatus {
+    fn new(name: &'static str, available: bool, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            available,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SourceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.available {
+            "available"
+        } else {
+            "unavailable"
+        };
+        write!(f, "{}: {status}, {}", self.name, self.detail)
+    }
 }
 
 /// Loader trait will try to load credential from different sources.
@@ -69,52 +286,424 @@ pub trait CredentialLoad: 'static + Send + Sync {
     async fn load_credential(&self, client: Client) -> Result<Option<Credential>>;
 }
 
+/// StaticCredentialProvider always returns the given credential.
+///
+/// This is useful if credentials are obtained outside reqsign, like from
+/// Vault or KMS. The credential won't be returned anymore once it's
+/// expired, so that the next provider in the chain could be used.
+#[derive(Clone)]
+pub struct StaticCredentialProvider {
+    credential: Credential,
+}
+
+impl StaticCredentialProvider {
+    /// Create a new static credential provider.
+    pub fn new(credential: Credential) -> Self {
+        Self { credential }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl CredentialLoad for StaticCredentialProvider {
+    async fn load_credential(&self, _: Client) -> Result<Option<Credential>> {
+        if !self.credential.is_valid() {
+            debug!("static credential has been expired");
+            return Ok(None);
+        }
+        Ok(Some(self.credential.clone()))
+    }
+}
+
 /// CredentialLoader will load credential from different methods.
+///
+/// With `no_network` feature enabled, only credentials from config (env and
+/// profile) will be loaded.
+#[cfg_attr(feature = "no_network", allow(dead_code))]
 pub struct DefaultLoader {
     client: Client,
     config: Config,
+    user_agent: String,
+    max_expires_in: Option<Duration>,
     credential: Arc<Mutex<Option<Credential>>>,
+    customized_credential_loader: Option<Box<dyn CredentialLoad>>,
+    metrics: SharedMetrics,
+    #[cfg(not(feature = "no_network"))]
+    container_loader: ContainerLoader,
+    #[cfg(not(feature = "no_network"))]
     imds_v2_loader: Option<IMDSv2Loader>,
+    #[cfg(not(feature = "no_network"))]
+    retry: RetryPolicy,
 }
 
 impl DefaultLoader {
     /// Create a new CredentialLoader
     pub fn new(client: Client, config: Config) -> Self {
+        #[cfg(not(feature = "no_network"))]
         let imds_v2_loader = if config.ec2_metadata_disabled {
             None
         } else {
             Some(IMDSv2Loader::new(client.clone()))
         };
+        #[cfg(not(feature = "no_network"))]
+        let container_loader = ContainerLoader::from_config(client.clone(), &config);
         Self {
             client,
             config,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            max_expires_in: None,
             credential: Arc::default(),
+            customized_credential_loader: None,
+            metrics: SharedMetrics::default(),
+            #[cfg(not(feature = "no_network"))]
+            container_loader,
+            #[cfg(not(feature = "no_network"))]
             imds_v2_loader,
+            #[cfg(not(feature = "no_network"))]
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Load credential via the given loader before all other sources.
+    ///
+    /// For example, use [`StaticCredentialProvider`] to sign requests with
+    /// a pre-built credential.
+    pub fn with_customized_credential_loader(mut self, loader: Box<dyn CredentialLoad>) -> Self {
+        self.customized_credential_loader = Some(loader);
+        self
+    }
+
+    /// Specify the metrics to record cache hits and refreshes of credential.
+    ///
+    /// Default to [`NoopMetrics`](crate::NoopMetrics).
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics.into();
+        self
+    }
+
+    /// Specify the `User-Agent` of requests sent to STS and ec2 metadata services.
+    ///
+    /// Default to `reqsign/{version}`.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        #[cfg(not(feature = "no_network"))]
+        {
+            self.container_loader = self.container_loader.with_user_agent(user_agent);
+            self.imds_v2_loader = self.imds_v2_loader.map(|l| l.with_user_agent(user_agent));
+        }
+        self
+    }
+
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
+
+    /// Specify the predicate to decide whether a response or a transport
+    /// error should be retried.
+    ///
+    /// By default, every request is sent only once. With a predicate set,
+    /// requests are attempted at most 3 times with exponential backoff while
+    /// the predicate returns `true`.
+    ///
+    /// The predicate is used by requests sent to STS, container endpoint and
+    /// ec2 metadata services.
+    #[cfg(not(feature = "no_network"))]
+    pub fn with_retry_policy(
+        mut self,
+        predicate: impl Fn(Result<&reqwest::Response, &reqwest::Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry = RetryPolicy::new(predicate);
+        self.container_loader.retry = self.retry.clone();
+        if let Some(loader) = &mut self.imds_v2_loader {
+            loader.retry = self.retry.clone();
         }
+        self
+    }
+
+    /// Clamp the expiration of loaded credentials to at most `max` from now.
+    ///
+    /// Credentials returned by providers that don't carry an expiration will
+    /// also be expired after `max`. The cached credential will be refreshed
+    /// 2 minutes before it's expired, so `max` should be larger than that.
+    pub fn with_max_expires_in(mut self, max: Duration) -> Self {
+        self.max_expires_in = Some(max);
+        self
+    }
+
+    /// Allow `host` to be used in `container_credentials_full_uri`.
+    ///
+    /// Only loopback, ECS and EKS container hosts are allowed by default.
+    #[cfg(not(feature = "no_network"))]
+    pub fn with_container_allowed_host(mut self, host: &str) -> Self {
+        self.container_loader = self.container_loader.with_allowed_host(host);
+        self
+    }
+
+    /// Allow any host to be used in `container_credentials_full_uri`.
+    ///
+    /// This is insecure: anyone who controls the env can redirect the
+    /// authorization token to an arbitrary host.
+    #[cfg(not(feature = "no_network"))]
+    pub fn with_container_allow_all_hosts(mut self) -> Self {
+        self.container_loader = self.container_loader.with_allow_all_hosts();
+        self
     }
 
     /// Disable load from ec2 metadata.
+    #[cfg(not(feature = "no_network"))]
     pub fn with_disable_ec2_metadata(mut self) -> Self {
         self.imds_v2_loader = None;
         self
     }
 
+    /// Specify the TTL of the ec2 metadata session token.
+    ///
+    /// The session token will be reused until it's expired.
+    #[cfg(not(feature = "no_network"))]
+    pub fn with_ec2_metadata_token_ttl(mut self, ttl: Duration) -> Self {
+        self.imds_v2_loader = self.imds_v2_loader.map(|l| l.with_token_ttl(ttl));
+        self
+    }
+
+    /// Specify the endpoint of ec2 metadata service.
+    ///
+    /// Default to `http://169.254.169.254`.
+    #[cfg(not(feature = "no_network"))]
+    pub fn with_ec2_metadata_endpoint(mut self, endpoint: &str) -> Self {
+        self.imds_v2_loader = self.imds_v2_loader.map(|l| l.with_endpoint(endpoint));
+        self
+    }
+
     /// Load credential.
     ///
     /// Resolution order:
+    /// 0. Customized loader set by [`Self::with_customized_credential_loader`]
     /// 1. Environment variables
     /// 2. Shared config (`~/.aws/config`, `~/.aws/credentials`)
-    /// 3. Web Identity Tokens
-    /// 4. ECS (IAM Roles for Tasks) & General HTTP credentials:
-    /// 5. EC2 IMDSv2
+    ///    - If `role_arn` is set, the role will be assumed with credentials above.
+    /// 3. Process credentials via `credential_process` in shared config
+    /// 4. Web Identity Tokens
+    /// 5. ECS (IAM Roles for Tasks) & General HTTP credentials:
+    /// 6. EC2 IMDSv2
+    ///
+    /// Only 0, 1, 2 and 3 are available with `no_network` feature enabled.
     pub async fn load(&self) -> Result<Option<Credential>> {
         // Return cached credential if it has been loaded at least once.
         match self.credential.lock().expect("lock poisoned").clone() {
-            Some(cred) if cred.is_valid() => return Ok(Some(cred)),
+            Some(cred) if cred.is_valid() => {
+                self.metrics.credential_cache_hit("aws");
+                return Ok(Some(cred));
+            }
             _ => (),
         }
 
-        let cred = self.load_inner().await?;
+        self.refresh().await
+    }
+
+    /// Describe the status of all credential sources for diagnostics.
+    ///
+    /// Sources are probed cheaply without loading credentials: only env,
+    /// files and the reachability of ec2 metadata service are checked. Note
+    /// that `env` and `profile` only take effect if the config is built by
+    /// [`Config::from_env`] and [`Config::from_profile`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn describe_available(&self) -> Vec<SourceStatus> {
+        self.describe_available_with(&EnvSnapshot::from_env()).await
+    }
+
+    /// Describe the status of all credential sources with the given env
+    /// snapshot.
+    ///
+    /// See [`Self::describe_available`] for details.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn describe_available_with(&self, env: &EnvSnapshot) -> Vec<SourceStatus> {
+        vec![
+            self.describe_env(env),
+            self.describe_profile(),
+            self.describe_config(),
+            self.describe_credential_process(),
+            self.describe_assume_role(),
+            self.describe_web_identity(),
+            self.describe_ec2_metadata().await,
+        ]
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn describe_env(&self, env: &EnvSnapshot) -> SourceStatus {
+        let missing: Vec<_> = [AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY]
+            .into_iter()
+            .filter(|k| env.env_var(k).is_none())
+            .collect();
+
+        if missing.is_empty() {
+            SourceStatus::new(
+                "env",
+                true,
+                format!("{AWS_ACCESS_KEY_ID} and {AWS_SECRET_ACCESS_KEY} are set"),
+            )
+        } else {
+            SourceStatus::new("env", false, format!("{} not set", missing.join(", ")))
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn describe_profile(&self) -> SourceStatus {
+        let profile = &self.config.profile;
+        let config_section = match profile.as_str() {
+            "default" => "default".to_string(),
+            x => format!("profile {x}"),
+        };
+
+        let mut details = Vec::with_capacity(2);
+        let mut available = false;
+        for (path, section) in [
+            (&self.config.shared_credentials_file, profile.as_str()),
+            (&self.config.config_file, config_section.as_str()),
+        ] {
+            let Some(expanded) = expand_homedir(path) else {
+                details.push(format!("{path} can't be expanded"));
+                continue;
+            };
+            if fs::metadata(&expanded).is_err() {
+                details.push(format!("{expanded} does not exist"));
+                continue;
+            }
+            match ini::Ini::load_from_file(&expanded) {
+                Ok(conf) if conf.section(Some(section)).is_some() => {
+                    available = true;
+                    details.push(format!("profile {profile} found in {expanded}"));
+                }
+                Ok(_) => details.push(format!("profile {profile} not found in {expanded}")),
+                Err(err) => details.push(format!("{expanded} can't be parsed: {err}")),
+            }
+        }
+
+        SourceStatus::new("profile", available, details.join("; "))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn describe_config(&self) -> SourceStatus {
+        match (&self.config.access_key_id, &self.config.secret_access_key) {
+            (Some(_), Some(_)) => SourceStatus::new(
+                "config",
+                true,
+                if self.config.session_token.is_some() {
+                    "access key and session token are configured"
+                } else {
+                    "access key is configured"
+                },
+            ),
+            _ => SourceStatus::new("config", false, "access key is not configured"),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn describe_credential_process(&self) -> SourceStatus {
+        match &self.config.credential_process {
+            Some(_) => SourceStatus::new(
+                "credential_process",
+                true,
+                "credential_process is configured",
+            ),
+            None => SourceStatus::new("credential_process", false, "credential_process is not set"),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn describe_assume_role(&self) -> SourceStatus {
+        match &self.config.role_arn {
+            None => SourceStatus::new("assume_role", false, "role_arn is not set"),
+            Some(_) if self.config.web_identity_token_file.is_some() => SourceStatus::new(
+                "assume_role",
+                false,
+                "role_arn will be assumed with web identity token",
+            ),
+            Some(role_arn) => SourceStatus::new(
+                "assume_role",
+                true,
+                format!("{role_arn} will be assumed with credential from config"),
+            ),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn describe_web_identity(&self) -> SourceStatus {
+        match (&self.config.web_identity_token_file, &self.config.role_arn) {
+            (None, _) => {
+                SourceStatus::new("web_identity", false, "web_identity_token_file is not set")
+            }
+            (Some(_), None) => SourceStatus::new("web_identity", false, "role_arn is not set"),
+            (Some(path), Some(role_arn)) if fs::metadata(path).is_ok() => SourceStatus::new(
+                "web_identity",
+                true,
+                format!("{role_arn} will be assumed with token file {path}"),
+            ),
+            (Some(path), Some(_)) => SourceStatus::new(
+                "web_identity",
+                false,
+                format!("token file {path} does not exist"),
+            ),
+        }
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), feature = "no_network"))]
+    async fn describe_ec2_metadata(&self) -> SourceStatus {
+        SourceStatus::new(
+            "ec2_metadata",
+            false,
+            "ec2 metadata is compiled out by no_network feature",
+        )
+    }
+
+    #[cfg(all(not(target_arch = "wasm32"), not(feature = "no_network")))]
+    async fn describe_ec2_metadata(&self) -> SourceStatus {
+        let Some(loader) = &self.imds_v2_loader else {
+            return SourceStatus::new("ec2_metadata", false, "ec2 metadata is disabled");
+        };
+
+        match loader.load_ec2_metadata_token().await {
+            Ok(_) => SourceStatus::new(
+                "ec2_metadata",
+                true,
+                format!("{} is reachable", loader.endpoint),
+            ),
+            Err(err) => SourceStatus::new(
+                "ec2_metadata",
+                false,
+                format!("{} is unreachable: {err}", loader.endpoint),
+            ),
+        }
+    }
+
+    /// Refresh credential in background `ahead` before it expires.
+    ///
+    /// The background task will be cancelled after the returned guard is
+    /// dropped. Failures will be retried with backoff, and [`Self::load`]
+    /// still works as usual if the background refresh is failing.
+    ///
+    /// Must be called inside a tokio runtime.
+    #[cfg(all(any(test, feature = "refresh-ahead"), not(target_arch = "wasm32")))]
+    pub fn refresh_ahead(self: &Arc<Self>, ahead: Duration) -> crate::RefreshGuard {
+        let loader = self.clone();
+        crate::refresh::spawn(ahead, move || {
+            let loader = loader.clone();
+            async move { Ok(loader.refresh().await?.and_then(|cred| cred.expires_in)) }
+        })
+    }
+
+    /// Load credential from sources and update the cache.
+    async fn refresh(&self) -> Result<Option<Credential>> {
+        let cred = self.load_inner().await;
+        self.metrics
+            .credential_refreshed("aws", matches!(cred, Ok(Some(_))));
+        let mut cred = cred?;
+
+        if let (Some(cred), Some(max)) = (&mut cred, self.max_expires_in) {
+            let max = now() + chrono::TimeDelta::from_std(max)?;
+            cred.expires_in = Some(cred.expires_in.map_or(max, |v| v.min(max)));
+        }
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         lock.clone_from(&cred);
@@ -123,13 +712,53 @@ impl DefaultLoader {
     }
 
     async fn load_inner(&self) -> Result<Option<Credential>> {
+        if let Some(loader) = &self.customized_credential_loader {
+            if let Some(cred) =
+                loader
+                    .load_credential(self.client.clone())
+                    .await
+                    .map_err(|err| {
+                        debug!("load credential via customized loader failed: {err:?}");
+                        err
+                    })?
+            {
+                return Ok(Some(cred));
+            }
+        }
+
         if let Some(cred) = self.load_via_config().map_err(|err| {
             debug!("load credential via config failed: {err:?}");
             err
+        })? {
+            #[cfg(feature = "no_network")]
+            if self.config.role_arn.is_some() {
+                return Err(anyhow!(
+                    "role_arn is configured but assume role is not available with no_network feature"
+                ));
+            }
+
+            #[cfg(not(feature = "no_network"))]
+            let cred = self
+                .load_via_assume_role(&cred)
+                .await
+                .map_err(|err| {
+                    debug!("load credential via assume_role failed: {err:?}");
+                    err
+                })?
+                .unwrap_or(cred);
+
+            return Ok(Some(cred));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(cred) = self.load_via_credential_process().await.map_err(|err| {
+            debug!("load credential via credential_process failed: {err:?}");
+            err
         })? {
             return Ok(Some(cred));
         }
 
+        #[cfg(not(feature = "no_network"))]
         if let Some(cred) = self
             .load_via_assume_role_with_web_identity()
             .await
@@ -141,6 +770,15 @@ impl DefaultLoader {
             return Ok(Some(cred));
         }
 
+        #[cfg(not(feature = "no_network"))]
+        if let Some(cred) = self.container_loader.load().await.map_err(|err| {
+            debug!("load credential via container failed: {err:?}");
+            err
+        })? {
+            return Ok(Some(cred));
+        }
+
+        #[cfg(not(feature = "no_network"))]
         if let Some(cred) = self.load_via_imds_v2().await.map_err(|err| {
             debug!("load credential via imds_v2 failed: {err:?}");
             err
@@ -153,19 +791,90 @@ impl DefaultLoader {
 
     fn load_via_config(&self) -> Result<Option<Credential>> {
         if let (Some(ak), Some(sk)) = (&self.config.access_key_id, &self.config.secret_access_key) {
+            // Set expires_in to 10 minutes to enforce re-read
+            // from file.
+            let mut expires_in = now() + chrono::TimeDelta::try_minutes(10).expect("in bounds");
+            // Temporary credentials must not be used after they expired.
+            if let Some(v) = self.config.expires_in {
+                if v <= now() {
+                    return Err(anyhow!(
+                        "temporary credential in config has been expired at {}",
+                        format_rfc3339(v)
+                    ));
+                }
+                expires_in = expires_in.min(v);
+            }
+
             Ok(Some(Credential {
                 access_key_id: ak.clone(),
                 secret_access_key: sk.clone(),
                 session_token: self.config.session_token.clone(),
-                // Set expires_in to 10 minutes to enforce re-read
-                // from file.
-                expires_in: Some(now() + chrono::TimeDelta::try_minutes(10).expect("in bounds")),
+                expires_in: Some(expires_in),
+                ..Default::default()
             }))
         } else {
             Ok(None)
         }
     }
 
+    /// Load credential by executing the `credential_process` command.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn load_via_credential_process(&self) -> Result<Option<Credential>> {
+        let Some(command) = &self.config.credential_process else {
+            return Ok(None);
+        };
+
+        #[cfg(windows)]
+        let output = tokio::process::Command::new("cmd")
+            .args(["/C", command])
+            .output()
+            .await;
+        #[cfg(not(windows))]
+        let output = tokio::process::Command::new("sh")
+            .args(["-c", command])
+            .output()
+            .await;
+        let output = output.map_err(|err| anyhow!("execute credential_process failed: {err}"))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "credential_process exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        parse_process_credentials(&output.stdout).map(Some)
+    }
+
+    /// Assume the role specified by `role_arn` with the base credential.
+    ///
+    /// Roles used together with web identity token will be handled by
+    /// [`DefaultLoader::load_via_assume_role_with_web_identity`] instead.
+    #[cfg(not(feature = "no_network"))]
+    async fn load_via_assume_role(&self, source_cred: &Credential) -> Result<Option<Credential>> {
+        if self.config.role_arn.is_none() || self.config.web_identity_token_file.is_some() {
+            return Ok(None);
+        }
+
+        // Global sts endpoint uses `us-east-1` as signing region.
+        let region = self.config.region.as_deref().unwrap_or("us-east-1");
+        let signer = Signer::new("sts", region);
+
+        let cred = assume_role(
+            &self.client,
+            &self.config,
+            &self.sts_endpoint()?,
+            &self.user_agent,
+            &self.retry,
+            &signer,
+            source_cred,
+        )
+        .await?;
+
+        Ok(Some(cred))
+    }
+
+    #[cfg(not(feature = "no_network"))]
     async fn load_via_imds_v2(&self) -> Result<Option<Credential>> {
         let loader = match &self.imds_v2_loader {
             Some(loader) => loader,
@@ -175,6 +884,7 @@ impl DefaultLoader {
         loader.load().await
     }
 
+    #[cfg(not(feature = "no_network"))]
     async fn load_via_assume_role_with_web_identity(&self) -> Result<Option<Credential>> {
         let (token_file, role_arn) =
             match (&self.config.web_identity_token_file, &self.config.role_arn) {
@@ -188,13 +898,17 @@ impl DefaultLoader {
         let endpoint = self.sts_endpoint()?;
 
         // Construct request to AWS STS Service.
-        let url = format!("https://{endpoint}/?Action=AssumeRoleWithWebIdentity&RoleArn={role_arn}&WebIdentityToken={token}&Version=2011-06-15&RoleSessionName={role_session_name}");
-        let req = self.client.get(&url).header(
-            http::header::CONTENT_TYPE.as_str(),
-            "application/x-www-form-urlencoded",
-        );
+        let url = format!("{endpoint}/?Action=AssumeRoleWithWebIdentity&RoleArn={role_arn}&WebIdentityToken={token}&Version=2011-06-15&RoleSessionName={role_session_name}");
+        let req = self
+            .client
+            .get(&url)
+            .header(
+                http::header::CONTENT_TYPE.as_str(),
+                "application/x-www-form-urlencoded",
+            )
+            .header(USER_AGENT, &self.user_agent);
 
-        let resp = req.send().await?;
+        let resp = self.retry.send(req).await?;
         if resp.status() != http::StatusCode::OK {
             let content = resp.text().await?;
             return Err(anyhow!("request to AWS STS Services failed: {content}"));
@@ -208,14 +922,16 @@ impl DefaultLoader {
             secret_access_key: resp_cred.secret_access_key,
             session_token: Some(resp_cred.session_token),
             expires_in: Some(parse_rfc3339(&resp_cred.expiration)?),
-        };
+            ..Default::default()
+        }
+        .with_arn(resp.result.assumed_role_user.map(|v| v.arn));
 
         Ok(Some(cred))
     }
 
     /// Get the sts endpoint.
     ///
-    /// The returning format may look like `sts.{region}.amazonaws.com`
+    /// The returning format may look like `https://sts.{region}.amazonaws.com`
     ///
     /// # Notes
     ///
@@ -223,24 +939,30 @@ impl DefaultLoader {
     /// We can check them by region name.
     ///
     /// ref: https://github.com/awslabs/aws-sdk-rust/blob/31cfae2cf23be0c68a47357070dea1aee9227e3a/sdk/sts/src/aws_endpoint.rs
+    #[cfg(not(feature = "no_network"))]
     fn sts_endpoint(&self) -> Result<String> {
+        // use user specified sts endpoint if set.
+        if let Some(endpoint) = &self.config.sts_endpoint {
+            return Ok(endpoint.trim_end_matches('/').to_string());
+        }
+
         // use regional sts if sts_regional_endpoints has been set.
         if self.config.sts_regional_endpoints == "regional" {
             let region = self.config.region.clone().ok_or_else(|| {
                 anyhow!("sts_regional_endpoints set to reginal, but region is not set")
             })?;
             if region.starts_with("cn-") {
-                Ok(format!("sts.{region}.amazonaws.com.cn"))
+                Ok(format!("https://sts.{region}.amazonaws.com.cn"))
             } else {
-                Ok(format!("sts.{region}.amazonaws.com"))
+                Ok(format!("https://sts.{region}.amazonaws.com"))
             }
         } else {
             let region = self.config.region.clone().unwrap_or_default();
             if region.starts_with("cn") {
                 // TODO: seems aws china doesn't support global sts?
-                Ok("sts.amazonaws.com.cn".to_string())
+                Ok("https://sts.amazonaws.com.cn".to_string())
             } else {
-                Ok("sts.amazonaws.com".to_string())
+                Ok("https://sts.amazonaws.com".to_string())
             }
         }
     }
@@ -254,31 +976,69 @@ impl CredentialLoad for DefaultLoader {
     }
 }
 
+#[cfg(not(feature = "no_network"))]
 pub struct IMDSv2Loader {
     client: Client,
+    user_agent: String,
+    endpoint: String,
+    token_ttl: Duration,
+    retry: RetryPolicy,
 
     token: Arc<Mutex<(String, DateTime)>>,
 }
 
+#[cfg(not(feature = "no_network"))]
 impl IMDSv2Loader {
     /// Create a new IMDSv2Loader.
     pub fn new(client: Client) -> Self {
         Self {
             client,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            endpoint: "http://169.254.169.254".to_string(),
+            // 21600s (6h) is recommended by AWS.
+            token_ttl: Duration::from_secs(21600),
+            retry: RetryPolicy::default(),
             token: Arc::new(Mutex::new(("".to_string(), DateTime::MIN_UTC))),
         }
     }
 
+    /// Specify the `User-Agent` of requests sent to ec2 metadata services.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Specify the TTL of the ec2 metadata session token.
+    ///
+    /// AWS allows TTL between 1 second and 6 hours, default to 6 hours. TTL
+    /// is truncated to whole seconds and clamped into the allowed range.
+    pub fn with_token_ttl(mut self, ttl: Duration) -> Self {
+        self.token_ttl = Duration::from_secs(ttl.as_secs().clamp(1, 21600));
+        self
+    }
+
+    /// Specify the endpoint of ec2 metadata service.
+    ///
+    /// Default to `http://169.254.169.254`.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
     pub async fn load(&self) -> Result<Option<Credential>> {
         let token = self.load_ec2_metadata_token().await?;
 
         // List all credentials that node has.
-        let url = "http://169.254.169.254/latest/meta-data/iam/security-credentials/";
+        let url = format!(
+            "{}/latest/meta-data/iam/security-credentials/",
+            self.endpoint
+        );
         let req = self
             .client
-            .get(url)
+            .get(&url)
+            .header(USER_AGENT, &self.user_agent)
             .header("x-aws-ec2-metadata-token", &token);
-        let resp = req.send().await?;
+        let resp = self.retry.send(req).await?;
         if resp.status() != http::StatusCode::OK {
             let content = resp.text().await?;
             return Err(anyhow!(
@@ -289,13 +1049,15 @@ impl IMDSv2Loader {
 
         // Get the credentials via role_name.
         let url = format!(
-            "http://169.254.169.254/latest/meta-data/iam/security-credentials/{profile_name}"
+            "{}/latest/meta-data/iam/security-credentials/{profile_name}",
+            self.endpoint
         );
         let req = self
             .client
             .get(&url)
+            .header(USER_AGENT, &self.user_agent)
             .header("x-aws-ec2-metadata-token", &token);
-        let resp = req.send().await?;
+        let resp = self.retry.send(req).await?;
         if resp.status() != http::StatusCode::OK {
             let content = resp.text().await?;
             return Err(anyhow!(
@@ -316,6 +1078,7 @@ impl IMDSv2Loader {
             secret_access_key: resp.secret_access_key,
             session_token: Some(resp.token),
             expires_in: Some(parse_rfc3339(&resp.expiration)?),
+            ..Default::default()
         };
 
         Ok(Some(cred))
@@ -332,14 +1095,17 @@ impl IMDSv2Loader {
             }
         }
 
-        let url = "http://169.254.169.254/latest/api/token";
+        let url = format!("{}/latest/api/token", self.endpoint);
         #[allow(unused_mut)]
         let mut req = self
             .client
-            .put(url)
+            .put(&url)
+            .header(USER_AGENT, &self.user_agent)
             .header(CONTENT_LENGTH, "0")
-            // 21600s (6h) is recommended by AWS.
-            .header("x-aws-ec2-metadata-token-ttl-seconds", "21600");
+            .header(
+                "x-aws-ec2-metadata-token-ttl-seconds",
+                self.token_ttl.as_secs().to_string(),
+            );
 
         // Set timeout to 1s to avoid hanging on non-s3 env.
         #[cfg(not(target_arch = "wasm32"))]
@@ -347,7 +1113,7 @@ impl IMDSv2Loader {
             req = req.timeout(std::time::Duration::from_secs(1));
         }
 
-        let resp = req.send().await?;
+        let resp = self.retry.send(req).await?;
         if resp.status() != http::StatusCode::OK {
             let content = resp.text().await?;
             return Err(anyhow!(
@@ -355,9 +1121,11 @@ impl IMDSv2Loader {
             ));
         }
         let ec2_token = resp.text().await?;
-        // Set expires_in to 10 minutes to enforce re-read.
-        let expires_in = now() + chrono::TimeDelta::try_seconds(21600).expect("in bounds")
-            - chrono::TimeDelta::try_seconds(600).expect("in bounds");
+        // Refresh the token 10 minutes (or half of the TTL if it's too short) before
+        // it's expired.
+        let ttl = chrono::TimeDelta::from_std(self.token_ttl)?;
+        let buffer = (ttl / 2).min(chrono::TimeDelta::try_seconds(600).expect("in bounds"));
+        let expires_in = now() + ttl - buffer;
 
         {
             *self.token.lock().expect("lock poisoned") = (ec2_token.clone(), expires_in);
@@ -367,6 +1135,7 @@ impl IMDSv2Loader {
     }
 }
 
+#[cfg(not(feature = "no_network"))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl CredentialLoad for IMDSv2Loader {
@@ -375,17 +1144,365 @@ impl CredentialLoad for IMDSv2Loader {
     }
 }
 
+/// ContainerLoader will load credential from ECS container metadata (IAM
+/// Roles for Tasks) or general HTTP endpoints like EKS Pod Identity.
+///
+/// The host of `full_uri` must be a loopback address, `169.254.170.2` (ECS),
+/// `169.254.170.23` or `fd00:ec2::23` (EKS), unless it's explicitly allowed
+/// to prevent the authorization token from being leaked by a poisoned env.
+#[cfg(not(feature = "no_network"))]
+pub struct ContainerLoader {
+    client: Client,
+    user_agent: String,
+    endpoint: String,
+    relative_uri: Option<String>,
+    full_uri: Option<String>,
+    authorization_token: Option<String>,
+    authorization_token_file: Option<String>,
+    allowed_hosts: Vec<String>,
+    allow_all_hosts: bool,
+    retry: RetryPolicy,
+}
+
+#[cfg(not(feature = "no_network"))]
+impl ContainerLoader {
+    /// Create a new ContainerLoader.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            endpoint: "http://169.254.170.2".to_string(),
+            relative_uri: None,
+            full_uri: None,
+            authorization_token: None,
+            authorization_token_file: None,
+            allowed_hosts: Vec::new(),
+            allow_all_hosts: false,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    fn from_config(client: Client, config: &Config) -> Self {
+        Self {
+            relative_uri: config.container_credentials_relative_uri.clone(),
+            full_uri: config.container_credentials_full_uri.clone(),
+            authorization_token: config.container_authorization_token.clone(),
+            authorization_token_file: config.container_authorization_token_file.clone(),
+            ..Self::new(client)
+        }
+    }
+
+    /// Specify the `User-Agent` of requests sent to container endpoint.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
+
+    /// Specify the predicate to decide whether a response or a transport
+    /// error should be retried.
+    ///
+    /// By default, every request is sent only once. With a predicate set,
+    /// requests are attempted at most 3 times with exponential backoff while
+    /// the predicate returns `true`.
+    pub fn with_retry_policy(
+        mut self,
+        predicate: impl Fn(Result<&reqwest::Response, &reqwest::Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry = RetryPolicy::new(predicate);
+        self
+    }
+
+    /// Specify the uri relative to `http://169.254.170.2`, takes precedence
+    /// over [`Self::with_full_uri`].
+    pub fn with_relative_uri(mut self, uri: &str) -> Self {
+        self.relative_uri = Some(uri.to_string());
+        self
+    }
+
+    /// Specify the full uri of credential endpoint.
+    pub fn with_full_uri(mut self, uri: &str) -> Self {
+        self.full_uri = Some(uri.to_string());
+        self
+    }
+
+    /// Specify the token sent in `Authorization` header.
+    pub fn with_authorization_token(mut self, token: &str) -> Self {
+        self.authorization_token = Some(token.to_string());
+        self
+    }
+
+    /// Specify the file to read token sent in `Authorization` header, takes
+    /// precedence over [`Self::with_authorization_token`].
+    ///
+    /// The file will be read for every request since it could be rotated.
+    pub fn with_authorization_token_file(mut self, path: &str) -> Self {
+        self.authorization_token_file = Some(path.to_string());
+        self
+    }
+
+    /// Allow `host` to be used in full uri besides the default ones.
+    pub fn with_allowed_host(mut self, host: &str) -> Self {
+        self.allowed_hosts.push(host.to_string());
+        self
+    }
+
+    /// Allow any host to be used in full uri.
+    ///
+    /// This is insecure: anyone who controls the env can redirect the
+    /// authorization token to an arbitrary host.
+    pub fn with_allow_all_hosts(mut self) -> Self {
+        self.allow_all_hosts = true;
+        self
+    }
+
+    /// Load credential from container endpoint.
+    ///
+    /// Returns `None` if neither relative uri nor full uri is configured.
+    pub async fn load(&self) -> Result<Option<Credential>> {
+        let url = match (&self.relative_uri, &self.full_uri) {
+            (Some(uri), _) => format!("{}{uri}", self.endpoint),
+            (None, Some(uri)) => {
+                self.check_full_uri(uri)?;
+                uri.to_string()
+            }
+            (None, None) => return Ok(None),
+        };
+
+        let mut req = self.client.get(&url).header(USER_AGENT, &self.user_agent);
+        if let Some(token) = self.load_authorization_token()? {
+            req = req.header(AUTHORIZATION, token);
+        }
+        let resp = self.retry.send(req).await?;
+        if resp.status() != http::StatusCode::OK {
+            let content = resp.text().await?;
+            return Err(anyhow!(
+                "request to AWS container credentials endpoint failed: {content}"
+            ));
+        }
+
+        let resp: ContainerCredentials = serde_json::from_str(&resp.text().await?)?;
+        Ok(Some(resp.into_credential()?))
+    }
+
+    fn load_authorization_token(&self) -> Result<Option<String>> {
+        load_authorization_token(
+            self.authorization_token_file.as_deref(),
+            self.authorization_token.as_deref(),
+        )
+    }
+
+    fn check_full_uri(&self, uri: &str) -> Result<()> {
+        if self.allow_all_hosts {
+            return Ok(());
+        }
+
+        let parsed: http::Uri = uri
+            .parse()
+            .map_err(|err| anyhow!("container credentials full uri {uri} is invalid: {err}"))?;
+        let host = parsed
+            .host()
+            .ok_or_else(|| anyhow!("container credentials full uri {uri} has no host"))?;
+
+        if is_allowed_container_host(host)
+            || self
+                .allowed_hosts
+                .iter()
+                .any(|v| v.eq_ignore_ascii_case(host))
+        {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "host {host} of container credentials full uri is not allowed, only loopback, ECS and EKS container hosts are allowed by default"
+        ))
+    }
+}
+
+/// Load the authorization token sent to container endpoints, `file` takes
+/// precedence over `token`.
+#[cfg(not(feature = "no_network"))]
+fn load_authorization_token(file: Option<&str>, token: Option<&str>) -> Result<Option<String>> {
+    if let Some(path) = file {
+        let token = fs::read_to_string(path).map_err(|err| {
+            anyhow!("read container authorization token file {path} failed: {err}")
+        })?;
+        return Ok(Some(token.trim().to_string()));
+    }
+
+    Ok(token.map(|v| v.to_string()))
+}
+
+/// Check if host is a loopback, ECS or EKS container host.
+#[cfg(not(feature = "no_network"))]
+fn is_allowed_container_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+
+    match host.parse::<std::net::IpAddr>() {
+        Ok(ip) => {
+            ip.is_loopback()
+                || matches!(
+                    ip.to_string().as_str(),
+                    "169.254.170.2" | "169.254.170.23" | "fd00:ec2::23"
+                )
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(feature = "no_network"))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl CredentialLoad for ContainerLoader {
+    async fn load_credential(&self, _: Client) -> Result<Option<Credential>> {
+        self.load().await
+    }
+}
+
+/// UnixSocketLoader will load credential from a broker listening on a Unix
+/// domain socket.
+///
+/// The broker speaks the same JSON protocol as the container credentials
+/// endpoint (see [`ContainerLoader`]), but no link-local HTTP endpoint is
+/// exposed.
+#[cfg(all(unix, not(feature = "no_network")))]
+pub struct UnixSocketLoader {
+    client: Client,
+    user_agent: String,
+    uri: String,
+    authorization_token: Option<String>,
+    authorization_token_file: Option<String>,
+    retry: RetryPolicy,
+}
+
+#[cfg(all(unix, not(feature = "no_network")))]
+impl UnixSocketLoader {
+    /// Create a new UnixSocketLoader which connects to the socket at `path`.
+    pub fn new(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let client = Client::builder()
+            .unix_socket(path.as_ref())
+            .build()
+            .map_err(|err| anyhow!("build unix socket client failed: {err}"))?;
+
+        Ok(Self {
+            client,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            uri: "/".to_string(),
+            authorization_token: None,
+            authorization_token_file: None,
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    /// Specify the `User-Agent` of requests sent to the broker.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
+
+    /// Specify the request path of credential endpoint, default to `/`.
+    pub fn with_uri(mut self, uri: &str) -> Self {
+        self.uri = uri.to_string();
+        self
+    }
+
+    /// Specify the token sent in `Authorization` header.
+    pub fn with_authorization_token(mut self, token: &str) -> Self {
+        self.authorization_token = Some(token.to_string());
+        self
+    }
+
+    /// Specify the file to read token sent in `Authorization` header, takes
+    /// precedence over [`Self::with_authorization_token`].
+    ///
+    /// The file will be read for every request since it could be rotated.
+    pub fn with_authorization_token_file(mut self, path: &str) -> Self {
+        self.authorization_token_file = Some(path.to_string());
+        self
+    }
+
+    /// Specify the predicate to decide whether a response or a transport
+    /// error should be retried.
+    ///
+    /// See [`ContainerLoader::with_retry_policy`] for details.
+    pub fn with_retry_policy(
+        mut self,
+        predicate: impl Fn(Result<&reqwest::Response, &reqwest::Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry = RetryPolicy::new(predicate);
+        self
+    }
+
+    /// Load credential from the broker.
+    pub async fn load(&self) -> Result<Option<Credential>> {
+        // The host is not used for routing, but required by HTTP.
+        let uri = self.uri.trim_start_matches('/');
+        let mut req = self
+            .client
+            .get(format!("http://localhost/{uri}"))
+            .header(USER_AGENT, &self.user_agent);
+        if let Some(token) = load_authorization_token(
+            self.authorization_token_file.as_deref(),
+            self.authorization_token.as_deref(),
+        )? {
+            req = req.header(AUTHORIZATION, token);
+        }
+        let resp = self.retry.send(req).await?;
+        if resp.status() != http::StatusCode::OK {
+            let content = resp.text().await?;
+            return Err(anyhow!(
+                "request to unix socket credentials broker failed: {content}"
+            ));
+        }
+
+        let resp: ContainerCredentials = serde_json::from_str(&resp.text().await?)?;
+        Ok(Some(resp.into_credential()?))
+    }
+}
+
+#[cfg(all(unix, not(feature = "no_network")))]
+#[async_trait]
+impl CredentialLoad for UnixSocketLoader {
+    async fn load_credential(&self, _: Client) -> Result<Option<Credential>> {
+        self.load().await
+    }
+}
+
+#[cfg(not(feature = "no_network"))]
 /// AssumeRoleLoader will load credential via assume role.
 pub struct AssumeRoleLoader {
     client: Client,
     config: Config,
+    user_agent: String,
+    retry: RetryPolicy,
 
     source_credential: Box<dyn CredentialLoad>,
     sts_signer: Signer,
 }
 
+#[cfg(not(feature = "no_network"))]
 impl AssumeRoleLoader {
     /// Create a new assume role loader.
+    ///
+    /// If `source_credential` is a [`DefaultLoader`] whose config has
+    /// `role_arn` set (like from `AWS_ROLE_ARN`), it will assume that role
+    /// first and this loader will chain from the assumed credential. Clear
+    /// `role_arn` of the source config to assume from the base credential
+    /// directly.
     pub fn new(
         client: Client,
         config: Config,
@@ -398,38 +1515,73 @@ impl AssumeRoleLoader {
         Ok(Self {
             client,
             config,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            retry: RetryPolicy::default(),
             source_credential,
 
             sts_signer: Signer::new("sts", &region),
         })
     }
 
-    /// Load credential via assume role.
-    pub async fn load(&self) -> Result<Option<Credential>> {
-        let role_arn =self.config.role_arn.clone().ok_or_else(|| {
-            anyhow!("assume role loader requires role_arn, but not found, please check your configuration")
-        })?;
+    /// Specify the `User-Agent` of requests sent to STS.
+    ///
+    /// Default to `reqsign/{version}`.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
 
-        let role_session_name = &self.config.role_session_name;
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
 
-        let endpoint = self.sts_endpoint()?;
+    /// Specify the predicate to decide whether a response or a transport
+    /// error should be retried.
+    ///
+    /// By default, every request is sent only once. With a predicate set,
+    /// requests are attempted at most 3 times with exponential backoff while
+    /// the predicate returns `true`.
+    pub fn with_retry_policy(
+        mut self,
+        predicate: impl Fn(Result<&reqwest::Response, &reqwest::Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry = RetryPolicy::new(predicate);
+        self
+    }
 
-        // Construct request to AWS STS Service.
-        let mut url = format!("https://{endpoint}/?Action=AssumeRole&RoleArn={role_arn}&Version=2011-06-15&RoleSessionName={role_session_name}");
-        if let Some(external_id) = &self.config.external_id {
-            write!(url, "&ExternalId={external_id}")?;
-        }
-        let mut req = self
-            .client
-            .get(&url)
-            .header(
-                http::header::CONTENT_TYPE.as_str(),
-                "application/x-www-form-urlencoded",
-            )
-            // Set content sha to empty string.
-            .header(X_AMZ_CONTENT_SHA_256, EMPTY_STRING_SHA256)
-            .build()?;
+    /// Specify the external id used in `AssumeRole`.
+    ///
+    /// Cross-account role trust policies usually require an external id.
+    pub fn with_external_id(mut self, external_id: &str) -> Self {
+        self.config.external_id = Some(external_id.to_string());
+        self
+    }
+
+    /// Specify the duration of the assumed role session.
+    ///
+    /// Must be between 900 (15 minutes) and 43200 (12 hours), STS will
+    /// also reject durations larger than the max session duration of the role.
+    pub fn with_duration_seconds(mut self, duration_seconds: u32) -> Self {
+        self.config.duration_seconds = Some(duration_seconds);
+        self
+    }
+
+    /// Specify the inline session policy to scope down the permissions.
+    pub fn with_policy(mut self, policy: &str) -> Self {
+        self.config.policy = Some(policy.to_string());
+        self
+    }
+
+    /// Specify the managed policy ARNs to scope down the permissions.
+    pub fn with_policy_arns(mut self, policy_arns: &[&str]) -> Self {
+        self.config.policy_arns = policy_arns.iter().map(|v| v.to_string()).collect();
+        self
+    }
 
+    /// Load credential via assume role.
+    pub async fn load(&self) -> Result<Option<Credential>> {
         let source_cred = self
             .source_credential
             .load_credential(self.client.clone())
@@ -438,30 +1590,23 @@ impl AssumeRoleLoader {
                 anyhow!("source credential is required for AssumeRole, but not found, please check your configuration")
             })?;
 
-        self.sts_signer.sign(&mut req, &source_cred)?;
-
-        let resp = self.client.execute(req).await?;
-        if resp.status() != http::StatusCode::OK {
-            let content = resp.text().await?;
-            return Err(anyhow!("request to AWS STS Services failed: {content}"));
-        }
-
-        let resp: AssumeRoleResponse = de::from_str(&resp.text().await?)?;
-        let resp_cred = resp.result.credentials;
-
-        let cred = Credential {
-            access_key_id: resp_cred.access_key_id,
-            secret_access_key: resp_cred.secret_access_key,
-            session_token: Some(resp_cred.session_token),
-            expires_in: Some(parse_rfc3339(&resp_cred.expiration)?),
-        };
+        let cred = assume_role(
+            &self.client,
+            &self.config,
+            &self.sts_endpoint()?,
+            &self.user_agent,
+            &self.retry,
+            &self.sts_signer,
+            &source_cred,
+        )
+        .await?;
 
         Ok(Some(cred))
     }
 
     /// Get the sts endpoint.
     ///
-    /// The returning format may look like `sts.{region}.amazonaws.com`
+    /// The returning format may look like `https://sts.{region}.amazonaws.com`
     ///
     /// # Notes
     ///
@@ -470,28 +1615,34 @@ impl AssumeRoleLoader {
     ///
     /// ref: https://github.com/awslabs/aws-sdk-rust/blob/31cfae2cf23be0c68a47357070dea1aee9227e3a/sdk/sts/src/aws_endpoint.rs
     fn sts_endpoint(&self) -> Result<String> {
+        // use user specified sts endpoint if set.
+        if let Some(endpoint) = &self.config.sts_endpoint {
+            return Ok(endpoint.trim_end_matches('/').to_string());
+        }
+
         // use regional sts if sts_regional_endpoints has been set.
         if self.config.sts_regional_endpoints == "regional" {
             let region = self.config.region.clone().ok_or_else(|| {
                 anyhow!("sts_regional_endpoints set to reginal, but region is not set")
             })?;
             if region.starts_with("cn-") {
-                Ok(format!("sts.{region}.amazonaws.com.cn"))
+                Ok(format!("https://sts.{region}.amazonaws.com.cn"))
             } else {
-                Ok(format!("sts.{region}.amazonaws.com"))
+                Ok(format!("https://sts.{region}.amazonaws.com"))
             }
         } else {
             let region = self.config.region.clone().unwrap_or_default();
             if region.starts_with("cn") {
                 // TODO: seems aws china doesn't support global sts?
-                Ok("sts.amazonaws.com.cn".to_string())
+                Ok("https://sts.amazonaws.com.cn".to_string())
             } else {
-                Ok("sts.amazonaws.com".to_string())
+                Ok("https://sts.amazonaws.com".to_string())
             }
         }
     }
 }
 
+#[cfg(not(feature = "no_network"))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl CredentialLoad for AssumeRoleLoader {
@@ -500,6 +1651,150 @@ impl CredentialLoad for AssumeRoleLoader {
     }
 }
 
+#[cfg(not(feature = "no_network"))]
+/// Assume role via sts with given source credential.
+async fn assume_role(
+    client: &Client,
+    config: &Config,
+    endpoint: &str,
+    user_agent: &str,
+    retry: &RetryPolicy,
+    signer: &Signer,
+    source_cred: &Credential,
+) -> Result<Credential> {
+    let role_arn = config.role_arn.clone().ok_or_else(|| {
+        anyhow!(
+            "assume role loader requires role_arn, but not found, please check your configuration"
+        )
+    })?;
+
+    let role_session_name = &config.role_session_name;
+
+    // Construct request to AWS STS Service.
+    let mut url = format!("{endpoint}/?Action=AssumeRole&RoleArn={role_arn}&Version=2011-06-15&RoleSessionName={role_session_name}");
+    if let Some(external_id) = &config.external_id {
+        write!(
+            url,
+            "&ExternalId={}",
+            utf8_percent_encode(external_id, &AWS_QUERY_ENCODE_SET)
+        )?;
+    }
+    if let Some(duration_seconds) = config.duration_seconds {
+        if !(STS_MIN_DURATION_SECONDS..=STS_MAX_DURATION_SECONDS).contains(&duration_seconds) {
+            return Err(anyhow!(
+                "duration_seconds {duration_seconds} is out of range, must be between {STS_MIN_DURATION_SECONDS} and {STS_MAX_DURATION_SECONDS}"
+            ));
+        }
+        write!(url, "&DurationSeconds={duration_seconds}")?;
+    }
+    if let Some(policy) = &config.policy {
+        write!(
+            url,
+            "&Policy={}",
+            utf8_percent_encode(policy, &AWS_QUERY_ENCODE_SET)
+        )?;
+    }
+    for (idx, arn) in config.policy_arns.iter().enumerate() {
+        write!(
+            url,
+            "&PolicyArns.member.{}.arn={}",
+            idx + 1,
+            utf8_percent_encode(arn, &AWS_QUERY_ENCODE_SET)
+        )?;
+    }
+    let mut req = client
+        .get(&url)
+        .header(
+            http::header::CONTENT_TYPE.as_str(),
+            "application/x-www-form-urlencoded",
+        )
+        // Set content sha to empty string.
+        .header(X_AMZ_CONTENT_SHA_256, EMPTY_STRING_SHA256)
+        .header(USER_AGENT, user_agent)
+        .build()?;
+
+    signer.sign(&mut req, source_cred)?;
+
+    let resp = retry.execute(client, req).await?;
+    if resp.status() != http::StatusCode::OK {
+        let content = resp.text().await?;
+        return Err(anyhow!("request to AWS STS Services failed: {content}"));
+    }
+
+    let resp: AssumeRoleResponse = de::from_str(&resp.text().await?)?;
+    let resp_cred = resp.result.credentials;
+
+    Ok(Credential {
+        access_key_id: resp_cred.access_key_id,
+        secret_access_key: resp_cred.secret_access_key,
+        session_token: Some(resp_cred.session_token),
+        expires_in: Some(parse_rfc3339(&resp_cred.expiration)?),
+        ..Default::default()
+    }
+    .with_arn(resp.result.assumed_role_user.map(|v| v.arn)))
+}
+
+/// Output of `credential_process`.
+///
+/// Reference: [Sourcing credentials with an external process](https://docs.aws.amazon.com/sdkref/latest/guide/feature-process-credentials.html)
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct ProcessCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    expiration: Option<String>,
+    account_id: Option<String>,
+}
+
+/// Parse the output of `credential_process`, only `Version: 1` is supported.
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_process_credentials(content: &[u8]) -> Result<Credential> {
+    let value: serde_json::Value = serde_json::from_slice(content)
+        .map_err(|err| anyhow!("parse credential_process output failed: {err}"))?;
+    match value.get("Version") {
+        None => return Err(anyhow!("credential_process output is missing Version")),
+        Some(v) if v.as_u64() == Some(1) => {}
+        Some(v) => {
+            return Err(anyhow!(
+                "credential_process output Version {v} is not supported"
+            ))
+        }
+    }
+
+    let resp: ProcessCredentials = serde_json::from_value(value)?;
+    if resp.access_key_id.is_empty() || resp.secret_access_key.is_empty() {
+        return Err(anyhow!(
+            "credential_process output is missing AccessKeyId or SecretAccessKey"
+        ));
+    }
+
+    // Credentials without expiration are static and never expire.
+    let expires_in = match resp.expiration.as_deref() {
+        None => None,
+        Some(v) => {
+            let expires_in = parse_rfc3339(v)?;
+            if expires_in <= now() {
+                return Err(anyhow!(
+                    "credential from credential_process has been expired at {v}"
+                ));
+            }
+            Some(expires_in)
+        }
+    };
+
+    Ok(Credential {
+        access_key_id: resp.access_key_id,
+        secret_access_key: resp.secret_access_key,
+        session_token: resp.session_token,
+        expires_in,
+        arn: None,
+        account_id: resp.account_id,
+    })
+}
+
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityResponse {
@@ -507,12 +1802,15 @@ struct AssumeRoleWithWebIdentityResponse {
     result: AssumeRoleWithWebIdentityResult,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityResult {
+    assumed_role_user: Option<AssumedRoleUser>,
     credentials: AssumeRoleWithWebIdentityCredentials,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityCredentials {
@@ -522,6 +1820,7 @@ struct AssumeRoleWithWebIdentityCredentials {
     expiration: String,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleResponse {
@@ -529,12 +1828,22 @@ struct AssumeRoleResponse {
     result: AssumeRoleResult,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleResult {
+    assumed_role_user: Option<AssumedRoleUser>,
     credentials: AssumeRoleCredentials,
 }
 
+#[cfg(not(feature = "no_network"))]
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct AssumedRoleUser {
+    arn: String,
+}
+
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleCredentials {
@@ -544,6 +1853,32 @@ struct AssumeRoleCredentials {
     expiration: String,
 }
 
+#[cfg(not(feature = "no_network"))]
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+struct ContainerCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: Option<String>,
+    expiration: Option<String>,
+    account_id: Option<String>,
+}
+
+#[cfg(not(feature = "no_network"))]
+impl ContainerCredentials {
+    fn into_credential(self) -> Result<Credential> {
+        Ok(Credential {
+            access_key_id: self.access_key_id,
+            secret_access_key: self.secret_access_key,
+            session_token: self.token,
+            expires_in: self.expiration.as_deref().map(parse_rfc3339).transpose()?,
+            arn: None,
+            account_id: self.account_id,
+        })
+    }
+}
+
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct Ec2MetadataIamSecurityCredentials {
@@ -557,6 +1892,8 @@ struct Ec2MetadataIamSecurityCredentials {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(not(feature = "no_network"))]
+    use std::collections::HashMap;
     use std::env;
     use std::str::FromStr;
     use std::vec;
@@ -565,6 +1902,7 @@ mod tests {
     use http::Request;
     use http::StatusCode;
     use once_cell::sync::Lazy;
+    #[cfg(not(feature = "no_network"))]
     use quick_xml::de;
     use reqwest::Client;
     use tokio::runtime::Runtime;
@@ -572,6 +1910,8 @@ mod tests {
     use super::*;
     use crate::aws::constants::*;
     use crate::aws::v4::Signer;
+    #[cfg(not(feature = "no_network"))]
+    use crate::test_util::MockServer;
 
     static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
         tokio::runtime::Builder::new_multi_thread()
@@ -580,6 +1920,94 @@ mod tests {
             .expect("Should create a tokio runtime")
     });
 
+    /// Loader that counts how many times it's called.
+    #[derive(Clone, Default)]
+    struct CountingLoader {
+        count: Arc<std::sync::atomic::AtomicUsize>,
+        expires_in: Option<Duration>,
+    }
+
+    impl CountingLoader {
+        fn count(&self) -> usize {
+            self.count.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl CredentialLoad for CountingLoader {
+        async fn load_credential(&self, _: Client) -> Result<Option<Credential>> {
+            self.count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(Some(Credential {
+                access_key_id: "access_key_id".to_string(),
+                secret_access_key: "secret_access_key".to_string(),
+                expires_in: self
+                    .expires_in
+                    .map(|v| now() + chrono::TimeDelta::from_std(v).expect("in bounds")),
+                ..Default::default()
+            }))
+        }
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_static_credential_provider() -> Result<()> {
+        let config = Config {
+            access_key_id: Some("config_access_key_id".to_string()),
+            secret_access_key: Some("config_secret_access_key".to_string()),
+            ..Default::default()
+        };
+        let cred = Credential {
+            access_key_id: "static_access_key_id".to_string(),
+            secret_access_key: "static_secret_access_key".to_string(),
+            session_token: Some("static_session_token".to_string()),
+            expires_in: Some(now() + chrono::TimeDelta::try_hours(1).expect("in bounds")),
+            ..Default::default()
+        };
+        let l = DefaultLoader::new(Client::new(), config)
+            .with_disable_ec2_metadata()
+            .with_customized_credential_loader(Box::new(StaticCredentialProvider::new(
+                cred.clone(),
+            )));
+
+        // Static credential takes precedence over config.
+        let x = l.load().await?.expect("credential must be loaded");
+        assert_eq!("static_access_key_id", x.access_key_id);
+        assert_eq!("static_secret_access_key", x.secret_access_key);
+        assert_eq!(Some("static_session_token"), x.session_token.as_deref());
+        assert_eq!(cred.expires_in, x.expires_in);
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_static_credential_provider_expired() -> Result<()> {
+        let cred = Credential {
+            access_key_id: "static_access_key_id".to_string(),
+            secret_access_key: "static_secret_access_key".to_string(),
+            session_token: None,
+            expires_in: Some(now() + chrono::TimeDelta::try_minutes(1).expect("in bounds")),
+            ..Default::default()
+        };
+        let provider = StaticCredentialProvider::new(cred);
+        assert!(provider.load_credential(Client::new()).await?.is_none());
+
+        // Fallback to the next source once the static credential is expired.
+        let config = Config {
+            access_key_id: Some("config_access_key_id".to_string()),
+            secret_access_key: Some("config_secret_access_key".to_string()),
+            ..Default::default()
+        };
+        let l = DefaultLoader::new(Client::new(), config)
+            .with_disable_ec2_metadata()
+            .with_customized_credential_loader(Box::new(provider));
+        let x = l.load().await?.expect("credential must be loaded");
+        assert_eq!("config_access_key_id", x.access_key_id);
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
     #[test]
     fn test_credential_env_loader_without_env() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -595,25 +2023,87 @@ mod tests {
     }
 
     #[test]
-    fn test_credential_env_loader_with_env() {
+    fn test_credential_env_loader_with_env() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        temp_env::with_vars(
+            vec![
+                (AWS_ACCESS_KEY_ID, Some("access_key_id")),
+                (AWS_SECRET_ACCESS_KEY, Some("secret_access_key")),
+            ],
+            || {
+                RUNTIME.block_on(async {
+                    let l = DefaultLoader::new(Client::new(), Config::default().from_env());
+                    let x = l.load().await.expect("load must succeed");
+
+                    let x = x.expect("must load succeed");
+                    assert_eq!("access_key_id", x.access_key_id);
+                    assert_eq!("secret_access_key", x.secret_access_key);
+                    // Static credentials don't carry identity.
+                    assert_eq!(None, x.arn);
+                    assert_eq!(None, x.account_id);
+                })
+            },
+        );
+    }
+
+    /// Start a mock sts service that handles exactly one AssumeRole request,
+    /// returns the endpoint and the received request head.
+    #[cfg(not(feature = "no_network"))]
+    #[test]
+    fn test_credential_env_loader_with_role_arn() {
         let _ = env_logger::builder().is_test(true).try_init();
 
+        let server = RUNTIME
+            .block_on(
+                MockServer::builder()
+                    .with_aws_assume_role(
+                        "assumed_access_key_id",
+                        "assumed_secret_access_key",
+                        "assumed_session_token",
+                    )
+                    .start(),
+            )
+            .expect("mock server must start");
+
         temp_env::with_vars(
             vec![
                 (AWS_ACCESS_KEY_ID, Some("access_key_id")),
                 (AWS_SECRET_ACCESS_KEY, Some("secret_access_key")),
+                (AWS_ROLE_ARN, Some("arn:aws:iam::123456789012:role/test")),
+                (AWS_ENDPOINT_URL_STS, Some(server.endpoint())),
+                (AWS_WEB_IDENTITY_TOKEN_FILE, None),
             ],
             || {
                 RUNTIME.block_on(async {
-                    let l = DefaultLoader::new(Client::new(), Config::default().from_env());
+                    let l = DefaultLoader::new(Client::new(), Config::default().from_env())
+                        .with_disable_ec2_metadata();
                     let x = l.load().await.expect("load must succeed");
 
                     let x = x.expect("must load succeed");
-                    assert_eq!("access_key_id", x.access_key_id);
-                    assert_eq!("secret_access_key", x.secret_access_key);
+                    assert_eq!("assumed_access_key_id", x.access_key_id);
+                    assert_eq!("assumed_secret_access_key", x.secret_access_key);
+                    assert_eq!(Some("assumed_session_token"), x.session_token.as_deref());
                 })
             },
         );
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert!(
+            requests[0]
+                .uri
+                .query()
+                .unwrap_or_default()
+                .starts_with("Action=AssumeRole&"),
+            "{:?}",
+            requests[0].uri
+        );
+        // The AssumeRole request must be signed by base credentials.
+        assert!(requests[0].headers[http::header::AUTHORIZATION]
+            .to_str()
+            .expect("authorization must be valid")
+            .starts_with("AWS4-HMAC-SHA256 Credential=access_key_id/"));
     }
 
     #[test]
@@ -698,6 +2188,227 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_credential_profile_loader_with_expiration() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let file_path = format!(
+            "{}/testdata/services/aws/temporary_credential",
+            env::current_dir()
+                .expect("load must exist")
+                .to_string_lossy()
+        );
+
+        let load = |profile: &str, expires_in: Option<DateTime>| {
+            temp_env::with_vars(
+                vec![
+                    (AWS_ACCESS_KEY_ID, None),
+                    (AWS_SECRET_ACCESS_KEY, None),
+                    (AWS_SESSION_TOKEN, None),
+                    (AWS_PROFILE, Some(profile)),
+                    (AWS_CONFIG_FILE, None),
+                    (AWS_SHARED_CREDENTIALS_FILE, Some(file_path.as_str())),
+                ],
+                || {
+                    let mut config = Config::default().from_env().from_profile();
+                    if expires_in.is_some() {
+                        config.expires_in = expires_in;
+                    }
+                    RUNTIME.block_on(DefaultLoader::new(Client::new(), config).load())
+                },
+            )
+        };
+
+        let cred = load("default", None).unwrap().unwrap();
+        assert_eq!("temporary_access_key_id", cred.access_key_id);
+        assert_eq!(
+            Some("temporary_session_token"),
+            cred.session_token.as_deref()
+        );
+        assert!(cred.expires_in.is_some());
+
+        // Credential expires before the re-read interval.
+        let expires_in = now() + chrono::TimeDelta::try_minutes(5).expect("in bounds");
+        let cred = load("default", Some(expires_in)).unwrap().unwrap();
+        assert_eq!(Some(expires_in), cred.expires_in);
+
+        let err = load("expired", None).expect_err("expired credential must be rejected");
+        assert!(err.to_string().contains("expired"), "{err}");
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[test]
+    fn test_describe_available() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let testdata = format!(
+            "{}/testdata/services/aws",
+            env::current_dir()
+                .expect("load must exist")
+                .to_string_lossy()
+        );
+        let describe = |envs: Vec<(&str, Option<String>)>, f: fn(&mut Config)| {
+            let mut envs = envs;
+            envs.push((AWS_PROFILE, None));
+            envs.push((AWS_ROLE_ARN, None));
+            envs.push((AWS_WEB_IDENTITY_TOKEN_FILE, None));
+            temp_env::with_vars(envs, || {
+                let mut config = Config::default().from_env().from_profile();
+                f(&mut config);
+                let l = DefaultLoader::new(Client::new(), config).with_disable_ec2_metadata();
+                RUNTIME
+                    .block_on(l.describe_available())
+                    .into_iter()
+                    .map(|v| (v.name, v))
+                    .collect::<HashMap<_, _>>()
+            })
+        };
+
+        // Nothing configured.
+        let status = describe(
+            vec![
+                (AWS_ACCESS_KEY_ID, None),
+                (AWS_SECRET_ACCESS_KEY, None),
+                (AWS_CONFIG_FILE, Some(format!("{testdata}/not_exist"))),
+                (
+                    AWS_SHARED_CREDENTIALS_FILE,
+                    Some(format!("{testdata}/not_exist")),
+                ),
+            ],
+            |_| {},
+        );
+        assert_eq!(7, status.len());
+        assert!(status.values().all(|v| !v.available), "{status:?}");
+        assert_eq!(
+            "AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY not set",
+            status["env"].detail
+        );
+        assert!(status["profile"].detail.contains("does not exist"));
+        assert_eq!("ec2 metadata is disabled", status["ec2_metadata"].detail);
+
+        // Credentials from env and profile.
+        let status = describe(
+            vec![
+                (AWS_ACCESS_KEY_ID, Some("env_access_key_id".to_string())),
+                (
+                    AWS_SECRET_ACCESS_KEY,
+                    Some("env_secret_access_key".to_string()),
+                ),
+                (AWS_CONFIG_FILE, Some(format!("{testdata}/default_config"))),
+                (
+                    AWS_SHARED_CREDENTIALS_FILE,
+                    Some(format!("{testdata}/default_credential")),
+                ),
+            ],
+            |config| config.role_arn = Some("arn:aws:iam::123456789012:role/test".to_string()),
+        );
+        for name in ["env", "profile", "config", "assume_role"] {
+            assert!(status[name].available, "{}", status[name]);
+        }
+        assert!(!status["web_identity"].available);
+        assert!(status["profile"].detail.contains(&format!(
+            "profile default found in {testdata}/default_credential"
+        )));
+        // Secret values must not be leaked.
+        for v in status.values() {
+            assert!(!v.to_string().contains("access_key_id"), "{v}");
+            assert!(!v.to_string().contains("secret_access_key"), "{v}");
+        }
+
+        // Web identity without token file.
+        let status = describe(
+            vec![
+                (AWS_ACCESS_KEY_ID, None),
+                (AWS_SECRET_ACCESS_KEY, None),
+                (AWS_CONFIG_FILE, Some(format!("{testdata}/not_exist"))),
+                (
+                    AWS_SHARED_CREDENTIALS_FILE,
+                    Some(format!("{testdata}/not_exist")),
+                ),
+            ],
+            |config| {
+                config.role_arn = Some("arn:aws:iam::123456789012:role/test".to_string());
+                config.web_identity_token_file = Some("/not_exist/token".to_string());
+            },
+        );
+        assert!(!status["assume_role"].available);
+        assert!(!status["web_identity"].available);
+        assert_eq!(
+            "token file /not_exist/token does not exist",
+            status["web_identity"].detail
+        );
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_describe_available_ec2_metadata() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_aws_imds(
+                "test_role",
+                "access_key_id",
+                "secret_access_key",
+                "session_token",
+            )
+            .start()
+            .await?;
+        let loader = DefaultLoader::new(Client::new(), Config::default())
+            .with_ec2_metadata_endpoint(server.endpoint());
+        let status = loader.describe_available().await;
+        let ec2 = status
+            .iter()
+            .find(|v| v.name == "ec2_metadata")
+            .expect("ec2_metadata must be described");
+        assert!(ec2.available, "{ec2}");
+        // Only the session token is fetched, credentials are not loaded.
+        assert!(server
+            .requests()
+            .iter()
+            .all(|v| v.uri.path() == "/latest/api/token"));
+
+        drop(server);
+        let loader = DefaultLoader::new(Client::new(), Config::default())
+            .with_ec2_metadata_endpoint("http://127.0.0.1:1");
+        let status = loader.describe_available().await;
+        let ec2 = status
+            .iter()
+            .find(|v| v.name == "ec2_metadata")
+            .expect("ec2_metadata must be described");
+        assert!(!ec2.available, "{ec2}");
+        assert!(ec2.detail.contains("unreachable"), "{ec2}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_describe_available_with() {
+        let config = Config {
+            ec2_metadata_disabled: true,
+            ..Default::default()
+        };
+        let loader = DefaultLoader::new(Client::new(), config);
+        let describe_env = |status: Vec<SourceStatus>| {
+            status
+                .into_iter()
+                .find(|v| v.name == "env")
+                .expect("env must be described")
+        };
+
+        let env = EnvSnapshot::new()
+            .with_env(AWS_ACCESS_KEY_ID, "access_key_id")
+            .with_env(AWS_SECRET_ACCESS_KEY, "secret_access_key");
+        let status = describe_env(loader.describe_available_with(&env).await);
+        assert!(status.available, "{status}");
+
+        let status = describe_env(loader.describe_available_with(&EnvSnapshot::new()).await);
+        assert!(!status.available, "{status}");
+        assert_eq!(
+            format!("{AWS_ACCESS_KEY_ID}, {AWS_SECRET_ACCESS_KEY} not set"),
+            status.detail
+        );
+    }
+
     /// AWS_SHARED_CREDENTIALS_FILE should be taken first.
     #[test]
     fn test_credential_profile_loader_from_both() {
@@ -815,6 +2526,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(not(feature = "no_network"))]
     #[test]
     fn test_signer_with_web_loader_assume_role() -> Result<()> {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -900,6 +2612,54 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_credential_builder() -> Result<()> {
+        let cred = Credential::builder()
+            .access_key("access_key_id")
+            .secret_key("secret_access_key")
+            .session_token("session_token")
+            .build()?;
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!("secret_access_key", cred.secret_access_key);
+        assert_eq!(Some("session_token"), cred.session_token.as_deref());
+        assert_eq!(None, cred.expires_in);
+        assert!(cred.is_valid());
+
+        Ok(())
+    }
+
+    #[test_case::test_case(None, Some("secret_access_key"), CredentialBuildError::MissingAccessKey ; "missing access key")]
+    #[test_case::test_case(Some(""), Some("secret_access_key"), CredentialBuildError::MissingAccessKey ; "empty access key")]
+    #[test_case::test_case(Some("access_key_id"), None, CredentialBuildError::MissingSecretKey ; "missing secret key")]
+    #[test_case::test_case(Some("access_key_id"), Some(""), CredentialBuildError::MissingSecretKey ; "empty secret key")]
+    fn test_credential_builder_error(
+        access_key: Option<&str>,
+        secret_key: Option<&str>,
+        expected: CredentialBuildError,
+    ) {
+        let mut builder = Credential::builder().session_token("session_token");
+        if let Some(v) = access_key {
+            builder = builder.access_key(v);
+        }
+        if let Some(v) = secret_key {
+            builder = builder.secret_key(v);
+        }
+        assert_eq!(expected, builder.build().unwrap_err());
+    }
+
+    #[test_case::test_case("arn:aws:sts::123456789012:assumed-role/demo/TestAR", Some("123456789012"); "assumed role")]
+    #[test_case::test_case("arn:aws-cn:iam::123456789012:user/test", Some("123456789012"); "china partition")]
+    #[test_case::test_case("arn:aws:s3:::bucket", None; "without account")]
+    #[test_case::test_case("not-an-arn", None; "invalid")]
+    fn test_account_id_from_arn(arn: &str, expected: Option<&str>) {
+        assert_eq!(expected, account_id_from_arn(arn).as_deref());
+
+        let cred = Credential::default().with_arn(Some(arn.to_string()));
+        assert_eq!(Some(arn), cred.arn.as_deref());
+        assert_eq!(expected, cred.account_id.as_deref());
+    }
+
+    #[cfg(not(feature = "no_network"))]
     #[test]
     fn test_parse_assume_role_with_web_identity_response() -> Result<()> {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -935,10 +2695,15 @@ mod tests {
         );
         assert_eq!(&resp.result.credentials.session_token, "session_token");
         assert_eq!(&resp.result.credentials.expiration, "2022-05-25T11:45:17Z");
+        assert_eq!(
+            "arn:aws:sts::123:assumed-role/reqsign/reqsign",
+            resp.result.assumed_role_user.expect("must exist").arn
+        );
 
         Ok(())
     }
 
+    #[cfg(not(feature = "no_network"))]
     #[test]
     fn test_parse_assume_role_response() -> Result<()> {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -988,6 +2753,658 @@ mod tests {
        +scqKmlzm8FDrypNC9Yjc8fPOLn9FX9KSYvKTr4rvx3iSIlTJabIQwj2ICCR/oLxBA=="
         );
         assert_eq!(&resp.result.credentials.expiration, "2019-11-09T13:34:41Z");
+        assert_eq!(
+            "arn:aws:sts::123456789012:assumed-role/demo/TestAR",
+            resp.result.assumed_role_user.expect("must exist").arn
+        );
+
+        Ok(())
+    }
+
+    /// Start a mock ec2 metadata service, returns the endpoint and all received
+    /// request heads.
+    #[cfg(not(feature = "no_network"))]
+    #[test_case::test_case(StatusCode::INTERNAL_SERVER_ERROR, false, 1; "not retry server error by default")]
+    #[test_case::test_case(StatusCode::FORBIDDEN, false, 1; "not retry client error by default")]
+    #[test_case::test_case(StatusCode::FORBIDDEN, true, 2; "retry client error by predicate")]
+    #[tokio::test]
+    async fn test_default_loader_with_retry_policy(
+        status: StatusCode,
+        custom: bool,
+        expected_attempts: usize,
+    ) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(
+                http::Method::PUT,
+                "/latest/api/token",
+                crate::test_util::MockResponse::new(status, "try again"),
+            )
+            .with_aws_imds(
+                "test_role",
+                "access_key_id",
+                "secret_access_key",
+                "session_token",
+            )
+            .start()
+            .await?;
+        let mut loader = DefaultLoader::new(Client::new(), Config::default())
+            .with_ec2_metadata_endpoint(server.endpoint());
+        if custom {
+            loader = loader.with_retry_policy(|result| {
+                result.is_ok_and(|resp| resp.status() == StatusCode::FORBIDDEN)
+            });
+        }
+
+        let cred = loader.load().await;
+        let token_requests = server
+            .requests()
+            .into_iter()
+            .filter(|v| v.uri.path() == "/latest/api/token")
+            .count();
+        assert_eq!(expected_attempts, token_requests);
+        if expected_attempts > 1 {
+            let cred = cred?.expect("credential must be loaded");
+            assert_eq!("access_key_id", cred.access_key_id);
+        } else {
+            assert!(!matches!(cred, Ok(Some(_))));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[test_case::test_case(Duration::from_secs(300), "300" ; "in range")]
+    #[test_case::test_case(Duration::from_millis(500), "1" ; "sub second")]
+    #[test_case::test_case(Duration::from_secs(86400), "21600" ; "over max")]
+    #[tokio::test]
+    async fn test_imds_v2_loader_token_ttl(ttl: Duration, expected: &str) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_aws_imds(
+                "test_role",
+                "access_key_id",
+                "secret_access_key",
+                "session_token",
+            )
+            .start()
+            .await?;
+        let loader = IMDSv2Loader::new(Client::new())
+            .with_endpoint(server.endpoint())
+            .with_token_ttl(ttl);
+
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("access_key_id", cred.access_key_id);
+        // Load again, the session token should be reused.
+        loader.load().await?.expect("credential must be loaded");
+
+        let requests = server.requests();
+        let token_requests = requests
+            .iter()
+            .filter(|v| v.method == http::Method::PUT && v.uri.path() == "/latest/api/token")
+            .collect::<Vec<_>>();
+        assert_eq!(1, token_requests.len());
+        assert_eq!(
+            token_requests[0].headers["x-aws-ec2-metadata-token-ttl-seconds"],
+            expected
+        );
+        // All requests should carry the default user agent.
+        assert!(requests
+            .iter()
+            .all(|v| v.headers[USER_AGENT] == DEFAULT_USER_AGENT));
+        // All credential requests should carry the same session token.
+        assert_eq!(
+            4,
+            requests
+                .iter()
+                .filter(|v| v
+                    .headers
+                    .get("x-aws-ec2-metadata-token")
+                    .is_some_and(|v| v == "mock_ec2_token"))
+                .count()
+        );
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_imds_v2_loader_token_refresh() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_aws_imds(
+                "test_role",
+                "access_key_id",
+                "secret_access_key",
+                "session_token",
+            )
+            .start()
+            .await?;
+        let loader = IMDSv2Loader::new(Client::new())
+            .with_endpoint(server.endpoint())
+            .with_token_ttl(Duration::from_secs(1));
+
+        loader.load().await?.expect("credential must be loaded");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        loader.load().await?.expect("credential must be loaded");
+
+        let token_requests = server
+            .requests()
+            .iter()
+            .filter(|v| v.method == http::Method::PUT && v.uri.path() == "/latest/api/token")
+            .count();
+        assert_eq!(2, token_requests);
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[test_case::test_case("test_external_id", "ExternalId=test_external_id" ; "plain")]
+    #[test_case::test_case("a+b=c&d/e f", "ExternalId=a%2Bb%3Dc%26d%2Fe%20f" ; "special characters")]
+    #[tokio::test]
+    async fn test_assume_role_loader_with_external_id(
+        external_id: &str,
+        expected: &str,
+    ) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_aws_assume_role(
+                "assumed_access_key_id",
+                "assumed_secret_access_key",
+                "assumed_session_token",
+            )
+            .start()
+            .await?;
+
+        let client = Client::new();
+        let source = DefaultLoader::new(
+            client.clone(),
+            Config {
+                access_key_id: Some("access_key_id".to_string()),
+                secret_access_key: Some("secret_access_key".to_string()),
+                ..Default::default()
+            },
+        )
+        .with_disable_ec2_metadata();
+        let cfg = Config {
+            region: Some("us-east-1".to_string()),
+            role_arn: Some("arn:aws:iam::123456789012:role/test".to_string()),
+            sts_endpoint: Some(server.endpoint().to_string()),
+            ..Default::default()
+        };
+        let loader =
+            AssumeRoleLoader::new(client, cfg, Box::new(source))?.with_external_id(external_id);
+
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("assumed_access_key_id", cred.access_key_id);
+        assert_eq!(
+            Some(crate::test_util::MOCK_ASSUMED_ROLE_ARN),
+            cred.arn.as_deref()
+        );
+        assert_eq!(Some("123456789012"), cred.account_id.as_deref());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert!(
+            requests[0]
+                .uri
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .any(|v| v == expected),
+            "{:?}",
+            requests[0].uri
+        );
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_assume_role_loader_with_duration_and_policy() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_aws_assume_role(
+                "assumed_access_key_id",
+                "assumed_secret_access_key",
+                "assumed_session_token",
+            )
+            .start()
+            .await?;
+
+        let client = Client::new();
+        let loader = |duration_seconds: u32| {
+            let source = DefaultLoader::new(
+                client.clone(),
+                Config {
+                    access_key_id: Some("access_key_id".to_string()),
+                    secret_access_key: Some("secret_access_key".to_string()),
+                    ..Default::default()
+                },
+            )
+            .with_disable_ec2_metadata();
+            let cfg = Config {
+                region: Some("us-east-1".to_string()),
+                role_arn: Some("arn:aws:iam::123456789012:role/test".to_string()),
+                sts_endpoint: Some(server.endpoint().to_string()),
+                ..Default::default()
+            };
+            AssumeRoleLoader::new(client.clone(), cfg, Box::new(source)).map(|l| {
+                l.with_duration_seconds(duration_seconds)
+                    .with_policy(r#"{"Version":"2012-10-17","Statement":[]}"#)
+                    .with_policy_arns(&[
+                        "arn:aws:iam::aws:policy/ReadOnlyAccess",
+                        "arn:aws:iam::123456789012:policy/test",
+                    ])
+            })
+        };
+
+        let cred = loader(3600)?
+            .load()
+            .await?
+            .expect("credential must be loaded");
+        assert_eq!("assumed_access_key_id", cred.access_key_id);
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        let query: HashMap<_, _> =
+            form_urlencoded::parse(requests[0].uri.query().unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        assert_eq!(
+            Some("3600"),
+            query.get("DurationSeconds").map(|v| v.as_str())
+        );
+        assert_eq!(
+            Some(r#"{"Version":"2012-10-17","Statement":[]}"#),
+            query.get("Policy").map(|v| v.as_str())
+        );
+        assert_eq!(
+            Some("arn:aws:iam::aws:policy/ReadOnlyAccess"),
+            query.get("PolicyArns.member.1.arn").map(|v| v.as_str())
+        );
+        assert_eq!(
+            Some("arn:aws:iam::123456789012:policy/test"),
+            query.get("PolicyArns.member.2.arn").map(|v| v.as_str())
+        );
+
+        for duration_seconds in [899, 43201] {
+            let err = loader(duration_seconds)?
+                .load()
+                .await
+                .expect_err("out of range duration must fail");
+            assert!(err.to_string().contains("out of range"), "{err}");
+        }
+        // Invalid duration is rejected before sending requests.
+        assert_eq!(1, server.requests().len());
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_imds_v2_loader_with_user_agent() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_aws_imds(
+                "test_role",
+                "access_key_id",
+                "secret_access_key",
+                "session_token",
+            )
+            .start()
+            .await?;
+        let loader = IMDSv2Loader::new(Client::new())
+            .with_endpoint(server.endpoint())
+            .with_user_agent("reqsign-test/1.0");
+        loader.load().await?.expect("credential must be loaded");
+
+        let requests = server.requests();
+        assert_eq!(3, requests.len());
+        assert!(requests
+            .iter()
+            .all(|v| v.headers[USER_AGENT] == "reqsign-test/1.0"));
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_imds_v2_loader_with_user_agent_suffix() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_aws_imds(
+                "test_role",
+                "access_key_id",
+                "secret_access_key",
+                "session_token",
+            )
+            .start()
+            .await?;
+        let loader = DefaultLoader::new(Client::new(), Config::default())
+            .with_ec2_metadata_endpoint(server.endpoint())
+            .with_user_agent_suffix("my-app/2.0");
+        loader
+            .load_via_imds_v2()
+            .await?
+            .expect("credential must be loaded");
+
+        let expected = format!("reqsign/{} my-app/2.0", env!("CARGO_PKG_VERSION"));
+        let requests = server.requests();
+        assert_eq!(3, requests.len());
+        assert!(requests.iter().all(|v| v.headers[USER_AGENT] == expected));
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_container_loader_with_loopback_full_uri() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(
+                http::Method::GET,
+                "/credentials",
+                crate::test_util::MockResponse::ok(
+                    r#"{"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key","Token":"session_token","Expiration":"2124-05-25T11:45:17Z"}"#,
+                ),
+            )
+            .start()
+            .await?;
+        let tmp_dir = tempfile::tempdir()?;
+        let token_file = tmp_dir.path().join("token");
+        fs::write(&token_file, "container_token\n")?;
+
+        let loader = ContainerLoader::new(Client::new())
+            .with_full_uri(&format!("{}/credentials", server.endpoint()))
+            .with_authorization_token("ignored")
+            .with_authorization_token_file(token_file.to_str().unwrap());
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!("secret_access_key", cred.secret_access_key);
+        assert_eq!(Some("session_token".to_string()), cred.session_token);
+        assert!(cred.expires_in.is_some());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(requests[0].headers[AUTHORIZATION], "container_token");
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_loader() -> Result<()> {
+        use tokio::io::AsyncReadExt;
+        use tokio::io::AsyncWriteExt;
+
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let tmp_dir = tempfile::tempdir()?;
+        let path = tmp_dir.path().join("broker.sock");
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        // Serve one request with canned credentials, the request head is returned.
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await?;
+            let mut head = Vec::new();
+            let mut buf = [0; 1024];
+            while !head.windows(4).any(|v| v == b"\r\n\r\n") {
+                let n = stream.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                head.extend_from_slice(&buf[..n]);
+            }
+
+            let body = r#"{"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key","Token":"session_token","Expiration":"2124-05-25T11:45:17Z","AccountId":"123456789012"}"#;
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(resp.as_bytes()).await?;
+            stream.shutdown().await?;
+            Ok::<_, std::io::Error>(String::from_utf8_lossy(&head).to_ascii_lowercase())
+        });
+
+        let loader = UnixSocketLoader::new(&path)?
+            .with_uri("/credentials")
+            .with_authorization_token("broker_token");
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!("secret_access_key", cred.secret_access_key);
+        assert_eq!(Some("session_token".to_string()), cred.session_token);
+        assert_eq!(Some("123456789012".to_string()), cred.account_id);
+        assert!(cred.expires_in.is_some());
+
+        let head = server.await??;
+        assert!(head.starts_with("get /credentials http/1.1\r\n"), "{head}");
+        assert!(
+            head.contains("\r\nauthorization: broker_token\r\n"),
+            "{head}"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_loader_without_broker() -> Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let loader = UnixSocketLoader::new(tmp_dir.path().join("not_exist.sock"))?;
+        assert!(loader.load().await.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[tokio::test]
+    async fn test_container_loader_rejects_external_full_uri() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let loader = ContainerLoader::new(Client::new())
+            .with_full_uri("http://169.254.169.254.attacker.example.com/credentials")
+            .with_authorization_token("secret");
+        let err = loader
+            .load()
+            .await
+            .expect_err("external host must be rejected");
+        assert!(err.to_string().contains("not allowed"), "{err}");
+
+        // Explicitly allowed hosts skip the check.
+        let loader = ContainerLoader::new(Client::new())
+            .with_full_uri("http://attacker.example.com/credentials")
+            .with_allowed_host("attacker.example.com");
+        assert!(loader
+            .check_full_uri("http://attacker.example.com/credentials")
+            .is_ok());
+
+        for uri in [
+            "http://127.0.0.1:8080/creds",
+            "http://localhost/creds",
+            "http://[::1]/creds",
+            "http://169.254.170.2/creds",
+            "http://169.254.170.23/v1/credentials",
+            "http://[fd00:ec2::23]/v1/credentials",
+        ] {
+            assert!(
+                ContainerLoader::new(Client::new())
+                    .check_full_uri(uri)
+                    .is_ok(),
+                "{uri} must be allowed"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_default_loader_refresh_ahead() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let source = CountingLoader {
+            expires_in: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        let loader = Arc::new(
+            DefaultLoader::new(Client::new(), Config::default())
+                .with_customized_credential_loader(Box::new(source.clone())),
+        );
+        // Refresh every 10s since credentials expire in 1 hour.
+        let guard = loader.refresh_ahead(Duration::from_secs(3590));
+
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert_eq!(3, source.count());
+        // Cached credential can be loaded without touching the source.
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!(3, source.count());
+
+        drop(guard);
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(3, source.count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_credential_with_expires_in() {
+        let cred = Credential {
+            access_key_id: "access_key_id".to_string(),
+            secret_access_key: "secret_access_key".to_string(),
+            ..Default::default()
+        };
+        assert!(cred.is_valid());
+
+        let cred =
+            cred.with_expires_in(now() + chrono::TimeDelta::try_minutes(1).expect("in bounds"));
+        assert!(
+            !cred.is_valid(),
+            "credential in refresh buffer should be invalid"
+        );
+    }
+
+    #[test_case::test_case(
+        r#"{"Version":1,"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key","SessionToken":"session_token","Expiration":"2099-01-01T00:00:00Z"}"#,
+        Some("session_token"),
+        true;
+        "temporary credential"
+    )]
+    #[test_case::test_case(
+        r#"{"Version":1,"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key"}"#,
+        None,
+        false;
+        "static credential"
+    )]
+    fn test_parse_process_credentials(
+        content: &str,
+        session_token: Option<&str>,
+        has_expiration: bool,
+    ) -> Result<()> {
+        let cred = parse_process_credentials(content.as_bytes())?;
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!("secret_access_key", cred.secret_access_key);
+        assert_eq!(session_token, cred.session_token.as_deref());
+        assert_eq!(has_expiration, cred.expires_in.is_some());
+        Ok(())
+    }
+
+    #[test_case::test_case(
+        r#"{"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key"}"#,
+        "missing Version";
+        "missing version"
+    )]
+    #[test_case::test_case(
+        r#"{"Version":2,"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key"}"#,
+        "Version 2 is not supported";
+        "unsupported version"
+    )]
+    #[test_case::test_case(
+        r#"{"Version":1,"AccessKeyId":"access_key_id"}"#,
+        "missing AccessKeyId or SecretAccessKey";
+        "missing secret access key"
+    )]
+    #[test_case::test_case(
+        r#"{"Version":1,"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key","Expiration":"2020-01-01T00:00:00Z"}"#,
+        "has been expired";
+        "expired credential"
+    )]
+    fn test_parse_process_credentials_failed(content: &str, expected: &str) {
+        let err = parse_process_credentials(content.as_bytes()).expect_err("parse must fail");
+        assert!(err.to_string().contains(expected), "{err}");
+    }
+
+    #[cfg(not(feature = "no_network"))]
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_default_loader_with_credential_process() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let loader = DefaultLoader::new(
+            Client::new(),
+            Config {
+                credential_process: Some(
+                    r#"echo '{"Version":1,"AccessKeyId":"access_key_id","SecretAccessKey":"secret_access_key"}'"#
+                        .to_string(),
+                ),
+                ..Default::default()
+            },
+        )
+        .with_disable_ec2_metadata();
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!("secret_access_key", cred.secret_access_key);
+
+        let loader = DefaultLoader::new(
+            Client::new(),
+            Config {
+                credential_process: Some("echo 'access denied' >&2; exit 1".to_string()),
+                ..Default::default()
+            },
+        )
+        .with_disable_ec2_metadata();
+        let err = loader.load().await.expect_err("load must fail");
+        assert!(err.to_string().contains("access denied"), "{err}");
+
+        Ok(())
+    }
+
+    #[test_case::test_case(None, 1 ; "not clamped")]
+    #[test_case::test_case(Some(Duration::from_secs(3600)), 1 ; "clamped but valid")]
+    #[test_case::test_case(Some(Duration::from_secs(60)), 2 ; "clamped into refresh buffer")]
+    #[tokio::test]
+    async fn test_default_loader_with_max_expires_in(
+        max: Option<Duration>,
+        expected: usize,
+    ) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // Credentials from source never expire.
+        let source = CountingLoader::default();
+        let mut loader = DefaultLoader::new(Client::new(), Config::default())
+            .with_customized_credential_loader(Box::new(source.clone()));
+        if let Some(max) = max {
+            loader = loader.with_max_expires_in(max);
+        }
+
+        let cred = loader.load().await?.expect("credential must be loaded");
+        if let Some(max) = max {
+            let expires_in = cred.expires_in.expect("expires_in must be clamped");
+            assert!(expires_in <= now() + chrono::TimeDelta::from_std(max)?);
+        }
+        // Credentials clamped into the 120s refresh buffer are refreshed on
+        // every load.
+        loader.load().await?.expect("credential must be loaded");
+        assert_eq!(expected, source.count());
 
         Ok(())
     }
@@ -5,12 +5,35 @@ mod config;
 pub use config::Config as AwsConfig;
 
 mod credential;
+#[cfg(not(feature = "no_network"))]
 pub use credential::AssumeRoleLoader as AwsAssumeRoleLoader;
+#[cfg(not(feature = "no_network"))]
+pub use credential::ContainerLoader as AwsContainerLoader;
 pub use credential::Credential as AwsCredential;
+pub use credential::CredentialBuildError as AwsCredentialBuildError;
+pub use credential::CredentialBuilder as AwsCredentialBuilder;
 pub use credential::CredentialLoad as AwsCredentialLoad;
 pub use credential::DefaultLoader as AwsDefaultLoader;
+pub use credential::SourceStatus as AwsSourceStatus;
+pub use credential::StaticCredentialProvider as AwsStaticCredentialProvider;
+#[cfg(all(unix, not(feature = "no_network")))]
+pub use credential::UnixSocketLoader as AwsUnixSocketLoader;
+
+mod chunked;
+pub use chunked::ChunkSigner as AwsChunkSigner;
+pub use chunked::StreamingPayload as AwsStreamingPayload;
 
 mod v4;
+pub use v4::ContentHashStrategy as AwsContentHashStrategy;
 pub use v4::Signer as AwsV4Signer;
 
-mod constants;
+crate::client::signing_client!(
+    /// Signing client for AWS, which loads credential by [`AwsDefaultLoader`] and
+    /// signs requests by [`AwsV4Signer`].
+    AwsSigningClient,
+    "aws",
+    AwsDefaultLoader,
+    AwsV4Signer
+);
+
+pub(crate) mod constants;
@@ -0,0 +1,294 @@
+//! Run the [AWS signing test suite](https://github.com/awslabs/aws-c-auth/tree/v0.9.0/tests/aws-signing-test-suite)
+//! against our SigV4 signer.
+//!
+//! Fixtures are stored in `testdata/services/aws/sigv4-test-suite`, every
+//! case contains a `request.txt` and the expected canonical request, string
+//! to sign and signature for both header and query signing.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use http::uri::Authority;
+use http::uri::Scheme;
+use http::HeaderName;
+use http::HeaderValue;
+use pretty_assertions::StrComparison;
+use serde::Deserialize;
+
+use super::ContentHashStrategy;
+use super::Signer;
+use crate::aws::credential::Credential;
+use crate::ctx::SigningContext;
+use crate::ctx::SigningMethod;
+use crate::time::parse_rfc3339;
+
+/// Cases that can't pass by design, with the reason.
+const SKIPPED: &[(&str, &str)] = &[
+    (
+        "get-relative-normalized",
+        "path normalization is not supported",
+    ),
+    (
+        "get-relative-relative-normalized",
+        "path normalization is not supported",
+    ),
+    (
+        "get-slash-dot-slash-normalized",
+        "path normalization is not supported",
+    ),
+    (
+        "get-slash-normalized",
+        "path normalization is not supported",
+    ),
+    (
+        "get-slash-pointless-dot-normalized",
+        "path normalization is not supported",
+    ),
+    (
+        "get-slashes-normalized",
+        "path normalization is not supported",
+    ),
+];
+
+#[derive(Deserialize)]
+struct Context {
+    credentials: ContextCredentials,
+    expiration_in_seconds: u64,
+    region: String,
+    service: String,
+    sign_body: bool,
+    timestamp: String,
+    #[serde(default)]
+    omit_session_token: bool,
+}
+
+#[derive(Deserialize)]
+struct ContextCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: Option<String>,
+}
+
+fn suite_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/services/aws/sigv4-test-suite")
+}
+
+/// Parse the raw http request in `request.txt`.
+///
+/// Paths are not percent-encoded in fixtures, and folded header lines will
+/// be joined into one value.
+fn parse_request(content: &str) -> Result<(SigningContext, Vec<u8>)> {
+    let (head, body) = content.split_once("\n\n").unwrap_or((content, ""));
+    let mut lines = head.lines();
+
+    let request_line = lines
+        .next()
+        .ok_or_else(|| anyhow!("request line is missing"))?;
+    let (method, target) = request_line
+        .strip_suffix(" HTTP/1.1")
+        .and_then(|v| v.split_once(' '))
+        .ok_or_else(|| anyhow!("request line {request_line} is invalid"))?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in lines {
+        if line.starts_with([' ', '\t']) {
+            let (_, value) = headers
+                .last_mut()
+                .ok_or_else(|| anyhow!("folded header line {line} without header"))?;
+            value.push_str(line);
+            continue;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("header line {line} is invalid"))?;
+        headers.push((name.to_string(), value.to_string()));
+    }
+
+    let mut header_map = http::HeaderMap::new();
+    for (name, value) in headers {
+        header_map.append(HeaderName::from_str(&name)?, HeaderValue::from_str(&value)?);
+    }
+    let host = header_map
+        .get(http::header::HOST)
+        .ok_or_else(|| anyhow!("host header is missing"))?
+        .to_str()?;
+
+    let ctx = SigningContext {
+        method: http::Method::from_str(method)?,
+        scheme: Scheme::HTTPS,
+        authority: Authority::from_str(host)?,
+        path: path.to_string(),
+        query: form_urlencoded::parse(query.as_bytes())
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect(),
+        headers: header_map,
+    };
+    Ok((ctx, body.as_bytes().to_vec()))
+}
+
+/// Compare the artifact with the fixture if it exists, the diff will be
+/// recorded into `failures`.
+fn check(dir: &Path, file: &str, actual: &str, failures: &mut Vec<String>) -> Result<()> {
+    let path = dir.join(file);
+    if !path.exists() {
+        return Ok(());
+    }
+    let expected = fs::read_to_string(&path)?;
+    record_diff(dir, file, &expected, actual, failures);
+    Ok(())
+}
+
+fn record_diff(dir: &Path, file: &str, expected: &str, actual: &str, failures: &mut Vec<String>) {
+    if expected != actual {
+        let case = dir.file_name().unwrap().to_string_lossy();
+        failures.push(format!(
+            "{case}/{file}:\n{}",
+            StrComparison::new(expected, actual)
+        ));
+    }
+}
+
+fn run_case(dir: &Path, failures: &mut Vec<String>) -> Result<()> {
+    let request = fs::read_to_string(dir.join("request.txt"))?;
+
+    let context_path = dir.join("context.json");
+    if !context_path.exists() {
+        // Legacy cases are only used to check the double encoded canonical uri,
+        // their `x-amz-date` don't match the request.
+        let (mut ctx, body) = parse_request(&request)?;
+        let signer = Signer::new("service", "us-east-1")
+            .with_double_uri_encode(true)
+            .with_content_sha256_header(false)
+            .content_hash_strategy(ContentHashStrategy::Compute);
+        let cred = Credential {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            ..Default::default()
+        };
+        let (creq, _) = signer.canonicalize(
+            &mut ctx,
+            SigningMethod::Header,
+            Some(&body),
+            &cred,
+            crate::time::now(),
+        )?;
+        let file = "header-canonical-request.txt";
+        let expected = fs::read_to_string(dir.join(file))?;
+        let first_lines = |v: &str| v.lines().take(2).collect::<Vec<_>>().join("\n");
+        record_diff(
+            dir,
+            file,
+            &first_lines(&expected),
+            &first_lines(&creq),
+            failures,
+        );
+        return Ok(());
+    }
+
+    let context: Context = serde_json::from_slice(&fs::read(context_path)?)?;
+    let now = parse_rfc3339(&context.timestamp)?;
+    let cred = Credential {
+        access_key_id: context.credentials.access_key_id,
+        secret_access_key: context.credentials.secret_access_key,
+        session_token: context.credentials.token,
+        expires_in: None,
+        ..Default::default()
+    };
+    // Paths in fixtures are not encoded, so they should be encoded only once.
+    let signer = Signer::new(&context.service, &context.region)
+        .with_double_uri_encode(false)
+        .with_sign_session_token(!context.omit_session_token)
+        .content_hash_strategy(ContentHashStrategy::Compute);
+
+    for (prefix, method, signer) in [
+        (
+            "header",
+            SigningMethod::Header,
+            // `x-amz-content-sha256` is only sent if body is signed.
+            signer.clone().with_content_sha256_header(context.sign_body),
+        ),
+        (
+            "query",
+            SigningMethod::Query(Duration::from_secs(context.expiration_in_seconds)),
+            signer.clone().with_content_sha256_header(false),
+        ),
+    ] {
+        let (mut ctx, body) = parse_request(&request)?;
+        let (creq, string_to_sign) =
+            signer.canonicalize(&mut ctx, method, Some(&body), &cred, now)?;
+        check(
+            dir,
+            &format!("{prefix}-canonical-request.txt"),
+            &creq,
+            failures,
+        )?;
+        check(
+            dir,
+            &format!("{prefix}-string-to-sign.txt"),
+            &string_to_sign,
+            failures,
+        )?;
+
+        let (ctx, body) = parse_request(&request)?;
+        let (_, signature) = signer.build_context(ctx, method, Some(&body), &cred, now, None)?;
+        check(
+            dir,
+            &format!("{prefix}-signature.txt"),
+            &signature,
+            failures,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_sigv4_test_suite() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let mut cases = fs::read_dir(suite_dir())?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    cases.retain(|v| v.is_dir());
+    cases.sort();
+
+    for (name, _) in SKIPPED {
+        assert!(
+            cases.iter().any(|v| v.ends_with(name)),
+            "skipped case {name} doesn't exist"
+        );
+    }
+
+    let mut failures = Vec::new();
+    let mut passed = 0;
+    for dir in &cases {
+        let name = dir.file_name().unwrap().to_string_lossy();
+        if let Some((_, reason)) = SKIPPED.iter().find(|(v, _)| *v == name) {
+            log::debug!("skip case {name}: {reason}");
+            continue;
+        }
+
+        let before = failures.len();
+        if let Err(err) = run_case(dir, &mut failures) {
+            failures.push(format!("{name}: {err:?}"));
+        }
+        if failures.len() == before {
+            passed += 1;
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} cases passed, failures:\n\n{}",
+        passed,
+        cases.len() - SKIPPED.len(),
+        failures.join("\n\n")
+    );
+    Ok(())
+}
@@ -2,6 +2,7 @@ use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
 
 // Headers used in azure services.
 pub const X_MS_DATE: &str = "x-ms-date";
+pub const X_MS_VERSION: &str = "x-ms-version";
 pub const CONTENT_MD5: &str = "content-md5";
 
 pub static AZURE_QUERY_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
@@ -1,5 +1,10 @@
 use std::collections::HashMap;
-use std::env;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::EnvSnapshot;
+use crate::Warning;
 
 /// Config carries all the configuration for Azure Storage services.
 #[derive(Clone, Default)]
@@ -13,26 +18,42 @@ pub struct Config {
     ///
     /// - this field if it's `is_some`
     pub account_key: Option<String>,
+    /// `secondary_account_key` will be loaded from
+    ///
+    /// - this field if it's `is_some`
+    ///
+    /// Azure storage accounts have two account keys to support key rotation.
+    /// The secondary key will only be used after
+    /// [`Loader::failover_to_secondary_key`](crate::AzureStorageLoader::failover_to_secondary_key)
+    /// is called.
+    pub secondary_account_key: Option<String>,
     /// `sas_token` will be loaded from
     ///
     /// - this field if it's `is_some`
+    /// - connection string: `SharedAccessSignature`
+    ///
+    /// This field is used as is, set it via [`Config::with_sas_token`] to
+    /// accept tokens with a leading `?` like `?sv=...` too.
     pub sas_token: Option<String>,
     /// Specifies the object id associated with a user assigned managed service identity resource
     ///
-    /// The values of client_id and msi_res_id are discarded
+    /// Mutually exclusive with `client_id` and `msi_res_id`, loading via IMDS fails if
+    /// more than one of them is set.
     ///
     /// This is part of use AAD(Azure Active Directory) authenticate on Azure VM
     pub object_id: Option<String>,
     /// Specifies the application id (client id) associated with a user assigned managed service identity resource
     ///
-    /// The values of object_id and msi_res_id are discarded
+    /// Mutually exclusive with `object_id` and `msi_res_id`, loading via IMDS fails if
+    /// more than one of them is set.
     /// - cnv value: [`AZURE_CLIENT_ID`]
     ///
     /// This is part of use AAD(Azure Active Directory) authenticate on Azure VM
     pub client_id: Option<String>,
     /// Specifies the ARM resource id of the user assigned managed service identity resource
     ///
-    /// The values of object_id and client_id are discarded
+    /// Mutually exclusive with `object_id` and `client_id`, loading via IMDS fails if
+    /// more than one of them is set.
     ///
     /// This is part of use AAD(Azure Active Directory) authenticate on Azure VM
     pub msi_res_id: Option<String>,
@@ -66,6 +87,15 @@ pub struct Config {
     /// - env value: [`AZURE_AUTHORITY_HOST`]
     /// - profile config: `authority_host`
     pub authority_host: Option<String>,
+    /// `scope` of the tokens requested from AAD(Azure Active Directory).
+    ///
+    /// Default to `https://storage.azure.com/.default`. Set it to the scope
+    /// of other resources like `https://management.azure.com/.default` to
+    /// load tokens for non-storage endpoints.
+    ///
+    /// IMDS requests a `resource` instead of a `scope`, which is derived by
+    /// trimming the `.default` suffix.
+    pub scope: Option<String>,
 }
 
 pub const AZURE_FEDERATED_TOKEN_FILE: &str = "AZURE_FEDERATED_TOKEN_FILE";
@@ -75,12 +105,102 @@ pub const AZURE_AUTHORITY_HOST: &str = "AZURE_AUTHORITY_HOST";
 const AZBLOB_ENDPOINT: &str = "AZBLOB_ENDPOINT";
 const AZBLOB_ACCOUNT_KEY: &str = "AZBLOB_ACCOUNT_KEY";
 const AZBLOB_ACCOUNT_NAME: &str = "AZBLOB_ACCOUNT_NAME";
+const STORAGE_TOKEN_SCOPE: &str = "https://storage.azure.com/.default";
 const AZURE_PUBLIC_CLOUD: &str = "https://login.microsoftonline.com";
 
+/// Well known account of Azurite, see [Azurite](https://learn.microsoft.com/en-us/azure/storage/common/storage-use-azurite#connection-strings).
+const AZURITE_ACCOUNT_NAME: &str = "devstoreaccount1";
+const AZURITE_ACCOUNT_KEY: &str =
+    "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
 impl Config {
+    /// The scope of AAD tokens, default to the storage scope.
+    #[cfg_attr(feature = "no_network", allow(dead_code))]
+    pub(crate) fn token_scope(&self) -> &str {
+        self.scope.as_deref().unwrap_or(STORAGE_TOKEN_SCOPE)
+    }
+
+    /// The resource of IMDS tokens, derived from [`Config::token_scope`].
+    #[cfg_attr(feature = "no_network", allow(dead_code))]
+    pub(crate) fn token_resource(&self) -> &str {
+        let scope = self.token_scope();
+        scope.strip_suffix(".default").unwrap_or(scope)
+    }
+
+    /// The selector of user-assigned managed identity used by IMDS, like
+    /// `("client_id", "xxx")`.
+    ///
+    /// Returns `None` to use the system-assigned identity, and an error if
+    /// more than one of `object_id`, `client_id` and `msi_res_id` is set
+    /// since none of them wins.
+    pub(crate) fn managed_identity_selector(&self) -> Result<Option<(&'static str, &str)>> {
+        let selectors: Vec<_> = [
+            ("object_id", self.object_id.as_deref()),
+            ("client_id", self.client_id.as_deref()),
+            ("msi_res_id", self.msi_res_id.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(k, v)| v.map(|v| (k, v)))
+        .collect();
+
+        match selectors.as_slice() {
+            [] => Ok(None),
+            [selector] => Ok(Some(*selector)),
+            _ => Err(anyhow!(
+                "only one of object_id, client_id and msi_res_id can be set, but got {}",
+                selectors
+                    .iter()
+                    .map(|(k, _)| *k)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// Check this config for deprecated or ignored settings.
+    ///
+    /// The returned warnings are not fatal, but users should migrate before
+    /// those settings stop working.
+    pub fn validate_with_warnings(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+
+        if let Some(token) = &self.sas_token {
+            let version = form_urlencoded::parse(token.as_bytes())
+                .find(|(k, _)| k == "sv")
+                .map(|(_, v)| v.into_owned());
+            if let Some(version) = version.filter(|v| v.as_str() < "2015-02-21") {
+                warnings.push(Warning::new(
+                    "sas_token",
+                    format!("signed version `{version}` is being retired by Azure, regenerate the token with a newer version"),
+                ));
+            }
+        }
+
+        if let Err(err) = self.managed_identity_selector() {
+            warnings.push(Warning::new(
+                "object_id",
+                format!("{err}, loading via IMDS will fail"),
+            ));
+        }
+
+        warnings
+    }
+
+    /// Set the SAS token pasted with a leading `?` or as a full SAS url,
+    /// only the query part without leading or trailing `&` will be kept.
+    pub fn with_sas_token(mut self, token: &str) -> Self {
+        self.sas_token = Some(normalize_sas_token(token));
+        self
+    }
+
     /// Load config from env.
-    pub fn from_env(mut self) -> Self {
-        let envs = env::vars().collect::<HashMap<_, _>>();
+    pub fn from_env(self) -> Self {
+        self.from_env_with(&EnvSnapshot::from_env())
+    }
+
+    /// Load config from the given env snapshot.
+    pub fn from_env_with(mut self, env: &EnvSnapshot) -> Self {
+        let envs = env.envs();
 
         // federated_token can be loaded from both `AZURE_FEDERATED_TOKEN` and `AZURE_FEDERATED_TOKEN_FILE`.
         if let Some(v) = envs.get(AZURE_FEDERATED_TOKEN_FILE) {
@@ -115,4 +235,190 @@ impl Config {
 
         self
     }
+
+    /// Load config from the [connection string](https://learn.microsoft.com/en-us/azure/storage/common/storage-configure-connection-string).
+    ///
+    /// `AccountName`, `AccountKey` and `SharedAccessSignature` are supported.
+    /// `UseDevelopmentStorage=true` loads the well known Azurite account.
+    ///
+    /// Endpoints like `BlobEndpoint` are ignored: requests could be sent to
+    /// private endpoints or Azurite as is, signing is not affected since the
+    /// canonicalized resource always starts with the account name.
+    pub fn from_connection_string(mut self, conn: &str) -> Result<Self> {
+        let mut kvs = HashMap::new();
+        for kv in conn.split(';').map(str::trim).filter(|kv| !kv.is_empty()) {
+            let (k, v) = kv
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid connection string segment: {kv}"))?;
+            kvs.insert(k.trim(), v.trim());
+        }
+
+        if kvs
+            .get("UseDevelopmentStorage")
+            .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+        {
+            self.account_name = Some(AZURITE_ACCOUNT_NAME.to_string());
+            self.account_key = Some(AZURITE_ACCOUNT_KEY.to_string());
+            return Ok(self);
+        }
+
+        if let Some(v) = kvs.get("AccountName") {
+            self.account_name = Some(v.to_string());
+        }
+        if let Some(v) = kvs.get("AccountKey") {
+            self.account_key = Some(v.to_string());
+        }
+        if let Some(v) = kvs.get("SharedAccessSignature") {
+            self = self.with_sas_token(v);
+        }
+
+        if self.account_name.is_none() && self.sas_token.is_none() {
+            return Err(anyhow!(
+                "connection string must contain AccountName or SharedAccessSignature"
+            ));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Normalize the SAS token, see [`Config::with_sas_token`].
+fn normalize_sas_token(token: &str) -> String {
+    let token = token.trim();
+    // `?` is never used in SAS token unescaped.
+    let token = token.split_once('?').map_or(token, |(_, query)| query);
+    token.trim_matches('&').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case::test_case("sv=2021-01-01&sig=abc"; "plain")]
+    #[test_case::test_case("?sv=2021-01-01&sig=abc"; "leading question mark")]
+    #[test_case::test_case(" ?sv=2021-01-01&sig=abc& "; "surrounding whitespace")]
+    #[test_case::test_case("https://account.blob.core.windows.net/container?sv=2021-01-01&sig=abc"; "full url")]
+    fn test_with_sas_token(token: &str) {
+        let config = Config::default().with_sas_token(token);
+        assert_eq!(Some("sv=2021-01-01&sig=abc"), config.sas_token.as_deref());
+    }
+
+    #[test]
+    fn test_from_connection_string() -> Result<()> {
+        let config = Config::default().from_connection_string(
+            "DefaultEndpointsProtocol=https;AccountName=account;AccountKey=a2V5;EndpointSuffix=core.chinacloudapi.cn",
+        )?;
+        assert_eq!(Some("account"), config.account_name.as_deref());
+        assert_eq!(Some("a2V5"), config.account_key.as_deref());
+
+        let config = Config::default().from_connection_string(
+            "AccountName=account;AccountKey=a2V5;BlobEndpoint=https://account.privatelink.blob.core.windows.net/;",
+        )?;
+        assert_eq!(Some("account"), config.account_name.as_deref());
+
+        let config = Config::default().from_connection_string(
+            "BlobEndpoint=https://storage.example.com;SharedAccessSignature=sv=2021-01-01&sig=abc",
+        )?;
+        assert_eq!(None, config.account_name);
+        assert_eq!(Some("sv=2021-01-01&sig=abc"), config.sas_token.as_deref());
+
+        let config = Config::default().from_connection_string(
+            "BlobEndpoint=https://storage.example.com;SharedAccessSignature=?sv=2021-01-01&sig=abc",
+        )?;
+        assert_eq!(Some("sv=2021-01-01&sig=abc"), config.sas_token.as_deref());
+
+        let config = Config::default().from_connection_string("UseDevelopmentStorage=true")?;
+        assert_eq!(Some(AZURITE_ACCOUNT_NAME), config.account_name.as_deref());
+        assert_eq!(Some(AZURITE_ACCOUNT_KEY), config.account_key.as_deref());
+
+        assert!(Config::default()
+            .from_connection_string("AccountName")
+            .is_err());
+        assert!(Config::default()
+            .from_connection_string("BlobEndpoint=https://storage.example.com")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_env_with_concurrently() {
+        std::thread::scope(|s| {
+            let handles = ["account1", "account2"].map(|account| {
+                let env = EnvSnapshot::new().with_env(AZBLOB_ACCOUNT_NAME, account);
+                s.spawn(move || Config::default().from_env_with(&env))
+            });
+
+            for (account, handle) in ["account1", "account2"].into_iter().zip(handles) {
+                let config = handle.join().expect("thread must succeed");
+                assert_eq!(Some(account), config.account_name.as_deref());
+            }
+        });
+    }
+
+    #[test_case::test_case(Some("object"), None, None, Some(("object_id", "object")) ; "object_id")]
+    #[test_case::test_case(None, Some("client"), None, Some(("client_id", "client")) ; "client_id")]
+    #[test_case::test_case(None, None, Some("res"), Some(("msi_res_id", "res")) ; "msi_res_id")]
+    #[test_case::test_case(None, None, None, None ; "system assigned")]
+    fn test_managed_identity_selector(
+        object_id: Option<&str>,
+        client_id: Option<&str>,
+        msi_res_id: Option<&str>,
+        expected: Option<(&str, &str)>,
+    ) -> Result<()> {
+        let config = Config {
+            object_id: object_id.map(String::from),
+            client_id: client_id.map(String::from),
+            msi_res_id: msi_res_id.map(String::from),
+            ..Default::default()
+        };
+        assert_eq!(expected, config.managed_identity_selector()?);
+        Ok(())
+    }
+
+    #[test_case::test_case(Some("object"), Some("client"), None, "object_id, client_id" ; "object_id and client_id")]
+    #[test_case::test_case(None, Some("client"), Some("res"), "client_id, msi_res_id" ; "client_id and msi_res_id")]
+    #[test_case::test_case(Some("object"), Some("client"), Some("res"), "object_id, client_id, msi_res_id" ; "all")]
+    fn test_managed_identity_selector_conflict(
+        object_id: Option<&str>,
+        client_id: Option<&str>,
+        msi_res_id: Option<&str>,
+        expected: &str,
+    ) {
+        let config = Config {
+            object_id: object_id.map(String::from),
+            client_id: client_id.map(String::from),
+            msi_res_id: msi_res_id.map(String::from),
+            ..Default::default()
+        };
+        let err = config
+            .managed_identity_selector()
+            .expect_err("conflicting selectors must be rejected");
+        assert!(err.to_string().ends_with(expected), "{err}");
+    }
+
+    #[test]
+    fn test_validate_with_warnings() {
+        let config = Config {
+            client_id: Some("client".to_string()),
+            ..Default::default()
+        }
+        .with_sas_token("?sv=2021-01-01&sig=abc");
+        assert!(config.validate_with_warnings().is_empty());
+
+        let config = Config::default().with_sas_token("?sv=2014-02-14&sig=abc");
+        let warnings = config.validate_with_warnings();
+        assert_eq!(1, warnings.len());
+        assert_eq!("sas_token", warnings[0].field);
+        assert!(warnings[0].message.contains("`2014-02-14`"));
+
+        let config = Config {
+            object_id: Some("object".to_string()),
+            client_id: Some("client".to_string()),
+            ..Default::default()
+        };
+        let warnings = config.validate_with_warnings();
+        assert_eq!(1, warnings.len());
+        assert_eq!("object_id", warnings[0].field);
+    }
 }
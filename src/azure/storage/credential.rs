@@ -21,6 +21,15 @@ pub enum Credential {
     BearerToken(String, DateTime),
 }
 
+/// AccountKey indicates which account key is used by the shared key credential.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountKey {
+    /// The primary account key, a.k.a. `key1`.
+    Primary,
+    /// The secondary account key, a.k.a. `key2`.
+    Secondary,
+}
+
 impl Credential {
     /// is current cred is valid?
     pub fn is_valid(&self) -> bool {
@@ -1,5 +1,6 @@
 use std::str;
 
+use http::header::USER_AGENT;
 use http::HeaderValue;
 use http::Method;
 use http::Request;
@@ -8,6 +9,7 @@ use reqwest::Url;
 use serde::Deserialize;
 
 use super::config::Config;
+use crate::retry::RetryPolicy;
 
 const MSI_API_VERSION: &str = "2019-08-01";
 const MSI_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
@@ -15,21 +17,18 @@ const MSI_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/toke
 /// Gets an access token for the specified resource and configuration.
 ///
 /// See <https://learn.microsoft.com/en-us/azure/app-service/overview-managed-identity?tabs=portal,http#using-the-rest-protocol>
-pub async fn get_access_token(resource: &str, config: &Config) -> anyhow::Result<AccessToken> {
+pub async fn get_access_token(
+    resource: &str,
+    config: &Config,
+    user_agent: &str,
+    retry: &RetryPolicy,
+) -> anyhow::Result<AccessToken> {
     let endpoint = config.endpoint.as_deref().unwrap_or(MSI_ENDPOINT);
     let mut query_items = vec![("api-version", MSI_API_VERSION), ("resource", resource)];
 
-    match (
-        config.object_id.as_ref(),
-        config.client_id.as_ref(),
-        config.msi_res_id.as_ref(),
-    ) {
-        (Some(object_id), None, None) => query_items.push(("object_id", object_id)),
-        (None, Some(client_id), None) => query_items.push(("client_id", client_id)),
-        (None, None, Some(msi_res_id)) => query_items.push(("msi_res_id", msi_res_id)),
-        // Only one of the object_id, client_id, or msi_res_id can be specified, if you specify both, will ignore all.
-        _ => (),
-    };
+    if let Some(selector) = config.managed_identity_selector()? {
+        query_items.push(selector);
+    }
 
     let url = Url::parse_with_params(endpoint, &query_items)?;
     let mut req = Request::builder()
@@ -39,13 +38,15 @@ pub async fn get_access_token(resource: &str, config: &Config) -> anyhow::Result
 
     req.headers_mut()
         .insert("metadata", HeaderValue::from_static("true"));
+    req.headers_mut()
+        .insert(USER_AGENT, HeaderValue::from_str(user_agent)?);
 
     if let Some(secret) = &config.msi_secret {
         req.headers_mut()
             .insert("x-identity-header", HeaderValue::from_str(secret)?);
     };
 
-    let res = Client::new().execute(req.try_into()?).await?;
+    let res = retry.execute(&Client::new(), req.try_into()?).await?;
     let rsp_status = res.status();
     let rsp_body = res.text().await?;
 
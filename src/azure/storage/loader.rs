@@ -1,20 +1,41 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use anyhow::Result;
 
+use crate::constants::DEFAULT_USER_AGENT;
+use crate::metrics::Metrics;
+use crate::metrics::SharedMetrics;
+#[cfg(not(feature = "no_network"))]
+use crate::retry::RetryPolicy;
+#[cfg(not(feature = "no_network"))]
 use crate::time::{now, parse_rfc3339};
 
+use super::config::Config;
+use super::credential::AccountKey;
 use super::credential::Credential;
+#[cfg(not(feature = "no_network"))]
 use super::imds_credential;
-use super::{config::Config, workload_identity_credential};
+#[cfg(not(feature = "no_network"))]
+use super::workload_identity_credential;
 
 /// Loader will load credential from different methods.
+///
+/// With `no_network` feature enabled, only credentials from config will be
+/// loaded.
 #[cfg_attr(test, derive(Debug))]
 pub struct Loader {
     config: Config,
+    #[cfg_attr(feature = "no_network", allow(dead_code))]
+    user_agent: String,
 
+    use_secondary_key: Arc<AtomicBool>,
     credential: Arc<Mutex<Option<Credential>>>,
+    metrics: SharedMetrics,
+    #[cfg(not(feature = "no_network"))]
+    retry: RetryPolicy,
 }
 
 impl Loader {
@@ -22,8 +43,86 @@ impl Loader {
     pub fn new(config: Config) -> Self {
         Self {
             config,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
 
+            use_secondary_key: Arc::default(),
             credential: Arc::default(),
+            metrics: SharedMetrics::default(),
+            #[cfg(not(feature = "no_network"))]
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Set the `User-Agent` of requests sent to token endpoints.
+    ///
+    /// Default to `reqsign/{version}`.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
+
+    /// Specify the metrics to record cache hits and refreshes of credential.
+    ///
+    /// Default to [`NoopMetrics`](crate::NoopMetrics).
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics.into();
+        self
+    }
+
+    /// Specify the predicate to decide whether a response or a transport
+    /// error should be retried.
+    ///
+    /// By default, every request is sent only once. With a predicate set,
+    /// requests are attempted at most 3 times with exponential backoff while
+    /// the predicate returns `true`.
+    ///
+    /// The predicate is used by requests sent to IMDS and workload identity
+    /// token endpoints.
+    #[cfg(not(feature = "no_network"))]
+    pub fn with_retry_policy(
+        mut self,
+        predicate: impl Fn(Result<&reqwest::Response, &reqwest::Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry = RetryPolicy::new(predicate);
+        self
+    }
+
+    /// Switch to the secondary account key.
+    ///
+    /// Callers can use this to fail over after the primary key has been
+    /// rotated, for example, on receiving `AuthenticationFailed` from services.
+    ///
+    /// Returns `false` if secondary account key is not configured or already
+    /// in use.
+    pub fn failover_to_secondary_key(&self) -> bool {
+        if self.config.secondary_account_key.is_none() {
+            return false;
+        }
+        if self.use_secondary_key.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+
+        // Clean up the cached credential so that next load will pick the secondary key.
+        *self.credential.lock().expect("lock poisoned") = None;
+        true
+    }
+
+    /// Get the account key that will be used by shared key credential.
+    ///
+    /// Returns `None` if account key is not configured.
+    pub fn account_key_in_use(&self) -> Option<AccountKey> {
+        self.config.account_key.as_ref()?;
+
+        if self.use_secondary_key.load(Ordering::Relaxed) {
+            Some(AccountKey::Secondary)
+        } else {
+            Some(AccountKey::Primary)
         }
     }
 
@@ -31,10 +130,16 @@ impl Loader {
     pub async fn load(&self) -> Result<Option<Credential>> {
         // Return cached credential if it's valid.
         match self.credential.lock().expect("lock poisoned").clone() {
-            Some(cred) if cred.is_valid() => return Ok(Some(cred)),
+            Some(cred) if cred.is_valid() => {
+                self.metrics.credential_cache_hit("azblob");
+                return Ok(Some(cred));
+            }
             _ => (),
         }
-        let cred = self.load_inner().await?;
+        let cred = self.load_inner().await;
+        self.metrics
+            .credential_refreshed("azblob", matches!(cred, Ok(Some(_))));
+        let cred = cred?;
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         lock.clone_from(&cred);
@@ -47,6 +152,7 @@ impl Loader {
             return Ok(Some(cred));
         }
 
+        #[cfg(not(feature = "no_network"))]
         if let Some(cred) = self.load_via_workload_identity().await? {
             return Ok(Some(cred));
         }
@@ -54,7 +160,12 @@ impl Loader {
         // try to load credential using AAD(Azure Active Directory) authenticate on Azure VM
         // we may get an error if not running on Azure VM
         // see https://learn.microsoft.com/en-us/azure/app-service/overview-managed-identity?tabs=portal,http#using-the-rest-protocol
-        self.load_via_imds().await
+        #[cfg(not(feature = "no_network"))]
+        if let Some(cred) = self.load_via_imds().await? {
+            return Ok(Some(cred));
+        }
+
+        Ok(None)
     }
 
     async fn load_via_config(&self) -> Result<Option<Credential>> {
@@ -64,6 +175,13 @@ impl Loader {
         }
 
         if let (Some(ak), Some(sk)) = (&self.config.account_name, &self.config.account_key) {
+            let sk = match (
+                self.account_key_in_use(),
+                &self.config.secondary_account_key,
+            ) {
+                (Some(AccountKey::Secondary), Some(secondary)) => secondary,
+                _ => sk,
+            };
             let cred = Credential::SharedKey(ak.clone(), sk.clone());
             return Ok(Some(cred));
         }
@@ -71,11 +189,21 @@ impl Loader {
         Ok(None)
     }
 
+    #[cfg(not(feature = "no_network"))]
     async fn load_via_imds(&self) -> Result<Option<Credential>> {
-        let token =
-            imds_credential::get_access_token("https://storage.azure.com/", &self.config).await?;
+        let token = imds_credential::get_access_token(
+            self.config.token_resource(),
+            &self.config,
+            &self.user_agent,
+            &self.retry,
+        )
+        .await?;
         let expires_on = if token.expires_on.is_empty() {
             now() + chrono::TimeDelta::try_minutes(10).expect("in bounds")
+        } else if let Ok(secs) = token.expires_on.parse::<i64>() {
+            // IMDS returns expires_on as unix epoch seconds.
+            chrono::DateTime::from_timestamp(secs, 0)
+                .ok_or_else(|| anyhow::anyhow!("expires_on is out of range: {secs}"))?
         } else {
             parse_rfc3339(&token.expires_on)?
         };
@@ -84,9 +212,14 @@ impl Loader {
         Ok(cred)
     }
 
+    #[cfg(not(feature = "no_network"))]
     async fn load_via_workload_identity(&self) -> Result<Option<Credential>> {
-        let workload_identity_token =
-            workload_identity_credential::get_workload_identity_token(&self.config).await?;
+        let workload_identity_token = workload_identity_credential::get_workload_identity_token(
+            &self.config,
+            &self.user_agent,
+            &self.retry,
+        )
+        .await?;
         match workload_identity_token {
             Some(token) => {
                 let expires_on_duration = match token.expires_on {
@@ -102,3 +235,188 @@ impl Loader {
         }
     }
 }
+
+#[cfg(test)]
+#[cfg(not(feature = "no_network"))]
+mod tests {
+    use http::Method;
+
+    use super::*;
+    use crate::test_util::MockResponse;
+    use crate::test_util::MockServer;
+
+    #[test_case::test_case(None, "https://storage.azure.com/.default" ; "default")]
+    #[test_case::test_case(Some("https://management.azure.com/.default"), "https://management.azure.com/.default" ; "custom")]
+    #[tokio::test]
+    async fn test_load_via_workload_identity_with_scope(
+        scope: Option<&str>,
+        expected: &str,
+    ) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(
+                Method::POST,
+                "/tenant/oauth2/v2.0/token",
+                MockResponse::ok(r#"{"access_token":"access_token"}"#),
+            )
+            .start()
+            .await?;
+
+        let dir = tempfile::tempdir()?;
+        let token_file = dir.path().join("token");
+        std::fs::write(&token_file, "federated_token")?;
+
+        let loader = Loader::new(Config {
+            federated_token_file: Some(token_file.to_string_lossy().to_string()),
+            tenant_id: Some("tenant".to_string()),
+            client_id: Some("client".to_string()),
+            authority_host: Some(server.endpoint().to_string()),
+            scope: scope.map(String::from),
+            ..Default::default()
+        });
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert!(matches!(cred, Credential::BearerToken(token, _) if token == "access_token"));
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        let (_, actual) = form_urlencoded::parse(&requests[0].body)
+            .find(|(k, _)| k == "scope")
+            .expect("scope must exist");
+        assert_eq!(expected, actual);
+
+        Ok(())
+    }
+
+    #[test_case::test_case("" ; "without trailing slash")]
+    #[test_case::test_case("/" ; "with trailing slash")]
+    #[tokio::test]
+    async fn test_load_via_workload_identity_with_authority_host(suffix: &str) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(
+                Method::POST,
+                "/tenant/oauth2/v2.0/token",
+                MockResponse::ok(r#"{"access_token":"access_token"}"#),
+            )
+            .start()
+            .await?;
+
+        let dir = tempfile::tempdir()?;
+        let token_file = dir.path().join("token");
+        std::fs::write(&token_file, "federated_token")?;
+
+        let loader = Loader::new(Config {
+            federated_token_file: Some(token_file.to_string_lossy().to_string()),
+            tenant_id: Some("tenant".to_string()),
+            client_id: Some("client".to_string()),
+            authority_host: Some(format!("{}{suffix}", server.endpoint())),
+            ..Default::default()
+        });
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert!(matches!(cred, Credential::BearerToken(token, _) if token == "access_token"));
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!("/tenant/oauth2/v2.0/token", requests[0].uri.path());
+
+        Ok(())
+    }
+
+    #[test_case::test_case(None, "https://storage.azure.com/" ; "default")]
+    #[test_case::test_case(Some("https://management.azure.com/.default"), "https://management.azure.com/" ; "custom")]
+    #[tokio::test]
+    async fn test_load_via_imds_with_scope(scope: Option<&str>, expected: &str) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_azure_imds("access_token", 4070908800)
+            .start()
+            .await?;
+
+        let loader = Loader::new(Config {
+            endpoint: Some(format!(
+                "{}/metadata/identity/oauth2/token",
+                server.endpoint()
+            )),
+            scope: scope.map(String::from),
+            ..Default::default()
+        });
+        loader.load().await?.expect("credential must be loaded");
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        let query = requests[0].uri.query().expect("query must exist");
+        let (_, actual) = form_urlencoded::parse(query.as_bytes())
+            .find(|(k, _)| k == "resource")
+            .expect("resource must exist");
+        assert_eq!(expected, actual);
+
+        Ok(())
+    }
+
+    #[test_case::test_case(false, 1 ; "single attempt by default")]
+    #[test_case::test_case(true, 2 ; "retry by predicate")]
+    #[tokio::test]
+    async fn test_load_via_imds_with_retry_policy(
+        custom: bool,
+        expected_attempts: usize,
+    ) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(
+                Method::GET,
+                "/metadata/identity/oauth2/token",
+                MockResponse::new(http::StatusCode::TOO_MANY_REQUESTS, "try again"),
+            )
+            .with_azure_imds("access_token", 4070908800)
+            .start()
+            .await?;
+
+        let mut loader = Loader::new(Config {
+            endpoint: Some(format!(
+                "{}/metadata/identity/oauth2/token",
+                server.endpoint()
+            )),
+            ..Default::default()
+        });
+        if custom {
+            loader = loader.with_retry_policy(|result| {
+                result.is_ok_and(|resp| resp.status() == http::StatusCode::TOO_MANY_REQUESTS)
+            });
+        }
+
+        let cred = loader.load().await;
+        assert_eq!(expected_attempts, server.requests().len());
+        assert_eq!(custom, matches!(cred, Ok(Some(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_via_imds_with_conflicting_selectors() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_azure_imds("access_token", 4070908800)
+            .start()
+            .await?;
+
+        let loader = Loader::new(Config {
+            endpoint: Some(format!(
+                "{}/metadata/identity/oauth2/token",
+                server.endpoint()
+            )),
+            object_id: Some("object".to_string()),
+            msi_res_id: Some("res".to_string()),
+            ..Default::default()
+        });
+        assert!(loader.load().await.is_err());
+        // The request must not be sent with a randomly picked identity.
+        assert!(server.requests().is_empty());
+
+        Ok(())
+    }
+}
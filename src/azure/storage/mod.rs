@@ -10,14 +10,26 @@ pub use config::Config as AzureStorageConfig;
 
 mod credential;
 
+pub use credential::AccountKey as AzureStorageAccountKey;
 pub use credential::Credential as AzureStorageCredential;
 
+#[cfg(not(feature = "no_network"))]
 mod imds_credential;
 
+#[cfg(not(feature = "no_network"))]
 mod workload_identity_credential;
 
 mod loader;
 
 pub use loader::Loader as AzureStorageLoader;
 
+crate::client::signing_client!(
+    /// Signing client for Azure Storage, which loads credential by [`AzureStorageLoader`] and
+    /// signs requests by [`AzureStorageSigner`].
+    AzureStorageSigningClient,
+    "azblob",
+    AzureStorageLoader,
+    AzureStorageSigner
+);
+
 mod sas;
@@ -1,5 +1,6 @@
 use std::{fs, str};
 
+use http::header::USER_AGENT;
 use http::HeaderValue;
 use http::Method;
 use http::Request;
@@ -8,13 +9,17 @@ use reqwest::Url;
 use serde::Deserialize;
 
 use super::config::Config;
+use crate::retry::RetryPolicy;
 
 pub const API_VERSION: &str = "api-version";
-const STORAGE_TOKEN_SCOPE: &str = "https://storage.azure.com/.default";
 /// Gets an access token for the specified resource and configuration.
 ///
 /// See <https://learn.microsoft.com/en-us/azure/app-service/overview-managed-identity?tabs=portal,http#using-the-rest-protocol>
-pub async fn get_workload_identity_token(config: &Config) -> anyhow::Result<Option<LoginResponse>> {
+pub async fn get_workload_identity_token(
+    config: &Config,
+    user_agent: &str,
+    retry: &RetryPolicy,
+) -> anyhow::Result<Option<LoginResponse>> {
     let (token_file, tenant_id, client_id, authority_host) = match (
         &config.federated_token_file,
         &config.tenant_id,
@@ -28,11 +33,10 @@ pub async fn get_workload_identity_token(config: &Config) -> anyhow::Result<Opti
     };
 
     let token = fs::read_to_string(token_file)?;
-    let url = Url::parse(authority_host)?.join(&format!("/{tenant_id}/oauth2/v2.0/token"))?;
-    let scopes: &[&str] = &[STORAGE_TOKEN_SCOPE];
+    let url = token_url(authority_host, tenant_id)?;
     let encoded_body: String = form_urlencoded::Serializer::new(String::new())
         .append_pair("client_id", client_id)
-        .append_pair("scope", &scopes.join(" "))
+        .append_pair("scope", config.token_scope())
         .append_pair(
             "client_assertion_type",
             "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
@@ -52,8 +56,10 @@ pub async fn get_workload_identity_token(config: &Config) -> anyhow::Result<Opti
 
     req.headers_mut()
         .insert(API_VERSION, HeaderValue::from_static("2019-06-01"));
+    req.headers_mut()
+        .insert(USER_AGENT, HeaderValue::from_str(user_agent)?);
 
-    let res = Client::new().execute(req.try_into()?).await?;
+    let res = retry.execute(&Client::new(), req.try_into()?).await?;
     let rsp_status = res.status();
     let rsp_body = res.text().await?;
 
@@ -69,8 +75,40 @@ pub async fn get_workload_identity_token(config: &Config) -> anyhow::Result<Opti
     Ok(Some(resp))
 }
 
+/// Build the token endpoint `{authority_host}/{tenant_id}/oauth2/v2.0/token`.
+///
+/// `authority_host` is accepted with or without the trailing slash, and its
+/// path (if any) is kept.
+fn token_url(authority_host: &str, tenant_id: &str) -> anyhow::Result<Url> {
+    let authority_host = authority_host.trim_end_matches('/');
+    let tenant_id = tenant_id.trim_matches('/');
+    Ok(Url::parse(&format!(
+        "{authority_host}/{tenant_id}/oauth2/v2.0/token"
+    ))?)
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct LoginResponse {
     pub expires_on: Option<String>,
     pub access_token: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case::test_case("https://login.microsoftonline.com", "https://login.microsoftonline.com/tenant/oauth2/v2.0/token"; "without trailing slash")]
+    #[test_case::test_case("https://login.microsoftonline.com/", "https://login.microsoftonline.com/tenant/oauth2/v2.0/token"; "with trailing slash")]
+    #[test_case::test_case("https://login.microsoftonline.com//", "https://login.microsoftonline.com/tenant/oauth2/v2.0/token"; "with multiple trailing slashes")]
+    #[test_case::test_case("http://127.0.0.1:8080/aad", "http://127.0.0.1:8080/aad/tenant/oauth2/v2.0/token"; "with path")]
+    #[test_case::test_case("http://127.0.0.1:8080/aad/", "http://127.0.0.1:8080/aad/tenant/oauth2/v2.0/token"; "with path and trailing slash")]
+    fn test_token_url(authority_host: &str, expected: &str) -> anyhow::Result<()> {
+        assert_eq!(expected, token_url(authority_host, "tenant")?.as_str());
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_url_invalid() {
+        assert!(token_url("login.microsoftonline.com", "tenant").is_err());
+    }
+}
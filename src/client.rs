@@ -0,0 +1,236 @@
+//! High level clients that pair a loader and a signer.
+
+/// Generate a signing client that owns a loader and a signer.
+///
+/// The loader must provide `async fn load(&self) -> Result<Option<C>>` and
+/// the signer must provide `fn sign(&self, req, cred: &C) -> Result<()>`.
+/// `$service` is the service name reported to [`crate::Metrics`].
+#[allow(unused_macros)]
+macro_rules! signing_client {
+    ($(#[$meta:meta])* $name:ident, $service:literal, $loader:ty, $signer:ty) => {
+        $(#[$meta])*
+        ///
+        /// The credential is cached and refreshed by the loader, so it will
+        /// only be reloaded while it's going to expire.
+        pub struct $name {
+            loader: $loader,
+            signer: $signer,
+            metrics: $crate::metrics::SharedMetrics,
+        }
+
+        impl $name {
+            /// Create a new signing client with given loader and signer.
+            pub fn new(loader: $loader, signer: $signer) -> Self {
+                Self {
+                    loader,
+                    signer,
+                    metrics: Default::default(),
+                }
+            }
+
+            /// Specify the metrics to record credential loads and signs.
+            ///
+            /// Default to [`NoopMetrics`]($crate::NoopMetrics).
+            pub fn with_metrics(mut self, metrics: std::sync::Arc<dyn $crate::Metrics>) -> Self {
+                self.metrics = metrics.into();
+                self
+            }
+
+            /// Get the loader of this client.
+            pub fn loader(&self) -> &$loader {
+                &self.loader
+            }
+
+            /// Get the signer of this client.
+            pub fn signer(&self) -> &$signer {
+                &self.signer
+            }
+
+            /// Load credential and sign the request in one call.
+            pub async fn sign(
+                &self,
+                req: &mut impl $crate::request::SignableRequest,
+            ) -> anyhow::Result<()> {
+                let start = $crate::metrics::Stopwatch::start();
+                let cred = self.loader.load().await;
+                let success = matches!(cred, Ok(Some(_)));
+                self.metrics
+                    .credential_loaded($service, start.elapsed(), success);
+                let cred = cred?.ok_or_else(|| anyhow::anyhow!("credential not found"))?;
+
+                let start = $crate::metrics::Stopwatch::start();
+                let result = self.signer.sign(req, &cred);
+                self.metrics
+                    .request_signed($service, start.elapsed(), result.is_ok());
+                result
+            }
+        }
+    };
+}
+
+#[allow(unused_imports)]
+pub(crate) use signing_client;
+
+#[cfg(all(test, feature = "services-aws", not(feature = "no_network")))]
+mod tests {
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use http::header::AUTHORIZATION;
+    use reqwest::Client;
+
+    use crate::aws::constants::*;
+    use crate::test_util::MockServer;
+    use crate::AwsConfig;
+    use crate::AwsDefaultLoader;
+    use crate::AwsSigningClient;
+    use crate::AwsV4Signer;
+    use crate::Metrics;
+
+    /// Metrics that records all callbacks as `name:service:success`.
+    #[derive(Default)]
+    struct RecordingMetrics {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl RecordingMetrics {
+        fn record(&self, name: &str, service: &str, success: bool) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{name}:{service}:{success}"));
+        }
+    }
+
+    impl Metrics for RecordingMetrics {
+        fn credential_loaded(&self, service: &'static str, _: Duration, success: bool) {
+            self.record("loaded", service, success)
+        }
+
+        fn credential_cache_hit(&self, service: &'static str) {
+            self.record("cache_hit", service, true)
+        }
+
+        fn credential_refreshed(&self, service: &'static str, success: bool) {
+            self.record("refreshed", service, success)
+        }
+
+        fn request_signed(&self, service: &'static str, _: Duration, success: bool) {
+            self.record("signed", service, success)
+        }
+    }
+
+    /// Run `f` without aws credentials in env and shared files, so that only
+    /// the mocked imds could provide them.
+    fn with_isolated_env<F: Future<Output = Result<()>>>(f: impl FnOnce() -> F) -> Result<()> {
+        temp_env::with_vars(
+            [
+                (AWS_CONFIG_FILE, Some("/not/exist/config")),
+                (AWS_SHARED_CREDENTIALS_FILE, Some("/not/exist/credentials")),
+                (AWS_ACCESS_KEY_ID, None),
+                (AWS_SECRET_ACCESS_KEY, None),
+                (AWS_SESSION_TOKEN, None),
+                (AWS_PROFILE, None),
+                (AWS_ROLE_ARN, None),
+                (AWS_WEB_IDENTITY_TOKEN_FILE, None),
+                (AWS_CONTAINER_CREDENTIALS_RELATIVE_URI, None),
+                (AWS_CONTAINER_CREDENTIALS_FULL_URI, None),
+                (AWS_EC2_METADATA_DISABLED, None),
+            ],
+            || tokio::runtime::Runtime::new()?.block_on(f()),
+        )
+    }
+
+    #[test]
+    fn test_signing_client_reuses_credential() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        with_isolated_env(|| async {
+            let server = MockServer::builder()
+                .with_aws_imds(
+                    "test_role",
+                    "access_key_id",
+                    "secret_access_key",
+                    "session_token",
+                )
+                .start()
+                .await?;
+            let client = AwsSigningClient::new(
+                AwsDefaultLoader::new(Client::new(), AwsConfig::default())
+                    .with_ec2_metadata_endpoint(server.endpoint()),
+                AwsV4Signer::new("s3", "test"),
+            );
+
+            for _ in 0..2 {
+                let mut req = http::Request::get("https://test.s3.amazonaws.com/hello")
+                    .body("")
+                    .expect("request must be valid");
+                client.sign(&mut req).await?;
+
+                assert!(req.headers()[AUTHORIZATION]
+                    .to_str()?
+                    .starts_with("AWS4-HMAC-SHA256 Credential=access_key_id/"));
+                assert_eq!("session_token", req.headers()["x-amz-security-token"]);
+            }
+
+            // Credential has been loaded only once.
+            let loads = server
+                .requests()
+                .iter()
+                .filter(|v| v.uri.path() == "/latest/meta-data/iam/security-credentials/test_role")
+                .count();
+            assert_eq!(1, loads);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_signing_client_with_metrics() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        with_isolated_env(|| async {
+            let server = MockServer::builder()
+                .with_aws_imds(
+                    "test_role",
+                    "access_key_id",
+                    "secret_access_key",
+                    "session_token",
+                )
+                .start()
+                .await?;
+            let metrics = Arc::new(RecordingMetrics::default());
+            let client = AwsSigningClient::new(
+                AwsDefaultLoader::new(Client::new(), AwsConfig::default())
+                    .with_ec2_metadata_endpoint(server.endpoint())
+                    .with_metrics(metrics.clone()),
+                AwsV4Signer::new("s3", "test"),
+            )
+            .with_metrics(metrics.clone());
+
+            for _ in 0..2 {
+                let mut req = http::Request::get("https://test.s3.amazonaws.com/hello")
+                    .body("")
+                    .expect("request must be valid");
+                client.sign(&mut req).await?;
+            }
+
+            assert_eq!(
+                vec![
+                    "refreshed:aws:true",
+                    "loaded:aws:true",
+                    "signed:aws:true",
+                    "cache_hit:aws:true",
+                    "loaded:aws:true",
+                    "signed:aws:true",
+                ],
+                *metrics.events.lock().unwrap()
+            );
+
+            Ok(())
+        })
+    }
+}
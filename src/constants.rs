@@ -0,0 +1,17 @@
+//! Constants shared by all services.
+
+use std::time::Duration;
+
+/// The default `User-Agent` of all requests sent by reqsign itself, like
+/// requests to metadata services, STS and token endpoints.
+pub const DEFAULT_USER_AGENT: &str = concat!("reqsign/", env!("CARGO_PKG_VERSION"));
+
+/// Max expiration of AWS SigV4 presigned requests: 7 days.
+///
+/// Reference: [Authenticating Requests: Using Query Parameters](https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-query-string-auth.html)
+pub const AWS_QUERY_MAX_EXPIRES: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Max expiration of Google V4 signed URLs: 7 days.
+///
+/// Reference: [Signed URLs](https://cloud.google.com/storage/docs/access-control/signed-urls#example)
+pub const GOOGLE_QUERY_MAX_EXPIRES: Duration = Duration::from_secs(7 * 24 * 3600);
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::time::Duration;
 
+use anyhow::anyhow;
 use anyhow::Result;
 use http::header::HeaderName;
 use http::uri::Authority;
@@ -8,6 +9,7 @@ use http::uri::Scheme;
 use http::HeaderMap;
 use http::HeaderValue;
 use http::Method;
+use http::Uri;
 
 pub struct SigningContext {
     pub method: Method,
@@ -19,7 +21,52 @@ pub struct SigningContext {
 }
 
 impl SigningContext {
-    pub fn path_percent_decoded(&self) -> Cow<str> {
+    /// Build a signing context from request parts.
+    pub fn from_parts(method: &Method, uri: &Uri, headers: HeaderMap) -> Result<Self> {
+        Ok(SigningContext {
+            method: method.clone(),
+            scheme: uri.scheme().cloned().unwrap_or(Scheme::HTTP),
+            authority: uri
+                .authority()
+                .cloned()
+                .ok_or_else(|| anyhow!("request without authority is invalid for signing"))?,
+            path: match uri.path() {
+                "" => "/".to_string(),
+                v => v.to_string(),
+            },
+            query: uri
+                .query()
+                .map(|v| {
+                    form_urlencoded::parse(v.as_bytes())
+                        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            headers,
+        })
+    }
+
+    /// Get the value of `host` header to sign.
+    ///
+    /// The default port (`443` for https and `80` for http) is stripped, while
+    /// non-default ports are retained. Brackets of IPv6 literals are kept,
+    /// like `[::1]:9000`.
+    pub fn host(&self) -> &str {
+        let default_port = if self.scheme == Scheme::HTTPS {
+            443
+        } else if self.scheme == Scheme::HTTP {
+            80
+        } else {
+            return self.authority.as_str();
+        };
+
+        match self.authority.port_u16() {
+            Some(port) if port == default_port => self.authority.host(),
+            _ => self.authority.as_str(),
+        }
+    }
+
+    pub fn path_percent_decoded(&self) -> Cow<'_, str> {
         percent_encoding::percent_decode_str(&self.path).decode_utf8_lossy()
     }
 
@@ -112,11 +159,23 @@ impl SigningContext {
     #[inline]
     pub fn header_get_or_default(&self, key: &HeaderName) -> Result<&str> {
         match self.headers.get(key) {
-            Some(v) => Ok(v.to_str()?),
+            Some(v) => Self::header_value_to_str(key.as_str(), v),
             None => Ok(""),
         }
     }
 
+    /// Convert header value to str for canonicalization.
+    ///
+    /// Header values that contain non-ASCII bytes can't be canonicalized as-is,
+    /// we return an error instead of signing a request that services will reject.
+    /// Users should encode such values (for example, percent-encode the UTF-8
+    /// metadata) before signing.
+    pub fn header_value_to_str<'a>(name: &str, value: &'a HeaderValue) -> Result<&'a str> {
+        value.to_str().map_err(|_| {
+            anyhow!("header {name} contains non-ASCII value which can't be signed, please encode it before signing")
+        })
+    }
+
     pub fn header_value_normalize(v: &mut HeaderValue) {
         let bs = v.as_bytes();
 
@@ -140,17 +199,17 @@ impl SigningContext {
         h
     }
 
-    pub fn header_to_vec_with_prefix(&self, prefix: &str) -> Vec<(String, String)> {
+    pub fn header_to_vec_with_prefix(&self, prefix: &str) -> Result<Vec<(String, String)>> {
         self.headers
             .iter()
             // Filter all header that starts with prefix
             .filter(|(k, _)| k.as_str().starts_with(prefix))
             // Convert all header name to lowercase
             .map(|(k, v)| {
-                (
+                Ok((
                     k.as_str().to_lowercase(),
-                    v.to_str().expect("must be valid header").to_string(),
-                )
+                    Self::header_value_to_str(k.as_str(), v)?.to_string(),
+                ))
             })
             .collect()
     }
@@ -188,3 +247,38 @@ pub enum SigningMethod {
     /// Signing with query.
     Query(Duration),
 }
+
+impl SigningMethod {
+    /// Check the expiration of query signing against the max expiration
+    /// allowed by service, so that we can return an error instead of
+    /// producing requests that will be rejected by service.
+    pub fn check_expires(self, service: &str, max: Option<Duration>) -> Result<Self> {
+        if let (SigningMethod::Query(expire), Some(max)) = (self, max) {
+            if expire > max {
+                return Err(anyhow!(
+                    "expires {}s exceeds the max expiration {}s allowed by {service}",
+                    expire.as_secs(),
+                    max.as_secs()
+                ));
+            }
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case::test_case("http://127.0.0.1:9000/hello", "127.0.0.1:9000"; "ipv4 with port")]
+    #[test_case::test_case("http://[::1]:9000/hello", "[::1]:9000"; "ipv6 with port")]
+    #[test_case::test_case("http://[::1]/hello", "[::1]"; "ipv6 without port")]
+    #[test_case::test_case("http://[::1]:80/hello", "[::1]"; "ipv6 with http default port")]
+    #[test_case::test_case("https://[2001:db8::1]:443/hello", "[2001:db8::1]"; "ipv6 with https default port")]
+    #[test_case::test_case("https://[2001:db8::1]:80/hello", "[2001:db8::1]:80"; "ipv6 with http port over https")]
+    fn test_host(uri: &str, expected: &str) -> Result<()> {
+        let ctx = SigningContext::from_parts(&Method::GET, &uri.parse()?, HeaderMap::new())?;
+        assert_eq!(expected, ctx.host());
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! Dump canonical requests and strings to sign for debugging.
+//!
+//! Only available with `dump-canonical` feature enabled, signers won't
+//! pay anything for it otherwise.
+//!
+//! Only signers that build canonical requests are covered, that is AWS V4
+//! and Google V4. Other signers don't dump anything.
+
+use std::sync::Arc;
+use std::sync::RwLock;
+
+/// Sink to receive the canonical request and string to sign of every
+/// signed request.
+///
+/// Security tokens in the canonical request are redacted, so it no longer
+/// matches the hash in the string to sign if a token is present.
+///
+/// Closures like `|service: &str, canonical_request: &str, string_to_sign: &str| {}`
+/// can be used as sink too.
+pub trait CanonicalSink: Send + Sync + 'static {
+    /// Called after the string to sign is built and before it's signed.
+    fn dump(&self, service: &str, canonical_request: &str, string_to_sign: &str);
+}
+
+impl<F> CanonicalSink for F
+where
+    F: Fn(&str, &str, &str) + Send + Sync + 'static,
+{
+    fn dump(&self, service: &str, canonical_request: &str, string_to_sign: &str) {
+        self(service, canonical_request, string_to_sign)
+    }
+}
+
+static SINK: RwLock<Option<Arc<dyn CanonicalSink>>> = RwLock::new(None);
+
+/// Set the global sink of canonical dumps.
+///
+/// Dumps will be logged at `debug` level with target `reqsign::dump` if no
+/// sink is set.
+pub fn set_canonical_sink(sink: impl CanonicalSink) {
+    *SINK.write().expect("lock poisoned") = Some(Arc::new(sink));
+}
+
+/// Headers and query params whose values must not be dumped.
+const SENSITIVE_KEYS: &[&str] = &["x-amz-security-token"];
+
+const REDACTED: &str = "<redacted>";
+
+pub(crate) fn dump(service: &str, canonical_request: &str, string_to_sign: &str) {
+    let canonical_request = redact(canonical_request);
+    let sink = SINK.read().expect("lock poisoned").clone();
+    match sink {
+        Some(sink) => sink.dump(service, &canonical_request, string_to_sign),
+        None => log::debug!(
+            target: "reqsign::dump",
+            "{service} canonical request:\n{canonical_request}\nstring to sign:\n{string_to_sign}"
+        ),
+    }
+}
+
+/// Redact values of [`SENSITIVE_KEYS`] in canonical headers and query.
+///
+/// The canonical query is the third line, and canonical headers follow it
+/// in `name:value` form.
+fn redact(canonical_request: &str) -> String {
+    let is_sensitive = |k: &str| SENSITIVE_KEYS.iter().any(|v| k.eq_ignore_ascii_case(v));
+
+    canonical_request
+        .split('\n')
+        .enumerate()
+        .map(|(idx, line)| {
+            if idx == 2 {
+                return line
+                    .split('&')
+                    .map(|kv| match kv.split_once('=') {
+                        Some((k, _)) if is_sensitive(k) => format!("{k}={REDACTED}"),
+                        _ => kv.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("&");
+            }
+            match line.split_once(':') {
+                Some((k, _)) if idx > 2 && is_sensitive(k) => format!("{k}:{REDACTED}"),
+                _ => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Dump canonical request and string to sign, expands to nothing without
+/// `dump-canonical` feature.
+macro_rules! dump_canonical {
+    ($service:expr, $canonical_request:expr, $string_to_sign:expr) => {
+        #[cfg(feature = "dump-canonical")]
+        $crate::dump::dump($service, $canonical_request, $string_to_sign);
+    };
+}
+
+pub(crate) use dump_canonical;
@@ -0,0 +1,89 @@
+//! Snapshot of the environment used while loading configs.
+
+use std::collections::HashMap;
+use std::env;
+
+/// EnvSnapshot is a snapshot of the environment used by `Config::from_env_with`.
+///
+/// Reading env vars directly races with other threads (and tests) mutating
+/// the environment. Taking a snapshot once and passing it around makes
+/// config loading deterministic, and allows injecting envs in tests without
+/// touching the process environment.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSnapshot {
+    envs: HashMap<String, String>,
+}
+
+impl EnvSnapshot {
+    /// Create an empty snapshot without any env.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Snapshot the env vars of current process.
+    pub fn from_env() -> Self {
+        Self {
+            envs: env::vars().collect(),
+        }
+    }
+
+    /// Set an env var of this snapshot.
+    pub fn with_env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.envs.insert(key.into(), value.into());
+        self
+    }
+
+    /// Get the env var of this snapshot.
+    pub fn env_var(&self, key: &str) -> Option<&str> {
+        self.envs.get(key).map(String::as_str)
+    }
+
+    /// Get all env vars of this snapshot.
+    #[cfg_attr(
+        not(any(
+            feature = "services-aliyun",
+            feature = "services-aws",
+            feature = "services-azblob",
+            feature = "services-huaweicloud",
+            feature = "services-tencent"
+        )),
+        allow(dead_code)
+    )]
+    pub(crate) fn envs(&self) -> &HashMap<String, String> {
+        &self.envs
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for EnvSnapshot {
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self {
+            envs: iter
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_snapshot() {
+        let env = EnvSnapshot::new().with_env("KEY", "value");
+        assert_eq!(Some("value"), env.env_var("KEY"));
+        assert_eq!(None, env.env_var("NOT_EXIST"));
+
+        let env: EnvSnapshot = [("KEY", "value")].into_iter().collect();
+        assert_eq!(Some("value"), env.env_var("KEY"));
+
+        temp_env::with_var("REQSIGN_TEST_ENV_SNAPSHOT", Some("value"), || {
+            let env = EnvSnapshot::from_env();
+            // The snapshot is not affected by later changes.
+            temp_env::with_var("REQSIGN_TEST_ENV_SNAPSHOT", Some("changed"), || {
+                assert_eq!(Some("value"), env.env_var("REQSIGN_TEST_ENV_SNAPSHOT"));
+            });
+        });
+    }
+}
@@ -3,6 +3,11 @@ use percent_encoding::NON_ALPHANUMERIC;
 
 // Env values used in google services.
 pub const GOOGLE_APPLICATION_CREDENTIALS: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+/// Inline credential JSON (or base64 encoded JSON) instead of a file path.
+pub const GOOGLE_CREDENTIALS: &str = "GOOGLE_CREDENTIALS";
+
+/// The default token endpoint of Google OAuth2.
+pub const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
 
 /// AsciiSet for [Google UriEncode](https://cloud.google.com/storage/docs/authentication/canonical-requests)
 ///
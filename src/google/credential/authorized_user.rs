@@ -0,0 +1,14 @@
+//! An authorized user, usually created by `gcloud auth application-default login`.
+
+use super::default_token_uri;
+
+#[derive(Clone, serde::Deserialize)]
+#[cfg_attr(test, derive(Debug))]
+#[serde(rename_all = "snake_case")]
+pub struct AuthorizedUser {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
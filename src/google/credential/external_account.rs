@@ -116,7 +116,7 @@ mod credential_source {
     #[cfg_attr(test, derive(Debug))]
     #[serde(rename_all = "snake_case")]
     pub struct UrlSourcedCredentials {
-        /// The URL to where the POST request is made.
+        /// The URL to where the GET request is made.
         pub url: String,
         /// The headers to be injected in the request.
         #[serde(default)]
@@ -1,18 +1,36 @@
-//! An impersonated service account.
+//! An impersonated service account, usually created by
+//! `gcloud auth application-default login --impersonate-service-account`.
+
+use serde::Deserialize;
+use serde::Deserializer;
+
+use super::Credential;
 
 #[derive(Clone, serde::Deserialize)]
 #[cfg_attr(test, derive(Debug))]
 #[serde(rename_all = "snake_case")]
 pub struct ImpersonatedServiceAccount {
+    /// The chained service accounts to be delegated, could be empty.
+    #[serde(default)]
     pub delegates: Vec<String>,
     pub service_account_impersonation_url: String,
-    pub source_credentials: SourceCredentials,
+    /// The credential used to call the impersonation url, like an authorized
+    /// user or service account.
+    #[serde(deserialize_with = "deserialize_source_credentials")]
+    pub source_credentials: Box<Credential>,
 }
 
-#[derive(Clone, serde::Deserialize)]
-#[cfg_attr(test, derive(Debug))]
-pub struct SourceCredentials {
-    pub client_id: String,
-    pub client_secret: String,
-    pub refresh_token: String,
+fn deserialize_source_credentials<'de, D>(deserializer: D) -> Result<Box<Credential>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    let content = serde_json::to_vec(&value).map_err(serde::de::Error::custom)?;
+    let cred = Credential::from_slice(&content).map_err(serde::de::Error::custom)?;
+    if cred.service_account.is_none() && cred.authorized_user.is_none() {
+        return Err(serde::de::Error::custom(
+            "source_credentials must be an authorized user or service account",
+        ));
+    }
+    Ok(Box::new(cred))
 }
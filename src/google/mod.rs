@@ -2,15 +2,30 @@
 
 mod constants;
 
+// Credential fields are only consumed by token loaders.
+#[cfg_attr(feature = "no_network", allow(dead_code, unused_imports))]
 mod credential;
+#[cfg(not(feature = "no_network"))]
 pub(crate) use credential::external_account;
 pub use credential::Credential as GoogleCredential;
 pub use credential::CredentialLoader as GoogleCredentialLoader;
 
 mod token;
 pub use token::Token as GoogleToken;
+#[cfg(not(feature = "no_network"))]
 pub use token::TokenLoad as GoogleTokenLoad;
+#[cfg(not(feature = "no_network"))]
 pub use token::TokenLoader as GoogleTokenLoader;
 
 mod signer;
 pub use signer::Signer as GoogleSigner;
+
+#[cfg(not(feature = "no_network"))]
+crate::client::signing_client!(
+    /// Signing client for Google, which loads credential by [`GoogleTokenLoader`] and
+    /// signs requests by [`GoogleSigner`].
+    GoogleSigningClient,
+    "google",
+    GoogleTokenLoader,
+    GoogleSigner
+);
@@ -1,20 +1,52 @@
+#[cfg(not(feature = "no_network"))]
+mod authorized_user;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "no_network")))]
+mod disk_cache;
+#[cfg(not(feature = "no_network"))]
 mod external_account;
+#[cfg(not(feature = "no_network"))]
 mod impersonated_service_account;
+#[cfg(not(feature = "no_network"))]
 mod service_account;
 
 use std::fmt::Debug;
 use std::fmt::Formatter;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "no_network")))]
+use std::path::Path;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "no_network")))]
+use std::path::PathBuf;
+#[cfg(not(feature = "no_network"))]
 use std::sync::Arc;
+#[cfg(not(feature = "no_network"))]
 use std::sync::Mutex;
 
+#[cfg(not(feature = "no_network"))]
 use anyhow::Result;
+#[cfg(not(feature = "no_network"))]
 use async_trait::async_trait;
+#[cfg(not(feature = "no_network"))]
+use http::header::USER_AGENT;
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "no_network")))]
+use log::debug;
+#[cfg(not(feature = "no_network"))]
 use reqwest::Client;
 use serde::Deserialize;
+#[cfg(not(feature = "no_network"))]
 use serde::Serialize;
 
+#[cfg(not(feature = "no_network"))]
 use super::credential::Credential;
+#[cfg(not(feature = "no_network"))]
+use crate::constants::DEFAULT_USER_AGENT;
+#[cfg(not(feature = "no_network"))]
+use crate::metrics::Metrics;
+#[cfg(not(feature = "no_network"))]
+use crate::metrics::SharedMetrics;
+#[cfg(not(feature = "no_network"))]
+use crate::retry::RetryPolicy;
+#[cfg(not(feature = "no_network"))]
 use crate::time::now;
+#[cfg(not(feature = "no_network"))]
 use crate::time::DateTime;
 
 /// Token is the authentication methods used by google services.
@@ -48,6 +80,7 @@ impl Token {
     }
 
     /// Notes: don't allow get expires_in from reqsign.
+    #[cfg_attr(feature = "no_network", allow(dead_code))]
     pub(crate) fn expires_in(&self) -> usize {
         self.expires_in
     }
@@ -65,6 +98,7 @@ impl Debug for Token {
     }
 }
 
+#[cfg(not(feature = "no_network"))]
 /// Claims is used to build JWT for google cloud.
 ///
 /// ```json
@@ -85,20 +119,22 @@ pub struct Claims {
     iat: u64,
 }
 
+#[cfg(not(feature = "no_network"))]
 impl Claims {
-    pub fn new(client_email: &str, scope: &str) -> Claims {
+    pub fn new(client_email: &str, scope: &str, aud: &str) -> Claims {
         let current = now().timestamp() as u64;
 
         Claims {
             iss: client_email.to_string(),
             scope: scope.to_string(),
-            aud: "https://oauth2.googleapis.com/token".to_string(),
+            aud: aud.to_string(),
             exp: current + 3600,
             iat: current,
         }
     }
 }
 
+#[cfg(not(feature = "no_network"))]
 /// Loader trait will try to load credential from different sources.
 #[async_trait]
 pub trait TokenLoad: 'static + Send + Sync + Debug {
@@ -110,20 +146,29 @@ pub trait TokenLoad: 'static + Send + Sync + Debug {
     async fn load(&self, client: Client) -> Result<Option<Token>>;
 }
 
+#[cfg(not(feature = "no_network"))]
 /// TokenLoader will load token from different methods.
 #[cfg_attr(test, derive(Debug))]
 pub struct TokenLoader {
     scope: String,
     client: Client,
+    user_agent: String,
 
     credential: Option<Credential>,
     disable_vm_metadata: bool,
     service_account: Option<String>,
+    vm_metadata_endpoint: String,
+    token_uri: Option<String>,
     customed_token_loader: Option<Box<dyn TokenLoad>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    disk_cache_dir: Option<PathBuf>,
 
     token: Arc<Mutex<Option<(Token, DateTime)>>>,
+    metrics: SharedMetrics,
+    retry: RetryPolicy,
 }
 
+#[cfg(not(feature = "no_network"))]
 impl TokenLoader {
     /// Create a new token loader.
     ///
@@ -140,13 +185,20 @@ impl TokenLoader {
         Self {
             scope: scope.to_string(),
             client,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
 
             credential: None,
             disable_vm_metadata: false,
             service_account: None,
+            vm_metadata_endpoint: "http://metadata.google.internal".to_string(),
+            token_uri: None,
             customed_token_loader: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            disk_cache_dir: None,
 
             token: Arc::default(),
+            metrics: SharedMetrics::default(),
+            retry: RetryPolicy::default(),
         }
     }
 
@@ -168,12 +220,81 @@ impl TokenLoader {
         self
     }
 
+    /// Set the endpoint of vm metadata.
+    ///
+    /// Default to `http://metadata.google.internal`.
+    pub fn with_vm_metadata_endpoint(mut self, endpoint: &str) -> Self {
+        self.vm_metadata_endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set the token endpoint used to exchange tokens, mostly used with
+    /// emulators.
+    ///
+    /// Default to the `token_uri` of credential.
+    pub fn with_token_uri(mut self, token_uri: &str) -> Self {
+        self.token_uri = Some(token_uri.to_string());
+        self
+    }
+
+    /// Set the `User-Agent` of requests sent to token endpoints and vm metadata.
+    ///
+    /// Default to `reqsign/{version}`.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
+
+    /// Specify the metrics to record cache hits and refreshes of token.
+    ///
+    /// Default to [`NoopMetrics`](crate::NoopMetrics).
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics.into();
+        self
+    }
+
+    /// Specify the predicate to decide whether a response or a transport
+    /// error should be retried.
+    ///
+    /// By default, every request is sent only once. With a predicate set,
+    /// requests are attempted at most 3 times with exponential backoff while
+    /// the predicate returns `true`.
+    ///
+    /// The predicate is used by requests sent to token endpoints and vm
+    /// metadata.
+    pub fn with_retry_policy(
+        mut self,
+        predicate: impl Fn(Result<&reqwest::Response, &reqwest::Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry = RetryPolicy::new(predicate);
+        self
+    }
+
     /// Set the customed token loader for token loader.
     pub fn with_customed_token_loader(mut self, customed_token_loader: Box<dyn TokenLoad>) -> Self {
         self.customed_token_loader = Some(customed_token_loader);
         self
     }
 
+    /// Enable caching tokens on disk under the given directory.
+    ///
+    /// Tokens are keyed by the credential identity and scope, and will be
+    /// reused across processes until they expire, just like `gcloud` does.
+    /// The cache files are only readable by the current user.
+    ///
+    /// Disk cache is disabled by default.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_disk_cache(mut self, dir: impl AsRef<Path>) -> Self {
+        self.disk_cache_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
     /// Load token from different sources.
     pub async fn load(&self) -> Result<Option<Token>> {
         match self.token.lock().expect("lock poisoned").clone() {
@@ -181,12 +302,32 @@ impl TokenLoader {
                 if now()
                     < expire_in - chrono::TimeDelta::try_seconds(2 * 60).expect("in bounds") =>
             {
-                return Ok(Some(token))
+                self.metrics.credential_cache_hit("google");
+                return Ok(Some(token));
             }
             _ => (),
         }
 
-        let token = if let Some(token) = self.load_inner().await? {
+        #[cfg(not(target_arch = "wasm32"))]
+        let disk_cache_path = self.disk_cache_path();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &disk_cache_path {
+            match disk_cache::read(path) {
+                Ok(Some((token, expire_in))) => {
+                    let mut lock = self.token.lock().expect("lock poisoned");
+                    *lock = Some((token.clone(), expire_in));
+                    self.metrics.credential_cache_hit("google");
+                    return Ok(Some(token));
+                }
+                Ok(None) => (),
+                Err(err) => debug!("read token from disk cache failed: {err:?}"),
+            }
+        }
+
+        let token = self.load_inner().await;
+        self.metrics
+            .credential_refreshed("google", matches!(token, Ok(Some(_))));
+        let token = if let Some(token) = token? {
             token
         } else {
             return Ok(None);
@@ -195,6 +336,13 @@ impl TokenLoader {
         let expire_in =
             now() + chrono::TimeDelta::try_seconds(token.expires_in() as i64).expect("in bounds");
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &disk_cache_path {
+            if let Err(err) = disk_cache::write(path, &token, expire_in) {
+                debug!("write token into disk cache failed: {err:?}");
+            }
+        }
+
         let mut lock = self.token.lock().expect("lock poisoned");
         *lock = Some((token.clone(), expire_in));
 
@@ -214,6 +362,10 @@ impl TokenLoader {
             return Ok(Some(token));
         }
 
+        if let Some(token) = self.load_via_authorized_user().await? {
+            return Ok(Some(token));
+        }
+
         if let Some(token) = self.load_via_external_account().await? {
             return Ok(Some(token));
         }
@@ -225,6 +377,13 @@ impl TokenLoader {
         Ok(None)
     }
 
+    /// Resolve the token endpoint of credential.
+    ///
+    /// The endpoint set by [`TokenLoader::with_token_uri`] takes precedence.
+    fn token_uri(&self, uri: &str) -> String {
+        self.token_uri.clone().unwrap_or_else(|| uri.to_string())
+    }
+
     async fn load_via_customed_token_loader(&self) -> Result<Option<Token>> {
         match &self.customed_token_loader {
             Some(f) => f.load(self.client.clone()).await,
@@ -241,14 +400,17 @@ impl TokenLoader {
         // Use `default` if service account not set by user.
         let service_account = self.service_account.as_deref().unwrap_or("default");
 
-        let url = format!("http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/{service_account}/token?scopes={}", self.scope);
+        let url = format!(
+            "{}/computeMetadata/v1/instance/service-accounts/{service_account}/token?scopes={}",
+            self.vm_metadata_endpoint, self.scope
+        );
 
-        let resp = self
+        let req = self
             .client
             .get(&url)
-            .header("Metadata-Flavor", "Google")
-            .send()
-            .await?;
+            .header(USER_AGENT, &self.user_agent)
+            .header("Metadata-Flavor", "Google");
+        let resp = self.retry.send(req).await?;
 
         let token: Token = serde_json::from_slice(&resp.bytes().await?)?;
         Ok(Some(token))
@@ -0,0 +1,190 @@
+use anyhow::bail;
+use anyhow::Result;
+use http::header;
+use log::error;
+use serde::Deserialize;
+
+use super::Token;
+use super::TokenLoader;
+
+/// Error response of Google OAuth2 token endpoint.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ErrorResponse {
+    error: String,
+    error_description: String,
+}
+
+impl TokenLoader {
+    /// Exchange token via the refresh token of authorized user.
+    ///
+    /// The refresh token is kept in credential, so that a new access token
+    /// will be granted every time the cached one expires.
+    ///
+    /// Reference: [Refreshing an access token](https://developers.google.com/identity/protocols/oauth2/web-server#offline)
+    pub(super) async fn load_via_authorized_user(&self) -> Result<Option<Token>> {
+        let Some(cred) = self
+            .credential
+            .as_ref()
+            .and_then(|cred| cred.authorized_user.as_ref())
+        else {
+            return Ok(None);
+        };
+
+        let req = self
+            .client
+            .post(self.token_uri(&cred.token_uri))
+            .header(header::USER_AGENT, &self.user_agent)
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &cred.refresh_token),
+                ("client_id", &cred.client_id),
+                ("client_secret", &cred.client_secret),
+            ]);
+        let resp = self.retry.send(req).await?;
+
+        if !resp.status().is_success() {
+            error!("refresh token for authorized user got unexpected response: {resp:?}");
+            let content = resp.bytes().await?;
+            let err: ErrorResponse = serde_json::from_slice(&content).unwrap_or_default();
+            if err.error == "invalid_grant" {
+                bail!(
+                    "refresh token for authorized user has been expired or revoked: {}, please re-authenticate via `gcloud auth application-default login`",
+                    err.error_description
+                );
+            }
+            bail!(
+                "refresh token for authorized user failed: {}",
+                String::from_utf8_lossy(&content)
+            );
+        }
+
+        let token = serde_json::from_slice(&resp.bytes().await?)?;
+        Ok(Some(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+    use http::StatusCode;
+    use reqwest::Client;
+
+    use super::*;
+    use crate::google::credential::Credential;
+    use crate::test_util::MockResponse;
+    use crate::test_util::MockServer;
+
+    fn credential(token_uri: &str) -> Credential {
+        Credential::from_slice(
+            serde_json::json!({
+                "type": "authorized_user",
+                "client_id": "client_id",
+                "client_secret": "client_secret",
+                "refresh_token": "refresh_token",
+                "token_uri": token_uri,
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .expect("credential must be valid")
+    }
+
+    #[tokio::test]
+    async fn test_load_via_authorized_user() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        // Tokens expire within the refresh buffer, so every load will refresh.
+        let server = MockServer::builder()
+            .with_responses(
+                Method::POST,
+                "/token",
+                [
+                    MockResponse::ok(r#"{"access_token":"token_1","expires_in":60}"#),
+                    MockResponse::ok(r#"{"access_token":"token_2","expires_in":60}"#),
+                ],
+            )
+            .start()
+            .await?;
+        let loader = TokenLoader::new("scope", Client::new())
+            .with_credentials(credential(&format!("{}/token", server.endpoint())))
+            .with_disable_vm_metadata(true);
+
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("token_1", token.access_token());
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("token_2", token.access_token());
+
+        let requests = server.requests();
+        assert_eq!(2, requests.len());
+        for req in requests {
+            let form: Vec<(String, String)> =
+                form_urlencoded::parse(&req.body).into_owned().collect();
+            assert!(form.contains(&("grant_type".to_string(), "refresh_token".to_string())));
+            assert!(form.contains(&("refresh_token".to_string(), "refresh_token".to_string())));
+        }
+
+        Ok(())
+    }
+
+    #[test_case::test_case(false, 1 ; "single attempt by default")]
+    #[test_case::test_case(true, 2 ; "retry by predicate")]
+    #[tokio::test]
+    async fn test_load_via_authorized_user_with_retry_policy(
+        custom: bool,
+        expected_attempts: usize,
+    ) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_responses(
+                Method::POST,
+                "/token",
+                [
+                    MockResponse::new(StatusCode::SERVICE_UNAVAILABLE, "try again"),
+                    MockResponse::ok(r#"{"access_token":"token","expires_in":3600}"#),
+                ],
+            )
+            .start()
+            .await?;
+        let mut loader = TokenLoader::new("scope", Client::new())
+            .with_credentials(credential(&format!("{}/token", server.endpoint())))
+            .with_disable_vm_metadata(true);
+        if custom {
+            loader = loader.with_retry_policy(|result| {
+                result.is_ok_and(|resp| resp.status() == StatusCode::SERVICE_UNAVAILABLE)
+            });
+        }
+
+        let token = loader.load().await;
+        assert_eq!(expected_attempts, server.requests().len());
+        assert_eq!(custom, token.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_via_authorized_user_revoked() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(
+                Method::POST,
+                "/token",
+                MockResponse::new(
+                    StatusCode::BAD_REQUEST,
+                    r#"{"error":"invalid_grant","error_description":"Token has been expired or revoked."}"#,
+                ),
+            )
+            .start()
+            .await?;
+        let loader = TokenLoader::new("scope", Client::new())
+            .with_credentials(credential(&format!("{}/token", server.endpoint())))
+            .with_disable_vm_metadata(true);
+
+        let err = loader.load().await.expect_err("revoked token must fail");
+        assert!(err.to_string().contains("re-authenticate"), "{err}");
+
+        Ok(())
+    }
+}
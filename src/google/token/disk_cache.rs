@@ -0,0 +1,216 @@
+//! Cache google tokens on disk so that they can be reused across processes.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::debug;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::Token;
+use super::TokenLoader;
+use crate::hash::hex_sha256;
+use crate::time::format_rfc3339;
+use crate::time::now;
+use crate::time::parse_rfc3339;
+use crate::time::DateTime;
+
+/// The token stored on disk.
+///
+/// Unlike [`Token`], we store the absolute expire time here.
+#[derive(Serialize, Deserialize)]
+struct CachedToken {
+    access_token: String,
+    scope: String,
+    expires_at: String,
+}
+
+impl TokenLoader {
+    /// Build the cache file path for current token loader.
+    ///
+    /// Return `None` if disk cache is disabled or we can't decide the identity
+    /// of the token.
+    pub(super) fn disk_cache_path(&self) -> Option<PathBuf> {
+        let dir = self.disk_cache_dir.as_ref()?;
+
+        // Tokens returned by customed token loader have unknown identity.
+        if self.customed_token_loader.is_some() {
+            return None;
+        }
+
+        let identity = match &self.credential {
+            Some(cred) if cred.service_account.is_some() => {
+                format!(
+                    "service_account:{}",
+                    cred.service_account.as_ref()?.client_email
+                )
+            }
+            Some(cred) if cred.impersonated_service_account.is_some() => format!(
+                "impersonated_service_account:{}",
+                cred.impersonated_service_account
+                    .as_ref()?
+                    .service_account_impersonation_url
+            ),
+            Some(cred) if cred.authorized_user.is_some() => {
+                let user = cred.authorized_user.as_ref()?;
+                format!("authorized_user:{}:{}", user.client_id, user.refresh_token)
+            }
+            Some(cred) if cred.external_account.is_some() => {
+                format!(
+                    "external_account:{}",
+                    cred.external_account.as_ref()?.audience
+                )
+            }
+            _ if !self.disable_vm_metadata => format!(
+                "vm_metadata:{}",
+                self.service_account.as_deref().unwrap_or("default")
+            ),
+            _ => return None,
+        };
+
+        let key = hex_sha256(format!("{identity}\n{}", self.scope).as_bytes());
+        Some(dir.join(format!("{key}.json")))
+    }
+}
+
+/// Read token from disk cache.
+///
+/// Return `Ok(None)` if the cache doesn't exist or has been expired.
+pub(super) fn read(path: &Path) -> Result<Option<(Token, DateTime)>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let cached: CachedToken = serde_json::from_slice(&content)?;
+    let expires_at = parse_rfc3339(&cached.expires_at)?;
+
+    // Take 120s as buffer to avoid edge cases.
+    let remaining = expires_at - now() - chrono::TimeDelta::try_minutes(2).expect("in bounds");
+    if remaining <= chrono::TimeDelta::zero() {
+        debug!("cached token at {path:?} has been expired");
+        return Ok(None);
+    }
+
+    let token = Token::new(
+        &cached.access_token,
+        (expires_at - now()).num_seconds() as usize,
+        &cached.scope,
+    );
+    Ok(Some((token, expires_at)))
+}
+
+/// Write token into disk cache.
+///
+/// The cache file is only readable and writable by current user.
+pub(super) fn write(path: &Path, token: &Token, expires_at: DateTime) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let content = serde_json::to_vec(&CachedToken {
+        access_token: token.access_token().to_string(),
+        scope: token.scope.clone(),
+        expires_at: format_rfc3339(expires_at),
+    })?;
+
+    // Write into a temp file first to make sure readers never see a partial token.
+    let tmp_path = path.with_extension("json.tmp");
+    let mut opts = fs::OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut f = opts.open(&tmp_path)?;
+    f.write_all(&content)?;
+    f.sync_all()?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use reqwest::Client;
+
+    use super::*;
+
+    #[test]
+    fn test_disk_cache_write_and_read() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("token.json");
+
+        let token = Token::new("access_token", 3600, "scope");
+        let expires_at = now() + chrono::TimeDelta::try_seconds(3600).expect("in bounds");
+        write(&path, &token, expires_at)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path)?.permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+
+        let (cached, cached_expires_at) = read(&path)?.expect("cached token must exist");
+        assert_eq!("access_token", cached.access_token());
+        assert_eq!("scope", cached.scope);
+        // RFC3339 drops the sub-second part.
+        assert_eq!(expires_at.timestamp(), cached_expires_at.timestamp());
+        Ok(())
+    }
+
+    #[test]
+    fn test_disk_cache_expired() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("token.json");
+
+        let token = Token::new("access_token", 60, "scope");
+        let expires_at = now() + chrono::TimeDelta::try_seconds(60).expect("in bounds");
+        write(&path, &token, expires_at)?;
+
+        assert!(read(&path)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_disk_cache_not_found() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert!(read(&dir.path().join("not_exist.json"))?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_token_loader_reuse_disk_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let loader = TokenLoader::new("scope", Client::new()).with_disk_cache(dir.path());
+        let path = loader.disk_cache_path().expect("cache path must exist");
+        let expires_at = now() + chrono::TimeDelta::try_seconds(3600).expect("in bounds");
+        write(
+            &path,
+            &Token::new("cached_token", 3600, "scope"),
+            expires_at,
+        )?;
+
+        // A fresh loader with the same identity and scope will reuse the cached token
+        // without touching the vm metadata.
+        let loader = TokenLoader::new("scope", Client::new()).with_disk_cache(dir.path());
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("cached_token", token.access_token());
+
+        // Different scopes must not share the cache.
+        let loader = TokenLoader::new("another_scope", Client::new()).with_disk_cache(dir.path());
+        assert_ne!(
+            path,
+            loader.disk_cache_path().expect("cache path must exist")
+        );
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use anyhow::bail;
 use anyhow::Result;
 use http::header::ACCEPT;
 use http::header::CONTENT_TYPE;
+use http::header::USER_AGENT;
 use log::error;
 use serde::Deserialize;
 
@@ -11,6 +12,7 @@ use super::Token;
 use super::TokenLoader;
 use crate::google::credential::external_account::CredentialSource;
 use crate::google::credential::ExternalAccount;
+use crate::retry::RetryPolicy;
 
 /// The maximum impersonated token lifetime allowed, 1 hour.
 const MAX_LIFETIME: Duration = Duration::from_secs(3600);
@@ -28,6 +30,8 @@ async fn load_security_token(
     cred: &ExternalAccount,
     oidc_token: &str,
     client: &reqwest::Client,
+    user_agent: &str,
+    retry: &RetryPolicy,
 ) -> Result<Token> {
     // As documented in https://cloud.google.com/iam/docs/reference/sts/rest/v1/TopLevel/token.
     let req = serde_json::json!({
@@ -42,13 +46,13 @@ async fn load_security_token(
 
     let req = serde_json::to_vec(&req)?;
 
-    let resp = client
+    let req = client
         .post(&cred.token_url)
+        .header(USER_AGENT, user_agent)
         .header(ACCEPT, "application/json")
         .header(CONTENT_TYPE, "application/json")
-        .body(req)
-        .send()
-        .await?;
+        .body(req);
+    let resp = retry.send(req).await?;
 
     if !resp.status().is_success() {
         error!("exchange token got unexpected response: {:?}", resp);
@@ -64,6 +68,8 @@ async fn load_impersonated_token(
     access_token: &str,
     scope: &str,
     client: &reqwest::Client,
+    user_agent: &str,
+    retry: &RetryPolicy,
 ) -> Result<Option<Token>> {
     let Some(url) = &cred.service_account_impersonation_url else {
         return Ok(None);
@@ -82,14 +88,14 @@ async fn load_impersonated_token(
 
     let req = serde_json::to_vec(&req)?;
 
-    let resp = client
+    let req = client
         .post(url)
+        .header(USER_AGENT, user_agent)
         .header(ACCEPT, "application/json")
         .header(CONTENT_TYPE, "application/json")
         .bearer_auth(access_token)
-        .body(req)
-        .send()
-        .await?;
+        .body(req);
+    let resp = retry.send(req).await?;
 
     if !resp.status().is_success() {
         error!("impersonated token got unexpected response: {:?}", resp);
@@ -113,13 +119,32 @@ impl TokenLoader {
             return Ok(None);
         };
 
-        let oidc_token =
-            credential_source::load_oidc_token(&cred.credential_source, &self.client).await?;
+        let oidc_token = credential_source::load_oidc_token(
+            &cred.credential_source,
+            &self.client,
+            &self.user_agent,
+            &self.retry,
+        )
+        .await?;
 
-        let sts = load_security_token(cred, &oidc_token, &self.client).await?;
-        let token = load_impersonated_token(cred, sts.access_token(), &self.scope, &self.client)
-            .await?
-            .unwrap_or(sts);
+        let sts = load_security_token(
+            cred,
+            &oidc_token,
+            &self.client,
+            &self.user_agent,
+            &self.retry,
+        )
+        .await?;
+        let token = load_impersonated_token(
+            cred,
+            sts.access_token(),
+            &self.scope,
+            &self.client,
+            &self.user_agent,
+            &self.retry,
+        )
+        .await?
+        .unwrap_or(sts);
 
         Ok(Some(token))
     }
@@ -139,11 +164,13 @@ mod credential_source {
     pub(super) async fn load_oidc_token(
         source: &CredentialSource,
         client: &reqwest::Client,
+        user_agent: &str,
+        retry: &RetryPolicy,
     ) -> Result<String> {
         match source {
             CredentialSource::FileSourced(source) => load_file_sourced_oidc_token(source),
             CredentialSource::UrlSourced(source) => {
-                load_url_sourced_oidc_token(source, client).await
+                load_url_sourced_oidc_token(source, client, user_agent, retry).await
             }
         }
     }
@@ -151,14 +178,20 @@ mod credential_source {
     async fn load_url_sourced_oidc_token(
         source: &UrlSourcedCredentials,
         client: &reqwest::Client,
+        user_agent: &str,
+        retry: &RetryPolicy,
     ) -> Result<String> {
-        let headers: HeaderMap = source
+        let mut headers: HeaderMap = source
             .headers
             .iter()
             .map(|(key, value)| Ok((HeaderName::try_from(key)?, HeaderValue::try_from(value)?)))
             .collect::<Result<_>>()?;
+        // Allow users to override the user agent in credential source.
+        if !headers.contains_key(USER_AGENT) {
+            headers.insert(USER_AGENT, HeaderValue::from_str(user_agent)?);
+        }
 
-        let resp = client.get(&source.url).headers(headers).send().await?;
+        let resp = retry.send(client.get(&source.url).headers(headers)).await?;
         if !resp.status().is_success() {
             error!("exchange token got unexpected response: {:?}", resp);
             bail!("exchange OIDC token failed: {}", resp.text().await?);
@@ -176,3 +209,101 @@ mod credential_source {
         source.format.parse(&buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+    use reqwest::Client;
+
+    use super::*;
+    use crate::google::credential::Credential;
+    use crate::test_util::MockResponse;
+    use crate::test_util::MockServer;
+
+    #[test_case::test_case(serde_json::json!({"type": "text"}), "subject_token" ; "text")]
+    #[test_case::test_case(serde_json::json!({"type": "json", "subject_token_field_name": "id_token"}), r#"{"id_token":"subject_token"}"# ; "json")]
+    #[tokio::test]
+    async fn test_load_via_url_sourced_external_account(
+        format: serde_json::Value,
+        subject: &str,
+    ) -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(Method::GET, "/subject", MockResponse::ok(subject))
+            .with_response(
+                Method::POST,
+                "/sts",
+                MockResponse::ok(r#"{"access_token":"sts_token","expires_in":3600}"#),
+            )
+            .start()
+            .await?;
+
+        let cred = serde_json::json!({
+            "type": "external_account",
+            "audience": "//iam.googleapis.com/projects/000000000000/locations/global/workloadIdentityPools/reqsign/providers/reqsign-provider",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": format!("{}/sts", server.endpoint()),
+            "credential_source": {
+                "url": format!("{}/subject", server.endpoint()),
+                "headers": {"Metadata": "True"},
+                "format": format,
+            },
+        });
+        let loader = TokenLoader::new("scope", Client::new())
+            .with_credentials(Credential::from_slice(cred.to_string().as_bytes())?)
+            .with_disable_vm_metadata(true);
+
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("sts_token", token.access_token());
+
+        let requests = server.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!("/subject", requests[0].uri.path());
+        assert_eq!("True", requests[0].headers["metadata"]);
+        assert_eq!("/sts", requests[1].uri.path());
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body)?;
+        assert_eq!("subject_token", body["subjectToken"]);
+        assert_eq!(
+            "urn:ietf:params:oauth:token-type:jwt",
+            body["subjectTokenType"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_via_url_sourced_external_account_without_field() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = MockServer::builder()
+            .with_response(
+                Method::GET,
+                "/subject",
+                MockResponse::ok(r#"{"access_token":"subject_token"}"#),
+            )
+            .start()
+            .await?;
+
+        let cred = serde_json::json!({
+            "type": "external_account",
+            "audience": "audience",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+            "token_url": format!("{}/sts", server.endpoint()),
+            "credential_source": {
+                "url": format!("{}/subject", server.endpoint()),
+                "format": {"type": "json", "subject_token_field_name": "id_token"},
+            },
+        });
+        let loader = TokenLoader::new("scope", Client::new())
+            .with_credentials(Credential::from_slice(cred.to_string().as_bytes())?)
+            .with_disable_vm_metadata(true);
+
+        let err = loader.load().await.expect_err("missing field must fail");
+        assert!(format!("{err:?}").contains("id_token"), "{err:?}");
+        // The token must not be exchanged.
+        assert_eq!(1, server.requests().len());
+
+        Ok(())
+    }
+}
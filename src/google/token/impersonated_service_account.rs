@@ -1,15 +1,18 @@
 use std::time::Duration;
 
+use anyhow::anyhow;
 use anyhow::bail;
 use anyhow::Result;
 use http::header::CONTENT_TYPE;
+use http::header::USER_AGENT;
 use log::error;
 use serde::Deserialize;
 
-use crate::google::credential::impersonated_service_account::ImpersonatedServiceAccount;
-
 use super::Token;
 use super::TokenLoader;
+use crate::google::credential::impersonated_service_account::ImpersonatedServiceAccount;
+use crate::time::now;
+use crate::time::parse_rfc3339;
 
 #[derive(Clone, Deserialize, Default)]
 #[cfg_attr(test, derive(Debug))]
@@ -22,7 +25,17 @@ struct ImpersonatedToken {
 /// The maximum impersonated token lifetime allowed, 1 hour.
 const MAX_LIFETIME: Duration = Duration::from_secs(3600);
 
+/// The scope required by source credential to call IAM credentials API.
+const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
 impl TokenLoader {
+    /// Exchange token via impersonated service account.
+    ///
+    /// The source credential will be used to authenticate first, and then
+    /// the impersonation url will be called to generate the access token
+    /// of the target service account via `delegates`.
+    ///
+    /// Reference: [Create a short-lived access token](https://cloud.google.com/iam/docs/create-short-lived-credentials-direct#sa-credentials-oauth)
     pub(super) async fn load_via_impersonated_service_account(&self) -> Result<Option<Token>> {
         let Some(cred) = self
             .credential
@@ -32,65 +45,50 @@ impl TokenLoader {
             return Ok(None);
         };
 
-        let bearer_auth_token = self.generate_bearer_auth_token(cred).await?;
-        self.generate_access_token(cred, bearer_auth_token)
+        let source_token = self.generate_source_token(cred).await?;
+        self.generate_access_token(cred, source_token)
             .await
             .map(Some)
     }
 
-    async fn generate_bearer_auth_token(&self, cred: &ImpersonatedServiceAccount) -> Result<Token> {
-        let req = serde_json::json!({
-            "grant_type": "refresh_token",
-            "refresh_token": &cred.source_credentials.refresh_token,
-            "client_id": &cred.source_credentials.client_id,
-            "client_secret": &cred.source_credentials.client_secret,
-        });
-
-        let req = serde_json::to_vec(&req)?;
-
-        let resp = self
-            .client
-            .post("https://oauth2.googleapis.com/token")
-            .header(CONTENT_TYPE, "application/json")
-            .body(req)
-            .send()
-            .await?;
-
-        if !resp.status().is_success() {
-            error!("bearer token loader for impersonated service account got unexpected response: {:?}", resp);
-            bail!(
-                "bearer token loader for impersonated service account failed: {}",
-                resp.text().await?
-            );
+    /// Authenticate with the source credential.
+    async fn generate_source_token(&self, cred: &ImpersonatedServiceAccount) -> Result<Token> {
+        let mut loader = TokenLoader::new(CLOUD_PLATFORM_SCOPE, self.client.clone())
+            .with_credentials(cred.source_credentials.as_ref().clone())
+            .with_disable_vm_metadata(true)
+            .with_user_agent(&self.user_agent);
+        loader.retry = self.retry.clone();
+
+        // Source credential is either a service account or an authorized user.
+        if let Some(token) = loader.load_via_service_account().await? {
+            return Ok(token);
         }
-
-        let token: Option<Token> = serde_json::from_slice(&resp.bytes().await?)?;
-        let token = token.expect("couldn't parse bearer token response");
-
-        Ok(token)
+        loader.load_via_authorized_user().await?.ok_or_else(|| {
+            anyhow!("source credential of impersonated service account can't be loaded")
+        })
     }
 
     async fn generate_access_token(
         &self,
         cred: &ImpersonatedServiceAccount,
-        temp_token: Token,
+        source_token: Token,
     ) -> Result<Token> {
         let req = serde_json::json!({
             "lifetime": format!("{}s", MAX_LIFETIME.as_secs()),
-            "scope": &temp_token.scope.split(' ').collect::<Vec<&str>>(),
+            "scope": self.scope.split_whitespace().collect::<Vec<&str>>(),
             "delegates": &cred.delegates,
         });
 
         let req = serde_json::to_vec(&req)?;
 
-        let resp = self
+        let req = self
             .client
             .post(&cred.service_account_impersonation_url)
+            .header(USER_AGENT, &self.user_agent)
             .header(CONTENT_TYPE, "application/json")
-            .bearer_auth(temp_token.access_token)
-            .body(req)
-            .send()
-            .await?;
+            .bearer_auth(source_token.access_token())
+            .body(req);
+        let resp = self.retry.send(req).await?;
 
         if !resp.status().is_success() {
             error!("access token loader for impersonated service account got unexpected response: {:?}", resp);
@@ -100,9 +98,102 @@ impl TokenLoader {
             );
         }
 
-        let token: Option<ImpersonatedToken> = serde_json::from_slice(&resp.bytes().await?)?;
-        let token = token.expect("couldn't parse access token response");
+        let token: ImpersonatedToken = serde_json::from_slice(&resp.bytes().await?)?;
+        let expires_in = parse_rfc3339(&token.expire_time)
+            .map(|t| (t - now()).num_seconds().max(0) as usize)
+            .unwrap_or(MAX_LIFETIME.as_secs() as usize);
+
+        Ok(Token::new(&token.access_token, expires_in, &self.scope))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+    use reqwest::Client;
+
+    use super::*;
+    use crate::google::credential::Credential;
+    use crate::test_util::MockResponse;
+    use crate::test_util::MockServer;
+    use crate::time::format_rfc3339;
+
+    const IMPERSONATION_PATH: &str =
+        "/v1/projects/-/serviceAccounts/target@example-01.iam.gserviceaccount.com:generateAccessToken";
+
+    #[tokio::test]
+    async fn test_load_via_impersonated_service_account() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let expire_time = now() + chrono::TimeDelta::try_minutes(30).expect("in bounds");
+        let server = MockServer::builder()
+            .with_response(
+                Method::POST,
+                "/token",
+                MockResponse::ok(r#"{"access_token":"source_token","expires_in":3600}"#),
+            )
+            .with_response(
+                Method::POST,
+                IMPERSONATION_PATH,
+                MockResponse::ok(
+                    serde_json::json!({
+                        "accessToken": "impersonated_token",
+                        "expireTime": format_rfc3339(expire_time),
+                    })
+                    .to_string(),
+                ),
+            )
+            .start()
+            .await?;
 
-        Ok(Token::new(&token.access_token, 3600, &temp_token.scope))
+        let path = format!(
+            "{}/testdata/services/google/test_impersonated_service_account.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut value: serde_json::Value = serde_json::from_slice(&std::fs::read(path)?)?;
+        value["delegates"] = serde_json::json!([
+            "projects/-/serviceAccounts/delegate@example-01.iam.gserviceaccount.com"
+        ]);
+        value["service_account_impersonation_url"] =
+            format!("{}{IMPERSONATION_PATH}", server.endpoint()).into();
+        value["source_credentials"]["token_uri"] = format!("{}/token", server.endpoint()).into();
+
+        let loader = TokenLoader::new(
+            "https://www.googleapis.com/auth/devstorage.read_only",
+            Client::new(),
+        )
+        .with_credentials(Credential::from_slice(value.to_string().as_bytes())?)
+        .with_disable_vm_metadata(true);
+
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("impersonated_token", token.access_token());
+        assert!(token.expires_in() <= 1800 && token.expires_in() > 1700);
+
+        let requests = server.requests();
+        assert_eq!(2, requests.len());
+
+        // Source credential is refreshed with cloud-platform scope.
+        assert_eq!("/token", requests[0].uri.path());
+        let form: Vec<(String, String)> = form_urlencoded::parse(&requests[0].body)
+            .into_owned()
+            .collect();
+        assert!(form.contains(&(
+            "refresh_token".to_string(),
+            "placeholder_refresh_token".to_string()
+        )));
+
+        assert_eq!(IMPERSONATION_PATH, requests[1].uri.path());
+        assert_eq!("Bearer source_token", requests[1].headers["authorization"]);
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body)?;
+        assert_eq!(
+            serde_json::json!({
+                "lifetime": "3600s",
+                "scope": ["https://www.googleapis.com/auth/devstorage.read_only"],
+                "delegates": ["projects/-/serviceAccounts/delegate@example-01.iam.gserviceaccount.com"],
+            }),
+            body
+        );
+
+        Ok(())
     }
 }
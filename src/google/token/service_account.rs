@@ -5,6 +5,7 @@ use jsonwebtoken::Algorithm;
 use jsonwebtoken::EncodingKey;
 use jsonwebtoken::Header;
 use log::error;
+use rsa::pkcs1::EncodeRsaPrivateKey;
 
 use super::Claims;
 use super::Token;
@@ -23,22 +24,24 @@ impl TokenLoader {
             return Ok(None);
         };
 
+        // The token endpoint is also the audience of assertion.
+        let token_uri = self.token_uri(&cred.token_uri);
         let jwt = jsonwebtoken::encode(
             &Header::new(Algorithm::RS256),
-            &Claims::new(&cred.client_email, &self.scope),
-            &EncodingKey::from_rsa_pem(cred.private_key.as_bytes())?,
+            &Claims::new(&cred.client_email, &self.scope, &token_uri),
+            &EncodingKey::from_rsa_der(cred.rsa_private_key()?.to_pkcs1_der()?.as_bytes()),
         )?;
 
-        let resp = self
+        let req = self
             .client
-            .post("https://oauth2.googleapis.com/token")
+            .post(&token_uri)
+            .header(header::USER_AGENT, &self.user_agent)
             .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
             .form(&[
                 ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
                 ("assertion", &jwt),
-            ])
-            .send()
-            .await?;
+            ]);
+        let resp = self.retry.send(req).await?;
 
         if !resp.status().is_success() {
             error!("exchange token got unexpected response: {:?}", resp);
@@ -49,3 +52,111 @@ impl TokenLoader {
         Ok(Some(token))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+    use reqwest::Client;
+
+    use super::*;
+    use crate::google::credential::Credential;
+    use crate::hash::base64_decode;
+    use crate::test_util::MockResponse;
+    use crate::test_util::MockServer;
+
+    fn credential(token_uri: Option<&str>) -> Credential {
+        let path = format!(
+            "{}/testdata/services/google/testbucket_credential.json",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(path).expect("fixture must exist"))
+                .expect("fixture must be valid");
+        match token_uri {
+            Some(uri) => value["token_uri"] = uri.into(),
+            None => {
+                value.as_object_mut().unwrap().remove("token_uri");
+            }
+        }
+        Credential::from_slice(value.to_string().as_bytes()).expect("credential must be valid")
+    }
+
+    /// Extract the `aud` claim of the assertion sent to token endpoint.
+    fn assertion_aud(body: &[u8]) -> String {
+        let (_, jwt) = form_urlencoded::parse(body)
+            .find(|(k, _)| k == "assertion")
+            .expect("assertion must exist");
+        let claims = jwt.split('.').nth(1).expect("jwt must be valid");
+        // JWT is base64url encoded without padding.
+        let mut claims = claims.replace('-', "+").replace('_', "/");
+        while claims.len() % 4 != 0 {
+            claims.push('=');
+        }
+        let claims: serde_json::Value =
+            serde_json::from_slice(&base64_decode(&claims).expect("claims must be valid"))
+                .expect("claims must be valid");
+        claims["aud"].as_str().expect("aud must exist").to_string()
+    }
+
+    async fn token_server() -> Result<MockServer> {
+        MockServer::builder()
+            .with_response(
+                Method::POST,
+                "/custom/token",
+                MockResponse::ok(r#"{"access_token":"token","expires_in":3600}"#),
+            )
+            .start()
+            .await
+    }
+
+    #[test]
+    fn test_default_token_uri() {
+        let cred = credential(None);
+        assert_eq!(
+            "https://oauth2.googleapis.com/token",
+            cred.service_account.unwrap().token_uri
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_via_service_account_with_token_uri() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = token_server().await?;
+        let token_uri = format!("{}/custom/token", server.endpoint());
+        let loader = TokenLoader::new("scope", Client::new())
+            .with_credentials(credential(Some(&token_uri)))
+            .with_disable_vm_metadata(true);
+
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("token", token.access_token());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(token_uri, assertion_aud(&requests[0].body));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_via_service_account_with_token_uri_override() -> Result<()> {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server = token_server().await?;
+        let token_uri = format!("{}/custom/token", server.endpoint());
+        // Explicit token uri takes precedence over the token_uri in credential.
+        let loader = TokenLoader::new("scope", Client::new())
+            .with_credentials(credential(Some("https://oauth2.example.com/token")))
+            .with_token_uri(&token_uri)
+            .with_disable_vm_metadata(true);
+
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("token", token.access_token());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(token_uri, assertion_aud(&requests[0].body));
+
+        Ok(())
+    }
+}
@@ -1,14 +1,151 @@
 //! Hash related utils.
+//!
+//! Crypto operations are implemented by [RustCrypto](https://github.com/RustCrypto)
+//! via `crypto-rustcrypto` by default, enable `crypto-ring` to use
+//! [`ring`](https://docs.rs/ring) instead.
+//!
+//! ring rejects RSA private keys shorter than 2048 bits which RustCrypto
+//! accepts, so signing with such keys fails with `crypto-ring`.
 
 use anyhow::anyhow;
 use anyhow::Result;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
-use hmac::Hmac;
-use hmac::Mac;
-use sha1::Sha1;
-use sha2::Digest;
-use sha2::Sha256;
+#[cfg(any(feature = "services-google", feature = "services-oracle"))]
+use rsa::RsaPrivateKey;
+
+#[cfg(not(any(feature = "crypto-rustcrypto", feature = "crypto-ring")))]
+compile_error!("either `crypto-rustcrypto` or `crypto-ring` must be enabled");
+
+/// Crypto operations used by signers.
+///
+/// All backends must produce the identical output for the same input.
+trait Backend {
+    fn sha1(content: &[u8]) -> Vec<u8>;
+    fn sha256(content: &[u8]) -> Vec<u8>;
+    fn hmac_sha1(key: &[u8], content: &[u8]) -> Vec<u8>;
+    fn hmac_sha256(key: &[u8], content: &[u8]) -> Vec<u8>;
+    /// RSASSA-PKCS1-v1_5 with SHA256.
+    #[cfg(any(feature = "services-google", feature = "services-oracle"))]
+    fn rsa_sha256(key: &RsaPrivateKey, content: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Backend implemented by [RustCrypto](https://github.com/RustCrypto).
+#[cfg(feature = "crypto-rustcrypto")]
+#[cfg_attr(feature = "crypto-ring", allow(dead_code))]
+struct RustCrypto;
+
+#[cfg(feature = "crypto-rustcrypto")]
+impl Backend for RustCrypto {
+    fn sha1(content: &[u8]) -> Vec<u8> {
+        use sha1::Digest;
+
+        sha1::Sha1::digest(content).to_vec()
+    }
+
+    fn sha256(content: &[u8]) -> Vec<u8> {
+        use sha2::Digest;
+
+        sha2::Sha256::digest(content).to_vec()
+    }
+
+    fn hmac_sha1(key: &[u8], content: &[u8]) -> Vec<u8> {
+        use hmac::Mac;
+
+        let mut h = hmac::Hmac::<sha1::Sha1>::new_from_slice(key).expect("invalid key length");
+        h.update(content);
+        h.finalize().into_bytes().to_vec()
+    }
+
+    fn hmac_sha256(key: &[u8], content: &[u8]) -> Vec<u8> {
+        use hmac::Mac;
+
+        let mut h = hmac::Hmac::<sha2::Sha256>::new_from_slice(key).expect("invalid key length");
+        h.update(content);
+        h.finalize().into_bytes().to_vec()
+    }
+
+    #[cfg(any(feature = "services-google", feature = "services-oracle"))]
+    fn rsa_sha256(key: &RsaPrivateKey, content: &[u8]) -> Result<Vec<u8>> {
+        use rsa::signature::RandomizedSigner;
+        use rsa::signature::SignatureEncoding;
+
+        // Sign with rng to enable blinding against timing attacks.
+        let mut rng = rand::thread_rng();
+        let signing_key = rsa::pkcs1v15::SigningKey::<sha2::Sha256>::new(key.clone());
+        Ok(signing_key.try_sign_with_rng(&mut rng, content)?.to_vec())
+    }
+}
+
+/// Backend implemented by [ring](https://github.com/briansmith/ring).
+///
+/// RSA private keys must be at least [`RING_RSA_MIN_BITS`] long.
+#[cfg(feature = "crypto-ring")]
+struct Ring;
+
+/// The minimal bits of RSA private keys accepted by ring.
+#[cfg(all(
+    feature = "crypto-ring",
+    any(feature = "services-google", feature = "services-oracle")
+))]
+const RING_RSA_MIN_BITS: usize = 2048;
+
+#[cfg(feature = "crypto-ring")]
+impl Backend for Ring {
+    fn sha1(content: &[u8]) -> Vec<u8> {
+        ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, content)
+            .as_ref()
+            .to_vec()
+    }
+
+    fn sha256(content: &[u8]) -> Vec<u8> {
+        ring::digest::digest(&ring::digest::SHA256, content)
+            .as_ref()
+            .to_vec()
+    }
+
+    fn hmac_sha1(key: &[u8], content: &[u8]) -> Vec<u8> {
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, key);
+        ring::hmac::sign(&key, content).as_ref().to_vec()
+    }
+
+    fn hmac_sha256(key: &[u8], content: &[u8]) -> Vec<u8> {
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key);
+        ring::hmac::sign(&key, content).as_ref().to_vec()
+    }
+
+    #[cfg(any(feature = "services-google", feature = "services-oracle"))]
+    fn rsa_sha256(key: &RsaPrivateKey, content: &[u8]) -> Result<Vec<u8>> {
+        use rsa::pkcs1::EncodeRsaPrivateKey;
+        use rsa::traits::PublicKeyParts;
+
+        let bits = key.size() * 8;
+        if bits < RING_RSA_MIN_BITS {
+            return Err(anyhow!(
+                "rsa private key of {bits} bits is too small for crypto-ring, at least {RING_RSA_MIN_BITS} bits is required"
+            ));
+        }
+        let der = key.to_pkcs1_der()?;
+        let key_pair = ring::signature::RsaKeyPair::from_der(der.as_bytes())
+            .map_err(|e| anyhow!("load rsa private key failed: {e}"))?;
+
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+        key_pair
+            .sign(
+                &ring::signature::RSA_PKCS1_SHA256,
+                &ring::rand::SystemRandom::new(),
+                content,
+                &mut signature,
+            )
+            .map_err(|e| anyhow!("rsa sign failed: {e}"))?;
+        Ok(signature)
+    }
+}
+
+#[cfg(all(feature = "crypto-rustcrypto", not(feature = "crypto-ring")))]
+type DefaultBackend = RustCrypto;
+#[cfg(feature = "crypto-ring")]
+type DefaultBackend = Ring;
 
 /// Base64 encode
 pub fn base64_encode(content: &[u8]) -> String {
@@ -25,67 +162,167 @@ pub fn base64_decode(content: &str) -> Result<Vec<u8>> {
 /// SHA256 hash.
 #[allow(dead_code)]
 pub fn sha256(content: &[u8]) -> Vec<u8> {
-    Sha256::digest(content).as_slice().to_vec()
+    DefaultBackend::sha256(content)
 }
 
 /// Hex encoded SHA1 hash.
-///
-/// Use this function instead of `hex::encode(sha1(content))` can reduce
-/// extra copy.
 pub fn hex_sha1(content: &[u8]) -> String {
-    hex::encode(Sha1::digest(content).as_slice())
+    hex::encode(DefaultBackend::sha1(content))
 }
 
 /// Hex encoded SHA256 hash.
-///
-/// Use this function instead of `hex::encode(sha256(content))` can reduce
-/// extra copy.
 pub fn hex_sha256(content: &[u8]) -> String {
-    hex::encode(Sha256::digest(content).as_slice())
+    hex::encode(DefaultBackend::sha256(content))
 }
 
 /// HMAC with SHA256 hash.
 pub fn hmac_sha256(key: &[u8], content: &[u8]) -> Vec<u8> {
-    let mut h = Hmac::<Sha256>::new_from_slice(key).expect("invalid key length");
-    h.update(content);
-
-    h.finalize().into_bytes().to_vec()
+    DefaultBackend::hmac_sha256(key, content)
 }
 
 /// Base64 encoded HMAC with SHA256 hash.
 pub fn base64_hmac_sha256(key: &[u8], content: &[u8]) -> String {
-    let mut h = Hmac::<Sha256>::new_from_slice(key).expect("invalid key length");
-    h.update(content);
-
-    base64_encode(&h.finalize().into_bytes())
+    base64_encode(&DefaultBackend::hmac_sha256(key, content))
 }
 
 /// Hex encoded HMAC with SHA1 hash.
-///
-/// Use this function instead of `hex::encode(hmac_sha1(key, content))` can
-/// reduce extra copy.
 pub fn hex_hmac_sha1(key: &[u8], content: &[u8]) -> String {
-    let mut h = Hmac::<Sha1>::new_from_slice(key).expect("invalid key length");
-    h.update(content);
-
-    hex::encode(h.finalize().into_bytes())
+    hex::encode(DefaultBackend::hmac_sha1(key, content))
 }
 
 /// Hex encoded HMAC with SHA256 hash.
-///
-/// Use this function instead of `hex::encode(hmac_sha256(key, content))` can
-/// reduce extra copy.
 pub fn hex_hmac_sha256(key: &[u8], content: &[u8]) -> String {
-    let mut h = Hmac::<Sha256>::new_from_slice(key).expect("invalid key length");
-    h.update(content);
-
-    hex::encode(h.finalize().into_bytes())
+    hex::encode(DefaultBackend::hmac_sha256(key, content))
 }
 
 /// Base64 encoded HMAC with SHA1 hash.
 pub fn base64_hmac_sha1(key: &[u8], content: &[u8]) -> String {
-    let mut h = Hmac::<Sha1>::new_from_slice(key).expect("invalid key length");
-    h.update(content);
+    base64_encode(&DefaultBackend::hmac_sha1(key, content))
+}
+
+/// RSASSA-PKCS1-v1_5 signature with SHA256 hash.
+#[cfg(any(feature = "services-google", feature = "services-oracle"))]
+pub fn rsa_sha256(key: &RsaPrivateKey, content: &[u8]) -> Result<Vec<u8>> {
+    DefaultBackend::rsa_sha256(key, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// Test vectors from FIPS 180-2 and RFC 4231 / RFC 2202.
+    fn test_vectors<B: Backend>() {
+        assert_eq!(
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            hex::encode(B::sha1(b"abc"))
+        );
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            hex::encode(B::sha256(b"abc"))
+        );
+        assert_eq!(
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79",
+            hex::encode(B::hmac_sha1(b"Jefe", b"what do ya want for nothing?"))
+        );
+        assert_eq!(
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            hex::encode(B::hmac_sha256(b"Jefe", b"what do ya want for nothing?"))
+        );
+        // Key longer than block size must be hashed first.
+        assert_eq!(
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            hex::encode(B::hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ))
+        );
+    }
+
+    #[cfg(feature = "crypto-rustcrypto")]
+    #[test]
+    fn test_rust_crypto() {
+        test_vectors::<RustCrypto>();
+    }
+
+    #[cfg(feature = "crypto-ring")]
+    #[test]
+    fn test_ring() {
+        test_vectors::<Ring>();
+    }
+
+    /// Make sure all backends produce the same output on the same random inputs.
+    #[cfg(all(feature = "crypto-rustcrypto", feature = "crypto-ring"))]
+    #[test]
+    fn test_backends_are_identical() {
+        use rand::Rng;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..64 {
+            let key: Vec<u8> = (0..rng.gen_range(0..256)).map(|_| rng.gen()).collect();
+            let content: Vec<u8> = (0..rng.gen_range(0..4096)).map(|_| rng.gen()).collect();
+
+            assert_eq!(RustCrypto::sha1(&content), Ring::sha1(&content));
+            assert_eq!(RustCrypto::sha256(&content), Ring::sha256(&content));
+            assert_eq!(
+                RustCrypto::hmac_sha1(&key, &content),
+                Ring::hmac_sha1(&key, &content)
+            );
+            assert_eq!(
+                RustCrypto::hmac_sha256(&key, &content),
+                Ring::hmac_sha256(&key, &content)
+            );
+        }
+    }
+
+    /// PKCS#1 v1.5 signatures are deterministic, so they must be identical.
+    #[cfg(all(
+        feature = "crypto-rustcrypto",
+        feature = "crypto-ring",
+        feature = "services-google"
+    ))]
+    #[test]
+    fn test_rsa_backends_are_identical() -> Result<()> {
+        use rsa::pkcs8::DecodePrivateKey;
+
+        let content = std::fs::read_to_string(format!(
+            "{}/testdata/services/google/testbucket_credential.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))?;
+        let cred: serde_json::Value = serde_json::from_str(&content)?;
+        let key = RsaPrivateKey::from_pkcs8_pem(
+            cred["private_key"]
+                .as_str()
+                .expect("private_key must be valid"),
+        )?;
+
+        assert_eq!(
+            RustCrypto::rsa_sha256(&key, b"hello, world")?,
+            Ring::rsa_sha256(&key, b"hello, world")?
+        );
+        Ok(())
+    }
+
+    /// ring rejects RSA keys shorter than 2048 bits, which RustCrypto accepts.
+    #[cfg(all(
+        feature = "crypto-ring",
+        any(feature = "services-google", feature = "services-oracle")
+    ))]
+    #[test]
+    fn test_ring_rejects_small_rsa_keys() -> Result<()> {
+        use rsa::pkcs8::DecodePrivateKey;
+
+        // The 1024 bits key of the OCI example.
+        let key = RsaPrivateKey::from_pkcs8_pem(&std::fs::read_to_string(format!(
+            "{}/testdata/services/oracle/example_key.pem",
+            env!("CARGO_MANIFEST_DIR")
+        ))?)?;
 
-    base64_encode(&h.finalize().into_bytes())
+        let err = Ring::rsa_sha256(&key, b"hello, world").expect_err("small key must be rejected");
+        assert!(err.to_string().contains("at least 2048 bits"), "{err}");
+        #[cfg(feature = "crypto-rustcrypto")]
+        RustCrypto::rsa_sha256(&key, b"hello, world")?;
+        Ok(())
+    }
 }
@@ -4,6 +4,8 @@ use std::sync::Mutex;
 use anyhow::Result;
 
 use super::config::Config;
+use crate::metrics::Metrics;
+use crate::metrics::SharedMetrics;
 
 /// Credential for obs.
 #[derive(Clone)]
@@ -24,6 +26,7 @@ pub struct CredentialLoader {
     config: Config,
 
     credential: Arc<Mutex<Option<Credential>>>,
+    metrics: SharedMetrics,
 }
 
 impl CredentialLoader {
@@ -33,17 +36,30 @@ impl CredentialLoader {
             config,
 
             credential: Arc::default(),
+            metrics: SharedMetrics::default(),
         }
     }
 
+    /// Specify the metrics to record cache hits and refreshes of credential.
+    ///
+    /// Default to [`NoopMetrics`](crate::NoopMetrics).
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics.into();
+        self
+    }
+
     /// Load credential
     pub async fn load(&self) -> Result<Option<Credential>> {
         // Return cached credential if it's valid.
         if let Some(cred) = self.credential.lock().expect("lock poisoned").clone() {
+            self.metrics.credential_cache_hit("huaweicloud");
             return Ok(Some(cred));
         }
 
-        let cred = self.load_inner().await?;
+        let cred = self.load_inner().await;
+        self.metrics
+            .credential_refreshed("huaweicloud", matches!(cred, Ok(Some(_))));
+        let cred = cred?;
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         lock.clone_from(&cred);
@@ -109,4 +125,45 @@ mod tests {
             },
         );
     }
+
+    #[tokio::test]
+    async fn test_credential_loader_with_metrics() -> Result<()> {
+        #[derive(Default)]
+        struct RecordingMetrics {
+            events: Mutex<Vec<String>>,
+        }
+
+        impl Metrics for RecordingMetrics {
+            fn credential_cache_hit(&self, service: &'static str) {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("cache_hit:{service}"));
+            }
+
+            fn credential_refreshed(&self, service: &'static str, success: bool) {
+                self.events
+                    .lock()
+                    .unwrap()
+                    .push(format!("refreshed:{service}:{success}"));
+            }
+        }
+
+        let metrics = Arc::new(RecordingMetrics::default());
+        let l = CredentialLoader::new(Config {
+            access_key_id: Some("access_key_id".to_string()),
+            secret_access_key: Some("secret_access_key".to_string()),
+            ..Default::default()
+        })
+        .with_metrics(metrics.clone());
+        for _ in 0..2 {
+            l.load().await?.expect("credential must be loaded");
+        }
+
+        assert_eq!(
+            vec!["refreshed:huaweicloud:true", "cache_hit:huaweicloud"],
+            *metrics.events.lock().unwrap()
+        );
+        Ok(())
+    }
 }
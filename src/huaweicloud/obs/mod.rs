@@ -2,6 +2,7 @@
 
 mod signer;
 pub use signer::Signer as HuaweicloudObsSigner;
+pub use signer::SigningVersion as HuaweicloudObsSigningVersion;
 
 mod config;
 pub use config::Config as HuaweicloudObsConfig;
@@ -9,3 +10,12 @@ pub use config::Config as HuaweicloudObsConfig;
 mod credential;
 pub use credential::Credential as HuaweicloudObsCredential;
 pub use credential::CredentialLoader as HuaweicloudObsCredentialLoader;
+
+crate::client::signing_client!(
+    /// Signing client for Huaweicloud OBS, which loads credential by [`HuaweicloudObsCredentialLoader`] and
+    /// signs requests by [`HuaweicloudObsSigner`].
+    HuaweicloudObsSigningClient,
+    "huaweicloud",
+    HuaweicloudObsCredentialLoader,
+    HuaweicloudObsSigner
+);
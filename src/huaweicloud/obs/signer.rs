@@ -23,12 +23,34 @@ use crate::time::format_http_date;
 use crate::time::now;
 use crate::time::DateTime;
 
+/// Signing version used by [`Signer`].
+///
+/// New versions may be added in future, and [`SigningVersion::V4`] only
+/// exists with `services-aws` feature.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SigningVersion {
+    /// Native OBS signature, like `Authorization: OBS {access_key_id}:{signature}`.
+    #[default]
+    V2,
+    /// AWS SigV4 compatible signature of the S3-compatible endpoint, like
+    /// `Authorization: AWS4-HMAC-SHA256 Credential=...`.
+    ///
+    /// Requires `services-aws` feature.
+    #[cfg(feature = "services-aws")]
+    V4 {
+        /// Region of the endpoint, like `cn-north-4`.
+        region: String,
+    },
+}
+
 /// Singer that implement Huawei Cloud Object Storage Service Authorization.
 ///
 /// - [User Signature Authentication](https://support.huaweicloud.com/intl/en-us/api-obs/obs_04_0009.html)
 #[derive(Debug)]
 pub struct Signer {
     bucket: String,
+    version: SigningVersion,
 
     time: Option<DateTime>,
 }
@@ -38,10 +60,19 @@ impl Signer {
     pub fn new(bucket: &str) -> Self {
         Self {
             bucket: bucket.to_string(),
+            version: SigningVersion::default(),
             time: None,
         }
     }
 
+    /// Specify the signing version.
+    ///
+    /// Default to [`SigningVersion::V2`].
+    pub fn with_signing_version(mut self, version: SigningVersion) -> Self {
+        self.version = version;
+        self
+    }
+
     /// Specify the signing time.
     ///
     /// # Note
@@ -63,13 +94,17 @@ impl Signer {
         let now = self.time.unwrap_or_else(now);
         let mut ctx = req.build()?;
 
+        // Date provided by caller takes precedence.
+        if method == SigningMethod::Header && ctx.headers.get(DATE).is_none() {
+            ctx.headers.insert(DATE, format_http_date(now).parse()?);
+        }
+
         let string_to_sign = string_to_sign(&mut ctx, cred, now, method, &self.bucket)?;
         let signature =
             base64_hmac_sha1(cred.secret_access_key.as_bytes(), string_to_sign.as_bytes());
 
         match method {
             SigningMethod::Header => {
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
                 ctx.headers.insert(AUTHORIZATION, {
                     let mut value: HeaderValue =
                         format!("OBS {}:{}", cred.access_key_id, signature).parse()?;
@@ -79,7 +114,9 @@ impl Signer {
                 });
             }
             SigningMethod::Query(expire) => {
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                if ctx.headers.get(DATE).is_none() {
+                    ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                }
                 ctx.query_push("AccessKeyId", &cred.access_key_id);
                 ctx.query_push(
                     "Expires",
@@ -128,6 +165,12 @@ impl Signer {
     /// }
     /// ```
     pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<()> {
+        #[cfg(feature = "services-aws")]
+        if let SigningVersion::V4 { region } = &self.version {
+            let (signer, cred) = self.v4_signer(region, cred);
+            return signer.sign(req, &cred);
+        }
+
         let ctx = self.build(req, SigningMethod::Header, cred)?;
         req.apply(ctx)
     }
@@ -139,9 +182,38 @@ impl Signer {
         expire: Duration,
         cred: &Credential,
     ) -> Result<()> {
+        #[cfg(feature = "services-aws")]
+        if let SigningVersion::V4 { region } = &self.version {
+            let (signer, cred) = self.v4_signer(region, cred);
+            return signer.sign_query(req, expire, &cred);
+        }
+
         let ctx = self.build(req, SigningMethod::Query(expire), cred)?;
         req.apply(ctx)
     }
+
+    /// Build the SigV4 signer and credential for the S3-compatible endpoint.
+    #[cfg(feature = "services-aws")]
+    fn v4_signer(
+        &self,
+        region: &str,
+        cred: &Credential,
+    ) -> (crate::AwsV4Signer, crate::AwsCredential) {
+        #[allow(unused_mut)]
+        let mut signer = crate::AwsV4Signer::new("s3", region);
+        #[cfg(test)]
+        if let Some(time) = self.time {
+            signer = signer.time(time);
+        }
+
+        let cred = crate::AwsCredential {
+            access_key_id: cred.access_key_id.clone(),
+            secret_access_key: cred.secret_access_key.clone(),
+            session_token: cred.security_token.clone(),
+            ..Default::default()
+        };
+        (signer, cred)
+    }
 }
 
 /// Construct string to sign
@@ -176,7 +248,7 @@ fn string_to_sign(
     s.write_str("\n")?;
     match method {
         SigningMethod::Header => {
-            writeln!(&mut s, "{}", format_http_date(now))?;
+            writeln!(&mut s, "{}", ctx.header_get_or_default(&DATE)?)?;
         }
         SigningMethod::Query(expires) => {
             writeln!(
@@ -219,7 +291,7 @@ fn canonicalize_header(
     }
 
     Ok(SigningContext::header_to_string(
-        ctx.header_to_vec_with_prefix("x-obs-"),
+        ctx.header_to_vec_with_prefix("x-obs-")?,
         ":",
         "\n",
     ))
@@ -367,6 +439,45 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_sign_with_caller_date() -> Result<()> {
+        let config = Config {
+            access_key_id: Some("access_key".to_string()),
+            secret_access_key: Some("123456".to_string()),
+            ..Default::default()
+        };
+        let loader = CredentialLoader::new(config);
+        let cred = loader.load().await?.unwrap();
+
+        // Signing time is ignored since caller has provided the date.
+        let signer = Signer::new("bucket");
+
+        let get_req = "http://bucket.obs.cn-north-4.myhuaweicloud.com/object.txt";
+        let mut req = http::Request::get(Uri::from_str(get_req)?).body(())?;
+        req.headers_mut().insert(
+            HeaderName::from_str("Content-MD5")?,
+            HeaderValue::from_str("abc")?,
+        );
+        req.headers_mut().insert(
+            HeaderName::from_str("Content-Type")?,
+            HeaderValue::from_str("text/plain")?,
+        );
+        req.headers_mut().insert(
+            DATE,
+            HeaderValue::from_str("Mon, 15 Aug 2022 16:50:12 GMT")?,
+        );
+
+        signer.sign(&mut req, &cred)?;
+        let headers = req.headers();
+        assert_eq!(
+            "OBS access_key:9gUZ4ol2W19LyYcc92Bu3U0V09E=",
+            headers.get("Authorization").unwrap().to_str()?,
+        );
+        assert_eq!("Mon, 15 Aug 2022 16:50:12 GMT", headers[DATE]);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_sign_with_subresource() -> Result<()> {
         let config = Config {
@@ -453,4 +564,104 @@ mod tests {
 
         Ok(())
     }
+
+    #[cfg(feature = "services-aws")]
+    #[tokio::test]
+    async fn test_sign_v4() -> Result<()> {
+        let config = Config {
+            access_key_id: Some("access_key".to_string()),
+            secret_access_key: Some("123456".to_string()),
+            security_token: Some("security_token".to_string()),
+        };
+        let loader = CredentialLoader::new(config);
+        let cred = loader.load().await?.unwrap();
+
+        let time = chrono::DateTime::parse_from_rfc2822("Mon, 15 Aug 2022 16:50:12 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        let signer = Signer::new("bucket")
+            .with_signing_version(SigningVersion::V4 {
+                region: "cn-north-4".to_string(),
+            })
+            .with_time(time);
+
+        let get_req = "http://bucket.obs.cn-north-4.myhuaweicloud.com/object.txt";
+        let mut req = http::Request::get(Uri::from_str(get_req)?).body(())?;
+        signer.sign(&mut req, &cred)?;
+
+        // Should be the same as signing with aws signer directly.
+        let mut expected = http::Request::get(Uri::from_str(get_req)?).body(())?;
+        crate::AwsV4Signer::new("s3", "cn-north-4")
+            .time(time)
+            .sign(
+                &mut expected,
+                &crate::AwsCredential {
+                    access_key_id: "access_key".to_string(),
+                    secret_access_key: "123456".to_string(),
+                    session_token: Some("security_token".to_string()),
+                    expires_in: None,
+                    ..Default::default()
+                },
+            )?;
+
+        let auth = req.headers()["Authorization"].to_str()?;
+        assert!(
+            auth.starts_with(
+                "AWS4-HMAC-SHA256 Credential=access_key/20220815/cn-north-4/s3/aws4_request"
+            ),
+            "{auth}"
+        );
+        assert_eq!(expected.headers()["Authorization"], auth);
+        assert_eq!("security_token", req.headers()["x-amz-security-token"]);
+        assert!(req.headers().get(DATE).is_none());
+
+        // Query signing uses SigV4 too.
+        let mut req = http::Request::get(Uri::from_str(get_req)?).body(())?;
+        signer.sign_query(&mut req, Duration::from_secs(3600), &cred)?;
+        let query = req.uri().query().unwrap();
+        assert!(
+            query.contains("X-Amz-Algorithm=AWS4-HMAC-SHA256"),
+            "{query}"
+        );
+        assert!(query.contains("X-Amz-Signature="), "{query}");
+        assert!(!query.contains("AccessKeyId="), "{query}");
+
+        Ok(())
+    }
+
+    // The canonicalized resource is built from the bucket of signer, IPv6
+    // literals in host are kept as is.
+    #[test_case::test_case("http://[::1]:9000/object.txt"; "ipv6 with port")]
+    #[test_case::test_case("http://[::1]/object.txt"; "ipv6 without port")]
+    fn test_sign_with_ipv6(uri: &str) -> Result<()> {
+        let cred = Credential {
+            access_key_id: "access_key".to_string(),
+            secret_access_key: "123456".to_string(),
+            security_token: None,
+        };
+        let signer = Signer::new("bucket").with_time(
+            chrono::DateTime::parse_from_rfc2822("Mon, 15 Aug 2022 16:50:12 GMT")
+                .unwrap()
+                .with_timezone(&Utc),
+        );
+
+        let mut req = http::Request::get(uri)
+            .header("Content-MD5", "abc")
+            .header("Content-Type", "text/plain")
+            .body(())?;
+        signer.sign(&mut req, &cred)?;
+
+        // Same as the signature of virtual hosted style in `test_sign`.
+        assert_eq!(
+            "OBS access_key:9gUZ4ol2W19LyYcc92Bu3U0V09E=",
+            req.headers()["Authorization"].to_str()?,
+        );
+        assert_eq!(uri, req.uri().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_signing_version() {
+        assert_eq!(SigningVersion::V2, Signer::new("bucket").version);
+    }
 }
@@ -44,8 +44,36 @@
 //! reqsign support [`http::Request`] by default. Other request types support are hided
 //! under feature gates to reduce dependencies.
 //!
+//! Each service is also gated by its own feature, all of them are enabled by `services-all`
+//! in default features. Users of a single cloud can disable default features and enable only
+//! the services they need, dependencies of other services won't be compiled:
+//!
+//! - `services-aliyun`, `services-aws`, `services-azblob`, `services-google`,
+//!   `services-huaweicloud`, `services-oracle` and `services-tencent`.
+//! - `aliyun`, `aws`, `azure`, `google`, `huaweicloud`, `oracle` and `tencent` are
+//!   aliases of them.
+//!
+//! ```toml
+//! reqsign = { version = "*", default-features = false, features = ["reqwest_request", "aws", "crypto-rustcrypto"] }
+//! ```
+//!
+//! Other features:
+//!
 //! - `reqwest_request`: Enable to support signing [`reqwest::Request`]
 //! - `reqwest_blocking_request`: Enable to support signing [`reqwest::blocking::Request`]
+//! - `crypto-rustcrypto`: Enabled by default, use RustCrypto as crypto backend. Either this or
+//!   `crypto-ring` must be enabled.
+//! - `crypto-ring`: Enable to use [`ring`](https://docs.rs/ring) instead of RustCrypto as crypto backend.
+//!   RSA private keys of google and oracle must be at least 2048 bits long, which is not required
+//!   by RustCrypto.
+//! - `no_network`: Compile out all loaders that send requests (IMDS, STS, token exchange and so on),
+//!   only static credentials from config, env and profile can be used.
+//! - `metrics`: Enable [`MetricsRecorder`] to emit credential and signing metrics via the
+//!   [`metrics`](https://docs.rs/metrics) crate.
+//! - `refresh-ahead`: Enable to refresh credentials in background before they expire.
+//! - `test-util`: Enable [`test_util`] to mock metadata services in tests.
+//! - `dump-canonical`: Dump canonical requests and strings to sign of every signed request
+//!   to log or the sink set by `set_canonical_sink`, useful to chase signature mismatches.
 
 // Make sure all our public APIs have docs.
 #![warn(missing_docs)]
@@ -85,8 +113,79 @@ mod tencent;
 #[cfg(feature = "services-tencent")]
 pub use tencent::*;
 
+#[cfg(all(any(test, feature = "test-util"), not(target_arch = "wasm32")))]
+pub mod test_util;
+
+#[cfg(all(any(test, feature = "refresh-ahead"), not(target_arch = "wasm32")))]
+mod refresh;
+#[cfg(all(any(test, feature = "refresh-ahead"), not(target_arch = "wasm32")))]
+pub use refresh::RefreshGuard;
+
+mod client;
+mod constants;
+mod env_snapshot;
+pub use env_snapshot::EnvSnapshot;
 mod ctx;
 mod dirs;
+#[cfg_attr(
+    not(feature = "dump-canonical"),
+    allow(dead_code, unused_macros, unused_imports)
+)]
+mod dump;
+#[cfg(feature = "dump-canonical")]
+pub use dump::set_canonical_sink;
+#[cfg(feature = "dump-canonical")]
+pub use dump::CanonicalSink;
 mod hash;
+mod metrics;
+pub use metrics::Metrics;
+#[cfg(feature = "metrics")]
+pub use metrics::MetricsRecorder;
+pub use metrics::NoopMetrics;
 mod request;
+#[cfg(all(
+    any(
+        feature = "services-aws",
+        feature = "services-azblob",
+        feature = "services-google"
+    ),
+    not(feature = "no_network")
+))]
+mod retry;
 mod time;
+mod warning;
+pub use warning::Warning;
+
+/// Loaders sending requests must be absent with `no_network` enabled, while
+/// the default loader is still available.
+///
+/// ```
+/// use reqsign::AwsDefaultLoader;
+/// ```
+///
+/// ```compile_fail
+/// use reqsign::AwsAssumeRoleLoader;
+/// ```
+///
+/// ```compile_fail
+/// use reqsign::AwsContainerLoader;
+/// ```
+///
+/// ```compile_fail
+/// use reqsign::AwsUnixSocketLoader;
+/// ```
+#[cfg(all(doctest, feature = "no_network", feature = "services-aws"))]
+pub struct AwsNoNetwork;
+
+/// Token loaders of google send requests, they must be absent with
+/// `no_network` enabled.
+///
+/// ```
+/// use reqsign::GoogleSigner;
+/// ```
+///
+/// ```compile_fail
+/// use reqsign::GoogleTokenLoader;
+/// ```
+#[cfg(all(doctest, feature = "no_network", feature = "services-google"))]
+pub struct GoogleNoNetwork;
@@ -0,0 +1,259 @@
+//! Hooks to record metrics of credential loading and signing.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Metrics receives callbacks of credential loading and signing, so that
+/// operators can emit them to their own metrics system.
+///
+/// All methods are no-op by default, implement the ones you are interested
+/// in. `service` is the name of the service like `aws` and `azblob`.
+///
+/// Callbacks are invoked inline, implementations must be cheap and must not
+/// block.
+pub trait Metrics: Send + Sync {
+    /// Called after a credential has been loaded by a signing client, no
+    /// matter it's cached or not.
+    ///
+    /// `success` is `false` if the load failed or no credential was found.
+    fn credential_loaded(&self, service: &'static str, elapsed: Duration, success: bool) {
+        let _ = (service, elapsed, success);
+    }
+
+    /// Called while a loader returns the cached credential.
+    fn credential_cache_hit(&self, service: &'static str) {
+        let _ = service;
+    }
+
+    /// Called after a loader refreshed the credential from its sources, which
+    /// happens on cache miss or in background.
+    fn credential_refreshed(&self, service: &'static str, success: bool) {
+        let _ = (service, success);
+    }
+
+    /// Called after a request has been signed by a signing client.
+    fn request_signed(&self, service: &'static str, elapsed: Duration, success: bool) {
+        let _ = (service, elapsed, success);
+    }
+}
+
+/// Metrics that does nothing, used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+/// Metrics emitted via the [`metrics`](https://docs.rs/metrics) crate to the
+/// installed recorder.
+///
+/// All metrics carry a `service` label, `success` label is `true` or `false`:
+///
+/// - `reqsign_credential_loads_total` counter, with `success` label.
+/// - `reqsign_credential_load_duration_seconds` histogram, with `success` label.
+/// - `reqsign_credential_cache_hits_total` counter.
+/// - `reqsign_credential_refreshes_total` counter, with `success` label.
+/// - `reqsign_requests_signed_total` counter, with `success` label.
+/// - `reqsign_request_sign_duration_seconds` histogram, with `success` label.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsRecorder;
+
+#[cfg(feature = "metrics")]
+impl Metrics for MetricsRecorder {
+    fn credential_loaded(&self, service: &'static str, elapsed: Duration, success: bool) {
+        let labels = [("service", service), ("success", bool_label(success))];
+        ::metrics::counter!("reqsign_credential_loads_total", &labels).increment(1);
+        ::metrics::histogram!("reqsign_credential_load_duration_seconds", &labels)
+            .record(elapsed.as_secs_f64());
+    }
+
+    fn credential_cache_hit(&self, service: &'static str) {
+        ::metrics::counter!("reqsign_credential_cache_hits_total", "service" => service)
+            .increment(1);
+    }
+
+    fn credential_refreshed(&self, service: &'static str, success: bool) {
+        let labels = [("service", service), ("success", bool_label(success))];
+        ::metrics::counter!("reqsign_credential_refreshes_total", &labels).increment(1);
+    }
+
+    fn request_signed(&self, service: &'static str, elapsed: Duration, success: bool) {
+        let labels = [("service", service), ("success", bool_label(success))];
+        ::metrics::counter!("reqsign_requests_signed_total", &labels).increment(1);
+        ::metrics::histogram!("reqsign_request_sign_duration_seconds", &labels)
+            .record(elapsed.as_secs_f64());
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn bool_label(v: bool) -> &'static str {
+    if v {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+/// Metrics shared by loaders and clients, [`NoopMetrics`] by default.
+#[derive(Clone)]
+pub(crate) struct SharedMetrics(Arc<dyn Metrics>);
+
+impl Default for SharedMetrics {
+    fn default() -> Self {
+        Self(Arc::new(NoopMetrics))
+    }
+}
+
+impl Debug for SharedMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedMetrics")
+    }
+}
+
+impl From<Arc<dyn Metrics>> for SharedMetrics {
+    fn from(metrics: Arc<dyn Metrics>) -> Self {
+        Self(metrics)
+    }
+}
+
+impl Deref for SharedMetrics {
+    type Target = dyn Metrics;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Measure the elapsed time of credential loads and signs.
+///
+/// Monotonic [`Instant`](std::time::Instant) is used except on wasm, where
+/// it's not available and the wall clock is used instead.
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start: crate::time::DateTime,
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "services-aliyun",
+        feature = "services-aws",
+        feature = "services-azblob",
+        feature = "services-google",
+        feature = "services-huaweicloud",
+        feature = "services-oracle",
+        feature = "services-tencent"
+    )),
+    allow(dead_code)
+)]
+impl Stopwatch {
+    /// Start a stopwatch now.
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+            #[cfg(target_arch = "wasm32")]
+            start: crate::time::now(),
+        }
+    }
+
+    /// Elapsed time since started, zero if the clock goes backwards.
+    pub(crate) fn elapsed(&self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.start.elapsed();
+        #[cfg(target_arch = "wasm32")]
+        return (crate::time::now() - self.start)
+            .to_std()
+            .unwrap_or_default();
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use metrics_util::debugging::DebugValue;
+    use metrics_util::debugging::DebuggingRecorder;
+    use metrics_util::MetricKind;
+
+    use super::*;
+
+    #[test]
+    fn test_metrics_recorder() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            let m = MetricsRecorder;
+            m.credential_loaded("aws", Duration::from_millis(100), true);
+            m.credential_loaded("aws", Duration::from_millis(200), true);
+            m.credential_cache_hit("aws");
+            m.credential_refreshed("aws", false);
+            m.request_signed("azblob", Duration::from_millis(5), true);
+        });
+
+        let mut got: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let (kind, key) = key.into_parts();
+                let mut labels: Vec<_> = key
+                    .labels()
+                    .map(|l| format!("{}={}", l.key(), l.value()))
+                    .collect();
+                labels.sort();
+                (kind, key.name().to_string(), labels.join(","), value)
+            })
+            .collect();
+        got.sort_by(|a, b| a.1.cmp(&b.1));
+
+        let expected = vec![
+            (
+                MetricKind::Counter,
+                "reqsign_credential_cache_hits_total",
+                "service=aws",
+                DebugValue::Counter(1),
+            ),
+            (
+                MetricKind::Histogram,
+                "reqsign_credential_load_duration_seconds",
+                "service=aws,success=true",
+                DebugValue::Histogram(vec![0.1.into(), 0.2.into()]),
+            ),
+            (
+                MetricKind::Counter,
+                "reqsign_credential_loads_total",
+                "service=aws,success=true",
+                DebugValue::Counter(2),
+            ),
+            (
+                MetricKind::Counter,
+                "reqsign_credential_refreshes_total",
+                "service=aws,success=false",
+                DebugValue::Counter(1),
+            ),
+            (
+                MetricKind::Histogram,
+                "reqsign_request_sign_duration_seconds",
+                "service=azblob,success=true",
+                DebugValue::Histogram(vec![0.005.into()]),
+            ),
+            (
+                MetricKind::Counter,
+                "reqsign_requests_signed_total",
+                "service=azblob,success=true",
+                DebugValue::Counter(1),
+            ),
+        ];
+        assert_eq!(got.len(), expected.len());
+        for (got, (kind, name, labels, value)) in got.into_iter().zip(expected) {
+            assert_eq!(got.0, kind);
+            assert_eq!(got.1, name);
+            assert_eq!(got.2, labels);
+            assert_eq!(got.3, value);
+        }
+    }
+}
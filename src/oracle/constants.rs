@@ -1,2 +1,16 @@
+use percent_encoding::AsciiSet;
+use percent_encoding::NON_ALPHANUMERIC;
+
 // Env values used in oracle cloud infrastructure services.
 pub const ORACLE_CONFIG_PATH: &str = "~/.oci/config";
+
+// Headers used in oracle cloud infrastructure services.
+pub const X_CONTENT_SHA256: &str = "x-content-sha256";
+
+/// AsciiSet for query of the `(request-target)`, only unreserved characters
+/// are kept as is.
+pub static OCI_QUERY_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
@@ -6,6 +6,8 @@ use log::debug;
 
 use super::config::Config;
 use super::constants::ORACLE_CONFIG_PATH;
+use crate::metrics::Metrics;
+use crate::metrics::SharedMetrics;
 use crate::time::now;
 use crate::time::DateTime;
 
@@ -40,18 +42,33 @@ impl Credential {
 #[cfg_attr(test, derive(Debug))]
 pub struct Loader {
     credential: Arc<Mutex<Option<Credential>>>,
+    metrics: SharedMetrics,
 }
 
 impl Loader {
+    /// Specify the metrics to record cache hits and refreshes of credential.
+    ///
+    /// Default to [`NoopMetrics`](crate::NoopMetrics).
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics.into();
+        self
+    }
+
     /// Load credential.
     pub async fn load(&self) -> Result<Option<Credential>> {
         // Return cached credential if it's valid.
         match self.credential.lock().expect("lock poisoned").clone() {
-            Some(cred) if cred.is_valid() => return Ok(Some(cred)),
+            Some(cred) if cred.is_valid() => {
+                self.metrics.credential_cache_hit("oracle");
+                return Ok(Some(cred));
+            }
             _ => (),
         }
 
-        let cred = if let Some(cred) = self.load_inner().await? {
+        let cred = self.load_inner().await;
+        self.metrics
+            .credential_refreshed("oracle", matches!(cred, Ok(Some(_))));
+        let cred = if let Some(cred) = cred? {
             cred
         } else {
             return Ok(None);
@@ -11,4 +11,13 @@ mod credential;
 pub use credential::Credential as OCICredential;
 pub use credential::Loader as OCILoader;
 
+crate::client::signing_client!(
+    /// Signing client for OCI, which loads credential by [`OCILoader`] and
+    /// signs requests by [`OCIAPIKeySigner`].
+    OCISigningClient,
+    "oracle",
+    OCILoader,
+    OCIAPIKeySigner
+);
+
 mod constants;
@@ -3,48 +3,88 @@
 use anyhow::{Error, Result};
 use base64::{engine::general_purpose, Engine as _};
 use http::{
-    header::{AUTHORIZATION, DATE},
-    HeaderValue,
+    header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, DATE},
+    HeaderValue, Method,
 };
 use log::debug;
-use rsa::pkcs1v15::SigningKey;
-use rsa::sha2::Sha256;
-use rsa::signature::{SignatureEncoding, Signer};
+use percent_encoding::utf8_percent_encode;
 use rsa::{pkcs8::DecodePrivateKey, RsaPrivateKey};
 use std::fmt::Write;
 
+use super::constants::OCI_QUERY_ENCODE_SET;
+use super::constants::X_CONTENT_SHA256;
 use super::credential::Credential;
 use crate::ctx::SigningContext;
+use crate::hash::base64_encode;
+use crate::hash::rsa_sha256;
+use crate::hash::sha256;
 use crate::request::SignableRequest;
 use crate::time;
 use crate::time::DateTime;
 
 /// Singer for Oracle Cloud Infrastructure using API Key.
 #[derive(Default)]
-pub struct APIKeySigner {}
+pub struct APIKeySigner {
+    time: Option<DateTime>,
+}
 
 impl APIKeySigner {
+    /// Specify the signing time.
+    ///
+    /// # Note
+    ///
+    /// We should always take current time to sign requests.
+    /// Only use this function for testing.
+    #[cfg(test)]
+    pub fn with_time(mut self, time: DateTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+
     /// Building a signing context.
-    fn build(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<SigningContext> {
-        let now = time::now();
+    fn build(
+        &self,
+        req: &mut impl SignableRequest,
+        body: Option<&[u8]>,
+        cred: &Credential,
+    ) -> Result<SigningContext> {
+        let now = self.time.unwrap_or_else(time::now);
         let mut ctx = req.build()?;
+        // Query params are decoded while building context, encode them back
+        // so that they could be sent and signed in `(request-target)`.
+        ctx.query = ctx
+            .query
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    utf8_percent_encode(&k, &OCI_QUERY_ENCODE_SET).to_string(),
+                    utf8_percent_encode(&v, &OCI_QUERY_ENCODE_SET).to_string(),
+                )
+            })
+            .collect();
+        if ctx.headers.get(DATE).is_none() {
+            ctx.headers
+                .insert(DATE, HeaderValue::from_str(&time::format_http_date(now))?);
+        }
 
-        let string_to_sign = string_to_sign(&mut ctx, now)?;
+        let sign_body = is_body_signed(&ctx.method);
+        if sign_body {
+            canonicalize_body_header(&mut ctx, body)?;
+        }
+
+        let string_to_sign = string_to_sign(&mut ctx, sign_body)?;
         let private_key = if let Some(path) = &cred.key_file {
             RsaPrivateKey::read_pkcs8_pem_file(path)?
         } else {
             return Err(Error::msg("no private key"));
         };
-        let signing_key = SigningKey::<Sha256>::new(private_key);
-        let signature = signing_key.try_sign(string_to_sign.as_bytes())?;
-        let encoded_signature = general_purpose::STANDARD.encode(signature.to_bytes());
+        let signature = rsa_sha256(&private_key, string_to_sign.as_bytes())?;
+        let encoded_signature = general_purpose::STANDARD.encode(signature);
 
-        ctx.headers
-            .insert(DATE, HeaderValue::from_str(&time::format_http_date(now))?);
         if let Some(fp) = &cred.fingerprint {
             let mut auth_value = String::new();
             write!(auth_value, "Signature version=\"1\",")?;
-            write!(auth_value, "headers=\"date (request-target) host\",")?;
+            write!(auth_value, "headers=\"{}\",", signed_headers(sign_body))?;
             write!(
                 auth_value,
                 "keyId=\"{}/{}/{}\",",
@@ -62,11 +102,84 @@ impl APIKeySigner {
     }
 
     /// Signing request with header.
+    ///
+    /// `Date` set by the caller will be used as is, otherwise the current
+    /// time will be used.
+    ///
+    /// For `PUT`, `POST` and `PATCH` requests, OCI also requires the body to be
+    /// signed. `x-content-sha256` set by the caller will be used as is, otherwise
+    /// the request is signed as carrying an empty body. Use
+    /// [`APIKeySigner::sign_with_body`] to sign requests with a body.
     pub fn sign(&self, req: &mut impl SignableRequest, cred: &Credential) -> Result<()> {
-        let ctx = self.build(req, cred)?;
+        let ctx = self.build(req, None, cred)?;
 
         req.apply(ctx)
     }
+
+    /// Signing request with header and the given body.
+    ///
+    /// `x-content-sha256` and `content-length` will be calculated from `body`
+    /// for `PUT`, `POST` and `PATCH` requests. The body is ignored for other
+    /// methods.
+    pub fn sign_with_body(
+        &self,
+        req: &mut impl SignableRequest,
+        body: &[u8],
+        cred: &Credential,
+    ) -> Result<()> {
+        let ctx = self.build(req, Some(body), cred)?;
+
+        req.apply(ctx)
+    }
+}
+
+/// OCI only signs the body for methods that carry one.
+///
+/// Reference: [Request Signatures](https://docs.oracle.com/en-us/iaas/Content/API/Concepts/signingrequests.htm)
+fn is_body_signed(method: &Method) -> bool {
+    method == Method::PUT || method == Method::POST || method == Method::PATCH
+}
+
+fn signed_headers(sign_body: bool) -> &'static str {
+    if sign_body {
+        "date (request-target) host content-length content-type x-content-sha256"
+    } else {
+        "date (request-target) host"
+    }
+}
+
+/// Make sure `x-content-sha256`, `content-type` and `content-length` exist.
+fn canonicalize_body_header(ctx: &mut SigningContext, body: Option<&[u8]>) -> Result<()> {
+    match body {
+        Some(body) => {
+            ctx.headers.insert(
+                X_CONTENT_SHA256,
+                HeaderValue::from_str(&base64_encode(&sha256(body)))?,
+            );
+            ctx.headers
+                .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        }
+        None => {
+            if ctx.headers.get(X_CONTENT_SHA256).is_none() {
+                ctx.headers.insert(
+                    X_CONTENT_SHA256,
+                    HeaderValue::from_str(&base64_encode(&sha256(&[])))?,
+                );
+            }
+            if ctx.headers.get(CONTENT_LENGTH).is_none() {
+                ctx.headers
+                    .insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
+            }
+        }
+    }
+
+    // OCI SDKs default to json while content-type is not set.
+    if ctx.headers.get(CONTENT_TYPE).is_none() {
+        ctx.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+
+    Ok(())
 }
 
 /// Construct string to sign.
@@ -78,20 +191,248 @@ impl APIKeySigner {
 /// + "(request-target): {verb} {uri}" + "\n"
 /// + "host: {Host}"
 /// ```
-fn string_to_sign(ctx: &mut SigningContext, now: DateTime) -> Result<String> {
+///
+/// For requests with body:
+///
+/// ```text
+///   "date: {Date}" + "\n"
+/// + "(request-target): {verb} {uri}" + "\n"
+/// + "host: {Host}" + "\n"
+/// + "content-length: {ContentLength}" + "\n"
+/// + "content-type: {ContentType}" + "\n"
+/// + "x-content-sha256: {Sha256}"
+/// ```
+///
+/// `{uri}` is the path with query if any.
+fn string_to_sign(ctx: &mut SigningContext, sign_body: bool) -> Result<String> {
     let string_to_sign = {
         let mut f = String::new();
-        writeln!(f, "date: {}", time::format_http_date(now))?;
-        writeln!(
+        writeln!(f, "date: {}", ctx.header_get_or_default(&DATE)?)?;
+        write!(
             f,
             "(request-target): {} {}",
             ctx.method.as_str().to_lowercase(),
             ctx.path
         )?;
-        write!(f, "host: {}", ctx.authority)?;
+        for (i, (k, v)) in ctx.query.iter().enumerate() {
+            f.push(if i == 0 { '?' } else { '&' });
+            f.push_str(k);
+            if !v.is_empty() {
+                write!(f, "={v}")?;
+            }
+        }
+        writeln!(f)?;
+        write!(f, "host: {}", ctx.host())?;
+        if sign_body {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "content-length: {}",
+                ctx.header_get_or_default(&CONTENT_LENGTH)?
+            )?;
+            writeln!(
+                f,
+                "content-type: {}",
+                ctx.header_get_or_default(&CONTENT_TYPE)?
+            )?;
+            write!(
+                f,
+                "x-content-sha256: {}",
+                ctx.header_get_or_default(&X_CONTENT_SHA256.parse()?)?
+            )?;
+        }
         f
     };
 
     debug!("string to sign: {}", &string_to_sign);
     Ok(string_to_sign)
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use pretty_assertions::assert_eq;
+    use rsa::pkcs1v15::Signature;
+    use rsa::pkcs1v15::VerifyingKey;
+    use rsa::sha2::Sha256;
+    use rsa::signature::Verifier;
+
+    use super::*;
+    use crate::hash::base64_decode;
+
+    /// 2048 bits key used by tests verifying signatures.
+    const TEST_KEY: &str = "test_key.pem";
+    /// 1024 bits key of the OCI example, which is rejected by ring.
+    #[cfg(not(feature = "crypto-ring"))]
+    const EXAMPLE_KEY: &str = "example_key.pem";
+
+    fn test_key_file(name: &str) -> String {
+        format!(
+            "{}/testdata/services/oracle/{name}",
+            std::env::current_dir()
+                .expect("current_dir must exist")
+                .to_string_lossy()
+        )
+    }
+
+    /// Credential of [OCI test values](https://docs.oracle.com/en-us/iaas/Content/API/Concepts/signingrequests.htm#five).
+    fn test_credential(key: &str) -> Credential {
+        Credential {
+            tenancy:
+                "ocid1.tenancy.oc1..aaaaaaaaba3pv6wkcr4jqae5f15p2b2m2yt2j6rx32uzr4h25vqstifsfdsq"
+                    .to_string(),
+            user: "ocid1.user.oc1..aaaaaaaat5nvwcna5j6aqzjcaty5eqbb6qt2jvpkanghtgdaqedqw3rynjq"
+                .to_string(),
+            key_file: Some(test_key_file(key)),
+            fingerprint: Some("20:3b:97:13:55:1c:5b:0d:d3:37:d8:50:4e:c5:3a:34".to_string()),
+            expires_in: None,
+        }
+    }
+
+    /// Date used by the OCI example, its weekday doesn't match the date.
+    #[cfg(not(feature = "crypto-ring"))]
+    const TEST_DATE: &str = "Thu, 05 Jan 2014 21:31:40 GMT";
+
+    const TEST_KEY_ID: &str = "ocid1.tenancy.oc1..aaaaaaaaba3pv6wkcr4jqae5f15p2b2m2yt2j6rx32uzr4h25vqstifsfdsq/ocid1.user.oc1..aaaaaaaat5nvwcna5j6aqzjcaty5eqbb6qt2jvpkanghtgdaqedqw3rynjq/20:3b:97:13:55:1c:5b:0d:d3:37:d8:50:4e:c5:3a:34";
+
+    const TEST_BODY: &[u8] = br#"{
+    "compartmentId": "ocid1.compartment.oc1..aaaaaaaam3we6vgnherjq5q2idnccdflvjsnog7mlr6rtdb25gilchfeyjxa",
+    "instanceId": "ocid1.instance.oc1.phx.abuw4ljrlsfiqw6vzzxb43vyypt4pkodawglp3wqxjqofakrwvou52gb6s5a",
+    "volumeId": "ocid1.volume.oc1.phx.abyhqljrgvttnlx73nmrwfaux7kcvzfs3s66izvxf2h4lgvyndsdsnoiwr5q"
+}"#;
+
+    fn test_time() -> DateTime {
+        chrono::DateTime::parse_from_rfc2822("Sun, 05 Jan 2014 21:31:40 GMT")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// Extract the signature from authorization and verify it against the string to sign.
+    fn verify_signature(authorization: &str, string_to_sign: &str) -> Result<()> {
+        let signature = authorization
+            .split("signature=\"")
+            .nth(1)
+            .and_then(|v| v.strip_suffix('"'))
+            .expect("signature must exist");
+        let signature = Signature::try_from(base64_decode(signature)?.as_slice())?;
+
+        let private_key = RsaPrivateKey::read_pkcs8_pem_file(test_key_file(TEST_KEY))?;
+        let verifying_key = VerifyingKey::<Sha256>::new(private_key.to_public_key());
+        verifying_key.verify(string_to_sign.as_bytes(), &signature)?;
+
+        Ok(())
+    }
+
+    /// The GET example of OCI request signatures.
+    #[cfg(not(feature = "crypto-ring"))]
+    #[test]
+    fn test_sign_get_example() -> Result<()> {
+        let signer = APIKeySigner::default();
+
+        let uri = "https://iaas.us-phoenix-1.oraclecloud.com/20160918/instances?availabilityDomain=Pjwf%3A%20PHX-AD-1&compartmentId=ocid1.compartment.oc1..aaaaaaaam3we6vgnherjq5q2idnccdflvjsnog7mlr6rtdb25gilchfeyjxa&displayName=TeamXInstances&volumeId=ocid1.volume.oc1.phx.abyhqljrgvttnlx73nmrwfaux7kcvzfs3s66izvxf2h4lgvyndsdsnoiwr5q";
+        let mut req = http::Request::get(uri).header(DATE, TEST_DATE).body(())?;
+        signer.sign(&mut req, &test_credential(EXAMPLE_KEY))?;
+
+        assert!(req.headers().get(X_CONTENT_SHA256).is_none());
+        assert!(req.headers().get(CONTENT_LENGTH).is_none());
+        assert_eq!(uri, req.uri().to_string());
+        assert_eq!(TEST_DATE, req.headers()[DATE]);
+        assert_eq!(
+            format!(
+                "Signature version=\"1\",headers=\"date (request-target) host\",keyId=\"{TEST_KEY_ID}\",algorithm=\"rsa-sha256\",signature=\"GBas7grhyrhSKHP6AVIj/h5/Vp8bd/peM79H9Wv8kjoaCivujVXlpbKLjMPeDUhxkFIWtTtLBj3sUzaFj34XE6YZAHc9r2DmE4pMwOAy/kiITcZxa1oHPOeRheC0jP2dqbTll8fmTZVwKZOKHYPtrLJIJQHJjNvxFWeHQjMaR7M=\""
+            ),
+            req.headers()[AUTHORIZATION].to_str()?
+        );
+        Ok(())
+    }
+
+    /// The POST example of OCI request signatures.
+    #[cfg(not(feature = "crypto-ring"))]
+    #[test]
+    fn test_sign_post_example() -> Result<()> {
+        let signer = APIKeySigner::default();
+
+        let mut req = http::Request::post(
+            "https://iaas.us-phoenix-1.oraclecloud.com/20160918/volumeAttachments",
+        )
+        .header(DATE, TEST_DATE)
+        .header(CONTENT_TYPE, "application/json")
+        .body(())?;
+        signer.sign_with_body(&mut req, TEST_BODY, &test_credential(EXAMPLE_KEY))?;
+
+        assert_eq!(
+            "V9Z20UJTvkvpJ50flBzKE32+6m2zJjweHpDMX/U4Uy0=",
+            req.headers()[X_CONTENT_SHA256].to_str()?
+        );
+        assert_eq!("316", req.headers()[CONTENT_LENGTH].to_str()?);
+        assert_eq!(
+            format!(
+                "Signature version=\"1\",headers=\"date (request-target) host content-length content-type x-content-sha256\",keyId=\"{TEST_KEY_ID}\",algorithm=\"rsa-sha256\",signature=\"Mje8vIDPlwIHmD/cTDwRxE7HaAvBg16JnVcsuqaNRim23fFPgQfLoOOxae6WqKb1uPjYEl0qIdazWaBy/Ml8DRhqlocMwoSXv0fbukP8J5N80LCmzT/FFBvIvTB91XuXI3hYfP9Zt1l7S6ieVadHUfqBedWH0itrtPJBgKmrWso=\""
+            ),
+            req.headers()[AUTHORIZATION].to_str()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_get() -> Result<()> {
+        let signer = APIKeySigner::default().with_time(test_time());
+
+        let mut req = http::Request::get(
+            "https://iaas.us-phoenix-1.oraclecloud.com/20160918/instances?availabilityDomain=Pjwf%3A%20PHX-AD-1&displayName=TeamXInstances",
+        )
+        .body(())?;
+        signer.sign(&mut req, &test_credential(TEST_KEY))?;
+
+        let authorization = req.headers()[AUTHORIZATION].to_str()?;
+        assert!(authorization.contains("headers=\"date (request-target) host\""));
+        assert!(authorization.contains(&format!("keyId=\"{TEST_KEY_ID}\"")));
+
+        verify_signature(
+            authorization,
+            "date: Sun, 05 Jan 2014 21:31:40 GMT\n(request-target): get /20160918/instances?availabilityDomain=Pjwf%3A%20PHX-AD-1&displayName=TeamXInstances\nhost: iaas.us-phoenix-1.oraclecloud.com",
+        )
+    }
+
+    #[test]
+    fn test_sign_put_with_body() -> Result<()> {
+        let signer = APIKeySigner::default().with_time(test_time());
+
+        let mut req = http::Request::put(
+            "https://iaas.us-phoenix-1.oraclecloud.com/20160918/volumeAttachments",
+        )
+        .header(CONTENT_TYPE, "application/json")
+        .body(())?;
+        signer.sign_with_body(&mut req, TEST_BODY, &test_credential(TEST_KEY))?;
+
+        assert_eq!("Sun, 05 Jan 2014 21:31:40 GMT", req.headers()[DATE]);
+        let authorization = req.headers()[AUTHORIZATION].to_str()?;
+        assert!(authorization.contains(
+            "headers=\"date (request-target) host content-length content-type x-content-sha256\""
+        ));
+
+        verify_signature(
+            authorization,
+            "date: Sun, 05 Jan 2014 21:31:40 GMT\n(request-target): put /20160918/volumeAttachments\nhost: iaas.us-phoenix-1.oraclecloud.com\ncontent-length: 316\ncontent-type: application/json\nx-content-sha256: V9Z20UJTvkvpJ50flBzKE32+6m2zJjweHpDMX/U4Uy0=",
+        )
+    }
+
+    #[test]
+    fn test_sign_post_without_body() -> Result<()> {
+        let signer = APIKeySigner::default().with_time(test_time());
+
+        let mut req =
+            http::Request::post("https://iaas.us-phoenix-1.oraclecloud.com/20160918/instances")
+                .body(())?;
+        signer.sign(&mut req, &test_credential(TEST_KEY))?;
+
+        assert_eq!(
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            req.headers()[X_CONTENT_SHA256].to_str()?
+        );
+        assert_eq!("0", req.headers()[CONTENT_LENGTH].to_str()?);
+        assert_eq!("application/json", req.headers()[CONTENT_TYPE].to_str()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+//! Refresh credentials in background before they expire.
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::debug;
+use tokio::task::JoinHandle;
+
+use crate::time::now;
+use crate::time::DateTime;
+
+/// The minimal interval between two refreshes, make sure we never busy-loop.
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+/// The maximal interval between two refreshes.
+///
+/// Credentials without expiration will be refreshed in this interval, and
+/// this is also the upper bound of backoff on failures. Credentials with
+/// known expiration are always refreshed `ahead` before they expire.
+const MAX_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Guard of the background refresh task.
+///
+/// The task will be cancelled while this guard is dropped.
+#[derive(Debug)]
+pub struct RefreshGuard {
+    handle: JoinHandle<()>,
+}
+
+impl RefreshGuard {
+    /// Check if the background task has been finished.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Cancel the background task.
+    ///
+    /// This is equivalent to dropping the guard.
+    pub fn cancel(self) {}
+}
+
+impl Drop for RefreshGuard {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Spawn a task that calls `refresh` `ahead` before the credential expired.
+///
+/// `refresh` returns the expiration of the new credential. Failures will be
+/// retried with exponential backoff.
+///
+/// Must be called inside a tokio runtime.
+pub(crate) fn spawn<F, Fut>(ahead: Duration, refresh: F) -> RefreshGuard
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Option<DateTime>>> + Send,
+{
+    let handle = tokio::spawn(async move {
+        let mut backoff = MIN_INTERVAL;
+        loop {
+            let interval = match refresh().await {
+                Ok(expires_in) => {
+                    backoff = MIN_INTERVAL;
+                    next_interval(expires_in, ahead)
+                }
+                Err(err) => {
+                    debug!("refresh credential in background failed: {err:?}");
+                    let interval = backoff;
+                    backoff = (backoff * 2).min(MAX_INTERVAL);
+                    interval
+                }
+            };
+
+            tokio::time::sleep(interval).await;
+        }
+    });
+
+    RefreshGuard { handle }
+}
+
+/// Calculate the interval before next refresh.
+fn next_interval(expires_in: Option<DateTime>, ahead: Duration) -> Duration {
+    let Some(expires_in) = expires_in else {
+        return MAX_INTERVAL;
+    };
+
+    let interval = (expires_in - now())
+        .to_std()
+        .unwrap_or_default()
+        .saturating_sub(ahead);
+    interval.max(MIN_INTERVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn test_next_interval() {
+        let ahead = Duration::from_secs(60);
+
+        assert_eq!(MAX_INTERVAL, next_interval(None, ahead));
+        // Expired or going to expire credentials.
+        assert_eq!(MIN_INTERVAL, next_interval(Some(now()), ahead));
+        assert_eq!(
+            MIN_INTERVAL,
+            next_interval(
+                Some(now() + chrono::TimeDelta::try_seconds(30).expect("in bounds")),
+                ahead
+            )
+        );
+
+        let interval = next_interval(
+            Some(now() + chrono::TimeDelta::try_seconds(120).expect("in bounds")),
+            ahead,
+        );
+        assert!(interval > Duration::from_secs(58) && interval <= Duration::from_secs(60));
+
+        // Long-lived credentials are not capped by `MAX_INTERVAL`.
+        let interval = next_interval(
+            Some(now() + chrono::TimeDelta::try_hours(1).expect("in bounds")),
+            ahead,
+        );
+        assert!(interval > Duration::from_secs(3538) && interval <= Duration::from_secs(3540));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refresh_guard() {
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let cnt = count.clone();
+        let guard = spawn(Duration::from_secs(60), move || {
+            let cnt = cnt.clone();
+            async move {
+                cnt.fetch_add(1, Ordering::SeqCst);
+                Ok(Some(
+                    now() + chrono::TimeDelta::try_seconds(70).expect("in bounds"),
+                ))
+            }
+        });
+
+        // Refreshed immediately and then every 10s.
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert_eq!(3, count.load(Ordering::SeqCst));
+        assert!(!guard.is_finished());
+
+        guard.cancel();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert_eq!(3, count.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refresh_backoff_on_failures() {
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let cnt = count.clone();
+        let _guard = spawn(Duration::from_secs(60), move || {
+            let cnt = cnt.clone();
+            async move {
+                cnt.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow!("refresh failed"))
+            }
+        });
+
+        // Retried at 0s, 1s, 3s, 7s, 15s.
+        tokio::time::sleep(Duration::from_millis(15500)).await;
+        assert_eq!(5, count.load(Ordering::SeqCst));
+    }
+}
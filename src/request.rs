@@ -8,6 +8,7 @@ use anyhow::Result;
 use http::uri::PathAndQuery;
 use http::uri::Scheme;
 use http::Uri;
+use percent_encoding::percent_decode_str;
 
 use crate::ctx::SigningContext;
 
@@ -19,6 +20,15 @@ use crate::ctx::SigningContext;
 pub trait SignableRequest {
     fn build(&mut self) -> Result<SigningContext>;
 
+    /// Build the signing context like [`SignableRequest::build`], but keep
+    /// `+` in query as a literal plus instead of decoding it as space.
+    ///
+    /// Services like OSS treat `+` in query as is. Implementations that don't
+    /// override this fall back to [`SignableRequest::build`].
+    fn build_with_literal_plus(&mut self) -> Result<SigningContext> {
+        self.build()
+    }
+
     fn apply(&mut self, _ctx: SigningContext) -> Result<()>;
 }
 
@@ -41,11 +51,7 @@ impl<T> SignableRequest for http::Request<T> {
             path: paq.path().to_string(),
             query: paq
                 .query()
-                .map(|v| {
-                    form_urlencoded::parse(v.as_bytes())
-                        .map(|(k, v)| (k.into_owned(), v.into_owned()))
-                        .collect()
-                })
+                .map(|v| parse_query(v, true))
                 .unwrap_or_default(),
 
             // Take the headers out of the request to avoid copy.
@@ -54,6 +60,15 @@ impl<T> SignableRequest for http::Request<T> {
         })
     }
 
+    fn build_with_literal_plus(&mut self) -> Result<SigningContext> {
+        let this = self as &mut http::Request<T>;
+
+        let query = this.uri().query().map(|v| parse_query(v, false));
+        let mut ctx = self.build()?;
+        ctx.query = query.unwrap_or_default();
+        Ok(ctx)
+    }
+
     fn apply(&mut self, mut ctx: SigningContext) -> Result<()> {
         let this = self as &mut http::Request<T>;
 
@@ -121,11 +136,7 @@ impl SignableRequest for reqwest::Request {
             path: paq.path().to_string(),
             query: paq
                 .query()
-                .map(|v| {
-                    form_urlencoded::parse(v.as_bytes())
-                        .map(|(k, v)| (k.into_owned(), v.into_owned()))
-                        .collect()
-                })
+                .map(|v| parse_query(v, true))
                 .unwrap_or_default(),
 
             // Take the headers out of the request to avoid copy.
@@ -134,6 +145,15 @@ impl SignableRequest for reqwest::Request {
         })
     }
 
+    fn build_with_literal_plus(&mut self) -> Result<SigningContext> {
+        let this = self as &mut reqwest::Request;
+
+        let query = this.url().query().map(|v| parse_query(v, false));
+        let mut ctx = self.build()?;
+        ctx.query = query.unwrap_or_default();
+        Ok(ctx)
+    }
+
     fn apply(&mut self, mut ctx: SigningContext) -> Result<()> {
         let this = self as &mut reqwest::Request;
 
@@ -173,11 +193,7 @@ impl SignableRequest for reqwest::blocking::Request {
             path: paq.path().to_string(),
             query: paq
                 .query()
-                .map(|v| {
-                    form_urlencoded::parse(v.as_bytes())
-                        .map(|(k, v)| (k.into_owned(), v.into_owned()))
-                        .collect()
-                })
+                .map(|v| parse_query(v, true))
                 .unwrap_or_default(),
 
             // Take the headers out of the request to avoid copy.
@@ -186,6 +202,15 @@ impl SignableRequest for reqwest::blocking::Request {
         })
     }
 
+    fn build_with_literal_plus(&mut self) -> Result<SigningContext> {
+        let this = self as &mut reqwest::blocking::Request;
+
+        let query = this.url().query().map(|v| parse_query(v, false));
+        let mut ctx = self.build()?;
+        ctx.query = query.unwrap_or_default();
+        Ok(ctx)
+    }
+
     fn apply(&mut self, mut ctx: SigningContext) -> Result<()> {
         let this = self as &mut reqwest::blocking::Request;
 
@@ -202,3 +227,27 @@ impl SignableRequest for reqwest::blocking::Request {
         Ok(())
     }
 }
+
+/// Parse query into decoded key-value pairs.
+///
+/// `+` is decoded as space like `application/x-www-form-urlencoded` if
+/// `plus_as_space` is set, or kept as is otherwise.
+fn parse_query(query: &str, plus_as_space: bool) -> Vec<(String, String)> {
+    if plus_as_space {
+        return form_urlencoded::parse(query.as_bytes())
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+    }
+
+    query
+        .split('&')
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            let (k, v) = v.split_once('=').unwrap_or((v, ""));
+            (
+                percent_decode_str(k).decode_utf8_lossy().into_owned(),
+                percent_decode_str(v).decode_utf8_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
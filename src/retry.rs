@@ -0,0 +1,164 @@
+//! Retry of requests sent by credential loaders.
+
+use std::fmt;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::debug;
+use reqwest::Client;
+use reqwest::Request;
+use reqwest::RequestBuilder;
+use reqwest::Response;
+
+/// Max attempts of one request, including the first one.
+const MAX_ATTEMPTS: usize = 3;
+/// Backoff before the first retry, doubled for every following retry.
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Predicate to decide whether a response or error is retriable.
+type RetryPredicate = dyn Fn(Result<&Response, &reqwest::Error>) -> bool + Send + Sync;
+
+/// RetryPolicy decides whether requests of loaders should be retried.
+///
+/// By default, every request is sent only once so that loaders can fall
+/// through unavailable sources (like IMDS outside EC2) quickly. Once a
+/// predicate is set, requests are attempted at most 3 times with
+/// exponential backoff while the predicate returns `true` for the response
+/// or the transport error.
+#[derive(Clone, Default)]
+pub(crate) struct RetryPolicy {
+    predicate: Option<Arc<RetryPredicate>>,
+}
+
+impl Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("predicate", &self.predicate.as_ref().map(|_| "..."))
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy that retries responses and errors matching `predicate`.
+    pub(crate) fn new(
+        predicate: impl Fn(Result<&Response, &reqwest::Error>) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            predicate: Some(Arc::new(predicate)),
+        }
+    }
+
+    /// Send the request built by `req`, see [`RetryPolicy::execute`].
+    pub(crate) async fn send(&self, req: RequestBuilder) -> reqwest::Result<Response> {
+        let (client, req) = req.build_split();
+        self.execute(&client, req?).await
+    }
+
+    /// Execute the request, retry with backoff if the result is retriable.
+    ///
+    /// The last result is returned as is if all attempts are exhausted.
+    pub(crate) async fn execute(&self, client: &Client, req: Request) -> reqwest::Result<Response> {
+        let Some(predicate) = &self.predicate else {
+            return client.execute(req).await;
+        };
+
+        let mut backoff = MIN_BACKOFF;
+        for _ in 1..MAX_ATTEMPTS {
+            // Requests with streaming body can't be retried.
+            let Some(attempt) = req.try_clone() else {
+                break;
+            };
+            let result = client.execute(attempt).await;
+            if !predicate(result.as_ref()) {
+                return result;
+            }
+
+            match &result {
+                Ok(resp) => debug!(
+                    "request to {} got retriable status {}, retry after {backoff:?}",
+                    req.url(),
+                    resp.status()
+                ),
+                Err(err) => debug!(
+                    "request to {} failed: {err:?}, retry after {backoff:?}",
+                    req.url()
+                ),
+            }
+            // There is no timer available on wasm32, retry immediately.
+            #[cfg(not(target_arch = "wasm32"))]
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        client.execute(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use anyhow::Result;
+    use http::Method;
+    use http::StatusCode;
+
+    use super::*;
+    use crate::test_util::MockResponse;
+    use crate::test_util::MockServer;
+
+    #[test_case::test_case(false, 1; "single attempt by default")]
+    #[test_case::test_case(true, MAX_ATTEMPTS; "retry by predicate")]
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_responses(custom: bool, expected_attempts: usize) -> Result<()> {
+        let server = MockServer::builder()
+            .with_response(
+                Method::GET,
+                "/status",
+                MockResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "retry later"),
+            )
+            .start()
+            .await?;
+        let policy = if custom {
+            RetryPolicy::new(|result| result.is_ok_and(|v| v.status().is_server_error()))
+        } else {
+            RetryPolicy::default()
+        };
+
+        let client = Client::new();
+        let resp = policy
+            .send(client.get(format!("{}/status", server.endpoint())))
+            .await?;
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, resp.status());
+        assert_eq!(expected_attempts, server.requests().len());
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_transport_errors() -> Result<()> {
+        // Bind then drop a listener to get a port that refuses connections.
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+
+        let errors = Arc::new(AtomicUsize::new(0));
+        let policy = {
+            let errors = errors.clone();
+            RetryPolicy::new(move |result| {
+                let retriable = result.is_err();
+                if retriable {
+                    errors.fetch_add(1, Ordering::Relaxed);
+                }
+                retriable
+            })
+        };
+
+        let result = policy
+            .send(Client::new().get(format!("http://{addr}/status")))
+            .await;
+        assert!(result.is_err());
+        // The last attempt is returned as is without asking the predicate.
+        assert_eq!(MAX_ATTEMPTS - 1, errors.load(Ordering::Relaxed));
+
+        Ok(())
+    }
+}
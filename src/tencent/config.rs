@@ -1,7 +1,5 @@
-use std::collections::HashMap;
-use std::env;
-
 use super::constants::*;
+use crate::EnvSnapshot;
 
 /// Config carries all the configuration for Tencent COS services.
 #[derive(Clone)]
@@ -66,8 +64,13 @@ impl Default for Config {
 
 impl Config {
     /// Load config from env.
-    pub fn from_env(mut self) -> Self {
-        let envs = env::vars().collect::<HashMap<_, _>>();
+    pub fn from_env(self) -> Self {
+        self.from_env_with(&EnvSnapshot::from_env())
+    }
+
+    /// Load config from the given env snapshot.
+    pub fn from_env_with(mut self, env: &EnvSnapshot) -> Self {
+        let envs = env.envs();
 
         if let Some(v) = envs
             .get(TENCENTCLOUD_REGION)
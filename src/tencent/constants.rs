@@ -18,6 +18,12 @@ pub const TKE_PROVIDER_ID: &str = "TKE_PROVIDER_ID";
 pub const TENCENTCLOUD_WEB_IDENTITY_TOKEN_FILE: &str = "TENCENTCLOUD_WEB_IDENTITY_TOKEN_FILE";
 pub const TKE_IDENTITY_TOKEN_FILE: &str = "TKE_IDENTITY_TOKEN_FILE";
 
+// Headers used in tencent services.
+pub const X_COS_SECURITY_TOKEN: &str = "x-cos-security-token";
+/// CI (media processing) endpoints like `{bucket}.ci.{region}.myqcloud.com`
+/// take the security token in this header instead.
+pub const X_CI_SECURITY_TOKEN: &str = "x-ci-security-token";
+
 /// AsciiSet for [Tencent UriEncode](https://cloud.tencent.com/document/product/436/7778)
 pub static TENCENT_URI_ENCODE_SET: AsciiSet = NON_ALPHANUMERIC
     .remove(b'-')
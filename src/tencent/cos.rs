@@ -22,6 +22,10 @@ use crate::time::format_http_date;
 use crate::time::DateTime;
 
 /// Singer for Tencent COS.
+///
+/// CI (media processing) endpoints like `{bucket}.ci.{region}.myqcloud.com`
+/// share the same signature, only the security token will be sent in
+/// `x-ci-security-token` instead of `x-cos-security-token`.
 #[derive(Default)]
 pub struct Signer {
     time: Option<DateTime>,
@@ -61,9 +65,12 @@ impl Signer {
 
         match method {
             SigningMethod::Header => {
-                let signature = build_signature(&mut ctx, cred, now, Duration::from_secs(3600));
+                let signature = build_signature(&mut ctx, cred, now, Duration::from_secs(3600))?;
 
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                // Date provided by caller has been signed, keep it as is.
+                if ctx.headers.get(DATE).is_none() {
+                    ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                }
                 ctx.headers.insert(AUTHORIZATION, {
                     let mut value: HeaderValue = signature.parse()?;
                     value.set_sensitive(true);
@@ -71,7 +78,7 @@ impl Signer {
                 });
 
                 if let Some(token) = &cred.security_token {
-                    ctx.headers.insert("x-cos-security-token", {
+                    ctx.headers.insert(security_token_key(&ctx), {
                         let mut value: HeaderValue = token.parse()?;
                         value.set_sensitive(true);
 
@@ -80,14 +87,16 @@ impl Signer {
                 }
             }
             SigningMethod::Query(expire) => {
-                let signature = build_signature(&mut ctx, cred, now, expire);
+                let signature = build_signature(&mut ctx, cred, now, expire)?;
 
-                ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                if ctx.headers.get(DATE).is_none() {
+                    ctx.headers.insert(DATE, format_http_date(now).parse()?);
+                }
                 ctx.query_append(&signature);
 
                 if let Some(token) = &cred.security_token {
                     ctx.query_push(
-                        "x-cos-security-token".to_string(),
+                        security_token_key(&ctx).to_string(),
                         utf8_percent_encode(token, percent_encoding::NON_ALPHANUMERIC).to_string(),
                     );
                 }
@@ -115,12 +124,21 @@ impl Signer {
     }
 }
 
+/// Get the key to carry security token, CI endpoints use `x-ci-security-token`.
+fn security_token_key(ctx: &SigningContext) -> &'static str {
+    let host = ctx.authority.host();
+    match host.split_once(".ci.") {
+        Some((_, domain)) if domain.ends_with(".myqcloud.com") => X_CI_SECURITY_TOKEN,
+        _ => X_COS_SECURITY_TOKEN,
+    }
+}
+
 fn build_signature(
     ctx: &mut SigningContext,
     cred: &Credential,
     now: DateTime,
     expires: Duration,
-) -> String {
+) -> Result<String> {
     let key_time = format!(
         "{};{}",
         now.timestamp(),
@@ -155,7 +173,7 @@ fn build_signature(
     debug!("param string: {param_string}");
 
     let mut headers = ctx
-        .header_to_vec_with_prefix("")
+        .header_to_vec_with_prefix("")?
         .iter()
         .map(|(k, v)| {
             (
@@ -202,5 +220,105 @@ fn build_signature(
 
     let signature = hex_hmac_sha1(sign_key.as_bytes(), string_to_sign.as_bytes());
 
-    format!("q-sign-algorithm=sha1&q-ak={}&q-sign-time={}&q-key-time={}&q-header-list={}&q-url-param-list={}&q-signature={}", cred.secret_id, key_time, key_time, header_list, param_list, signature)
+    Ok(format!("q-sign-algorithm=sha1&q-ak={}&q-sign-time={}&q-key-time={}&q-header-list={}&q-url-param-list={}&q-signature={}", cred.secret_id, key_time, key_time, header_list, param_list, signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(security_token: Option<&str>) -> Credential {
+        Credential {
+            secret_id: "AKIDQjz3ltompVjBni5LitkWHFlFpwkn9U5q".to_string(),
+            secret_key: "BQYIM75p8x0iWVFSIgqEKwFprpRSVHlz".to_string(),
+            security_token: security_token.map(String::from),
+            expires_in: None,
+        }
+    }
+
+    /// Build the request of the [documented example](https://cloud.tencent.com/document/product/436/7778).
+    fn example_request(host: &str) -> Result<http::Request<()>> {
+        Ok(http::Request::put(format!(
+            "https://{host}/exampleobject(%E8%85%BE%E8%AE%AF%E4%BA%91)"
+        ))
+        .header("date", "Thu, 16 May 2019 06:45:51 GMT")
+        .header("host", host)
+        .header("content-type", "text/plain")
+        .header("content-length", "13")
+        .header("content-md5", "mQ/fVh815F3k6TAUm8m0eg==")
+        .header("x-cos-acl", "private")
+        .header("x-cos-grant-read", "uin=\"100000000011\"")
+        .body(())?)
+    }
+
+    // CI shares the signature scheme of COS, only the signed host differs.
+    #[test_case::test_case("examplebucket-1250000000.cos.ap-beijing.myqcloud.com", "3b8851a11a569213c17ba8fa7dcf2abec6935172"; "cos")]
+    #[test_case::test_case("examplebucket-1250000000.ci.ap-beijing.myqcloud.com", "e7c48771c277372cc082ee6c24803ea297974a01"; "ci")]
+    fn test_sign_documented_example(host: &str, signature: &str) -> Result<()> {
+        let now = chrono::DateTime::from_timestamp(1557989151, 0).unwrap();
+        let mut req = example_request(host)?;
+        Signer::new().with_time(now).sign_query(
+            &mut req,
+            Duration::from_secs(7200),
+            &credential(None),
+        )?;
+
+        let query = req.uri().query().expect("query must exist");
+        assert!(
+            query.contains("&q-header-list=content-length;content-md5;content-type;date;host;x-cos-acl;x-cos-grant-read&"),
+            "{query}"
+        );
+        assert!(
+            query.ends_with(&format!("&q-signature={signature}")),
+            "{query}"
+        );
+        Ok(())
+    }
+
+    // Brackets and port of IPv6 literals are kept in the signed host.
+    #[test_case::test_case("[::1]:9000", "00fda4f8d7f39d80ee5c5e060c5addd87d24bf48"; "ipv6 with port")]
+    #[test_case::test_case("[::1]", "3899f551579a177f74bf064090087f034db6e07c"; "ipv6 without port")]
+    fn test_sign_with_ipv6(host: &str, signature: &str) -> Result<()> {
+        let now = chrono::DateTime::from_timestamp(1557989151, 0).unwrap();
+        let uri = format!("http://{host}/exampleobject");
+        let mut req = http::Request::get(&uri).header("host", host).body(())?;
+        Signer::new().with_time(now).sign_query(
+            &mut req,
+            Duration::from_secs(7200),
+            &credential(None),
+        )?;
+
+        assert_eq!(host, req.headers()["host"]);
+        assert_eq!(Some(host), req.uri().authority().map(|v| v.as_str()));
+        let query = req.uri().query().expect("query must exist");
+        assert!(query.contains("&q-header-list=host&"), "{query}");
+        assert!(
+            query.ends_with(&format!("&q-signature={signature}")),
+            "{query}"
+        );
+        Ok(())
+    }
+
+    #[test_case::test_case("examplebucket-1250000000.cos.ap-beijing.myqcloud.com", X_COS_SECURITY_TOKEN; "cos")]
+    #[test_case::test_case("examplebucket-1250000000.ci.ap-beijing.myqcloud.com", X_CI_SECURITY_TOKEN; "ci")]
+    #[test_case::test_case("ci.example.com", X_COS_SECURITY_TOKEN; "custom domain")]
+    fn test_sign_with_security_token(host: &str, key: &str) -> Result<()> {
+        let cred = credential(Some("token"));
+
+        let mut req = http::Request::get(format!("https://{host}/jobs?tag=Transcode"))
+            .header("x-ci-extra", "value")
+            .body(())?;
+        Signer::new().sign(&mut req, &cred)?;
+        assert_eq!("token", req.headers()[key]);
+        // CI specific headers are signed too.
+        assert!(req.headers()[AUTHORIZATION]
+            .to_str()?
+            .contains("&q-header-list=x-ci-extra&"));
+
+        let mut req = http::Request::get(format!("https://{host}/jobs?tag=Transcode")).body(())?;
+        Signer::new().sign_query(&mut req, Duration::from_secs(3600), &cred)?;
+        let query = req.uri().query().expect("query must exist");
+        assert!(query.ends_with(&format!("&{key}=token")), "{query}");
+        Ok(())
+    }
 }
@@ -1,19 +1,32 @@
+#[cfg(not(feature = "no_network"))]
 use std::fs;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+#[cfg(not(feature = "no_network"))]
 use anyhow::anyhow;
 use anyhow::Result;
+#[cfg(not(feature = "no_network"))]
 use http::header::AUTHORIZATION;
+#[cfg(not(feature = "no_network"))]
 use http::header::CONTENT_LENGTH;
+#[cfg(not(feature = "no_network"))]
 use http::header::CONTENT_TYPE;
+#[cfg(not(feature = "no_network"))]
+use http::header::USER_AGENT;
 use log::debug;
 use reqwest::Client;
+#[cfg(not(feature = "no_network"))]
 use serde::Deserialize;
+#[cfg(not(feature = "no_network"))]
 use serde::Serialize;
 
 use super::config::Config;
+use crate::constants::DEFAULT_USER_AGENT;
+use crate::metrics::Metrics;
+use crate::metrics::SharedMetrics;
 use crate::time::now;
+#[cfg(not(feature = "no_network"))]
 use crate::time::parse_rfc3339;
 use crate::time::DateTime;
 
@@ -32,13 +45,24 @@ pub struct Credential {
 }
 
 /// CredentialLoader will load credential from different methods.
-#[derive(Default)]
+///
+/// With `no_network` feature enabled, only credentials from config will be
+/// loaded.
 #[cfg_attr(test, derive(Debug))]
+#[cfg_attr(feature = "no_network", allow(dead_code))]
 pub struct CredentialLoader {
     client: Client,
     config: Config,
+    user_agent: String,
 
     credential: Arc<Mutex<Option<Credential>>>,
+    metrics: SharedMetrics,
+}
+
+impl Default for CredentialLoader {
+    fn default() -> Self {
+        Self::new(Client::default(), Config::default())
+    }
 }
 
 impl CredentialLoader {
@@ -47,19 +71,47 @@ impl CredentialLoader {
         Self {
             client,
             config,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
 
             credential: Arc::default(),
+            metrics: SharedMetrics::default(),
         }
     }
 
+    /// Set the `User-Agent` of requests sent to STS.
+    ///
+    /// Default to `reqsign/{version}`.
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Append `suffix` like the product name of caller to the default
+    /// `User-Agent`, which produces `reqsign/{version} {suffix}`.
+    pub fn with_user_agent_suffix(self, suffix: &str) -> Self {
+        self.with_user_agent(&format!("{DEFAULT_USER_AGENT} {suffix}"))
+    }
+
+    /// Specify the metrics to record cache hits and refreshes of credential.
+    ///
+    /// Default to [`NoopMetrics`](crate::NoopMetrics).
+    pub fn with_metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = metrics.into();
+        self
+    }
+
     /// Load credential
     pub async fn load(&self) -> Result<Option<Credential>> {
         // Return cached credential if it's valid.
         if let Some(cred) = self.credential.lock().expect("lock poisoned").clone() {
+            self.metrics.credential_cache_hit("tencent");
             return Ok(Some(cred));
         }
 
-        let cred = self.load_inner().await?;
+        let cred = self.load_inner().await;
+        self.metrics
+            .credential_refreshed("tencent", matches!(cred, Ok(Some(_))));
+        let cred = cred?;
 
         let mut lock = self.credential.lock().expect("lock poisoned");
         lock.clone_from(&cred);
@@ -75,6 +127,7 @@ impl CredentialLoader {
             return Ok(Some(cred));
         }
 
+        #[cfg(not(feature = "no_network"))]
         if let Ok(Some(cred)) = self
             .load_via_assume_role_with_web_identity()
             .await
@@ -104,6 +157,7 @@ impl CredentialLoader {
         Ok(None)
     }
 
+    #[cfg(not(feature = "no_network"))]
     async fn load_via_assume_role_with_web_identity(&self) -> Result<Option<Credential>> {
         let (region, token_file, role_arn, provider_id) = match (
             &self.config.region,
@@ -155,6 +209,7 @@ impl CredentialLoader {
             .header(AUTHORIZATION.as_str(), "SKIP")
             .header(CONTENT_TYPE.as_str(), "application/json")
             .header(CONTENT_LENGTH, bs.len())
+            .header(USER_AGENT, &self.user_agent)
             .header("X-TC-Action", "AssumeRoleWithWebIdentity")
             .header("X-TC-Region", region)
             .header("X-TC-Timestamp", now().timestamp())
@@ -189,6 +244,7 @@ impl CredentialLoader {
     }
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Serialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityRequest {
@@ -198,12 +254,14 @@ struct AssumeRoleWithWebIdentityRequest {
     provider_id: String,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityResult {
     response: AssumeRoleWithWebIdentityResponse,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityResponse {
@@ -212,6 +270,7 @@ struct AssumeRoleWithWebIdentityResponse {
     credentials: AssumeRoleWithWebIdentityCredentials,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityCredentials {
@@ -220,6 +279,7 @@ struct AssumeRoleWithWebIdentityCredentials {
     tmp_secret_key: String,
 }
 
+#[cfg(not(feature = "no_network"))]
 #[derive(Default, Debug, Deserialize)]
 #[serde(default, rename_all = "PascalCase")]
 struct AssumeRoleWithWebIdentityError {
@@ -227,7 +287,7 @@ struct AssumeRoleWithWebIdentityError {
     message: String,
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "no_network")))]
 mod tests {
     use std::env;
     use std::str::FromStr;
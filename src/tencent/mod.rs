@@ -12,4 +12,13 @@ pub use credential::CredentialLoader as TencentCosCredentialLoader;
 mod config;
 pub use config::Config as TencentCosConfig;
 
+crate::client::signing_client!(
+    /// Signing client for Tencent COS, which loads credential by [`TencentCosCredentialLoader`] and
+    /// signs requests by [`TencentCosSigner`].
+    TencentCosSigningClient,
+    "tencent",
+    TencentCosCredentialLoader,
+    TencentCosSigner
+);
+
 mod constants;
@@ -0,0 +1,582 @@
+//! Utilities for testing credential loaders without touching real services.
+//!
+//! Enable the `test-util` feature to use them in your own tests.
+//!
+//! ```rust,no_run
+//! use anyhow::Result;
+//! use reqsign::test_util::MockServer;
+//! use reqsign::AwsConfig;
+//! use reqsign::AwsDefaultLoader;
+//! use reqwest::Client;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     let server = MockServer::builder()
+//!         .with_aws_imds("test_role", "access_key_id", "secret_access_key", "session_token")
+//!         .start()
+//!         .await?;
+//!
+//!     let loader = AwsDefaultLoader::new(Client::new(), AwsConfig::default())
+//!         .with_ec2_metadata_endpoint(server.endpoint());
+//!     let cred = loader.load().await?.expect("credential must be loaded");
+//!     assert_eq!("access_key_id", cred.access_key_id);
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use http::header::CONTENT_LENGTH;
+use http::HeaderMap;
+use http::HeaderName;
+use http::HeaderValue;
+use http::Method;
+use http::StatusCode;
+use http::Uri;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+/// Expiration of all credentials returned by canned responses.
+const MOCK_EXPIRATION: &str = "2099-01-01T00:00:00Z";
+/// ARN of the assumed role returned by canned AWS STS responses.
+pub const MOCK_ASSUMED_ROLE_ARN: &str = "arn:aws:sts::123456789012:assumed-role/test/reqsign";
+
+/// Response that will be returned by [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// Create a new response with given status and body.
+    pub fn new(status: StatusCode, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Create a new `200 OK` response with given body.
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new(StatusCode::OK, body)
+    }
+
+    /// Add a header into response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the header name or value is invalid.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(
+            HeaderName::from_bytes(name.as_bytes()).expect("header name must be valid"),
+            HeaderValue::from_str(value).expect("header value must be valid"),
+        );
+        self
+    }
+}
+
+/// Request that has been received by [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    /// Method of the request.
+    pub method: Method,
+    /// Uri of the request which only contains path and query.
+    pub uri: Uri,
+    /// Headers of the request.
+    pub headers: HeaderMap,
+    /// Body of the request.
+    pub body: Vec<u8>,
+}
+
+/// Builder for [`MockServer`].
+///
+/// Responses are matched by method and path, query is ignored. If more than
+/// one response is registered for the same route, they will be returned in
+/// order and the last one will be repeated. Requests to unknown routes will
+/// get `404 Not Found`.
+#[derive(Debug, Default)]
+pub struct MockServerBuilder {
+    routes: HashMap<(Method, String), VecDeque<MockResponse>>,
+}
+
+impl MockServerBuilder {
+    /// Register a response for given method and path.
+    pub fn with_response(self, method: Method, path: &str, resp: MockResponse) -> Self {
+        self.with_responses(method, path, [resp])
+    }
+
+    /// Register a sequence of responses for given method and path.
+    pub fn with_responses(
+        mut self,
+        method: Method,
+        path: &str,
+        resps: impl IntoIterator<Item = MockResponse>,
+    ) -> Self {
+        self.routes
+            .entry((method, path.to_string()))
+            .or_default()
+            .extend(resps);
+        self
+    }
+
+    /// Register canned responses of AWS EC2 instance metadata service (IMDSv2).
+    ///
+    /// The session token returned by this server is `mock_ec2_token`.
+    pub fn with_aws_imds(
+        self,
+        role: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+        session_token: &str,
+    ) -> Self {
+        let cred = format!(
+            r#"{{"Code":"Success","Type":"AWS-HMAC","AccessKeyId":"{access_key_id}","SecretAccessKey":"{secret_access_key}","Token":"{session_token}","Expiration":"{MOCK_EXPIRATION}"}}"#
+        );
+
+        self.with_response(
+            Method::PUT,
+            "/latest/api/token",
+            MockResponse::ok("mock_ec2_token"),
+        )
+        .with_response(
+            Method::GET,
+            "/latest/meta-data/iam/security-credentials/",
+            MockResponse::ok(role),
+        )
+        .with_response(
+            Method::GET,
+            &format!("/latest/meta-data/iam/security-credentials/{role}"),
+            MockResponse::ok(cred),
+        )
+    }
+
+    /// Register canned responses of AWS STS `AssumeRole`.
+    pub fn with_aws_assume_role(
+        self,
+        access_key_id: &str,
+        secret_access_key: &str,
+        session_token: &str,
+    ) -> Self {
+        let body = format!(
+            r#"<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleResult>
+    <AssumedRoleUser>
+      <Arn>{MOCK_ASSUMED_ROLE_ARN}</Arn>
+      <AssumedRoleId>AROA123EXAMPLE123:reqsign</AssumedRoleId>
+    </AssumedRoleUser>
+    <Credentials>
+      <AccessKeyId>{access_key_id}</AccessKeyId>
+      <SecretAccessKey>{secret_access_key}</SecretAccessKey>
+      <SessionToken>{session_token}</SessionToken>
+      <Expiration>{MOCK_EXPIRATION}</Expiration>
+    </Credentials>
+  </AssumeRoleResult>
+</AssumeRoleResponse>"#
+        );
+
+        self.with_response(
+            Method::GET,
+            "/",
+            MockResponse::ok(body).with_header("content-type", "text/xml"),
+        )
+    }
+
+    /// Register canned responses of Azure instance metadata service.
+    ///
+    /// The token will be served at `/metadata/identity/oauth2/token`.
+    pub fn with_azure_imds(self, access_token: &str, expires_on: i64) -> Self {
+        let body = format!(
+            r#"{{"access_token":"{access_token}","refresh_token":"","expires_in":"3599","expires_on":"{expires_on}","not_before":"{expires_on}","resource":"https://storage.azure.com/","token_type":"Bearer"}}"#
+        );
+
+        self.with_response(
+            Method::GET,
+            "/metadata/identity/oauth2/token",
+            MockResponse::ok(body).with_header("content-type", "application/json"),
+        )
+    }
+
+    /// Register canned responses of Aliyun STS `AssumeRoleWithOIDC`.
+    pub fn with_aliyun_assume_role_with_oidc(
+        self,
+        access_key_id: &str,
+        access_key_secret: &str,
+        security_token: &str,
+    ) -> Self {
+        let body = format!(
+            r#"{{"RequestId":"mock_request_id","Credentials":{{"AccessKeyId":"{access_key_id}","AccessKeySecret":"{access_key_secret}","SecurityToken":"{security_token}","Expiration":"{MOCK_EXPIRATION}"}}}}"#
+        );
+
+        self.with_response(
+            Method::GET,
+            "/",
+            MockResponse::ok(body).with_header("content-type", "application/json"),
+        )
+    }
+
+    /// Register canned responses of Google compute engine metadata server.
+    pub fn with_google_metadata(self, service_account: &str, access_token: &str) -> Self {
+        let body = format!(
+            r#"{{"access_token":"{access_token}","expires_in":3599,"token_type":"Bearer"}}"#
+        );
+
+        self.with_response(
+            Method::GET,
+            &format!("/computeMetadata/v1/instance/service-accounts/{service_account}/token"),
+            MockResponse::ok(body)
+                .with_header("content-type", "application/json")
+                .with_header("metadata-flavor", "Google"),
+        )
+    }
+
+    /// Start the mock server on a random local port.
+    ///
+    /// Must be called inside a tokio runtime.
+    pub async fn start(self) -> Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint = format!("http://{}", listener.local_addr()?);
+
+        let state = Arc::new(State {
+            routes: Mutex::new(self.routes),
+            requests: Mutex::default(),
+        });
+
+        let handle = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = serve(stream, state).await {
+                            log::debug!("mock server serve connection failed: {err:?}");
+                        }
+                    });
+                }
+            })
+        };
+
+        Ok(MockServer {
+            endpoint,
+            state,
+            handle,
+        })
+    }
+}
+
+/// A lightweight in-memory HTTP server that replies with scripted responses.
+///
+/// The server will be stopped after dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    endpoint: String,
+    state: Arc<State>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Create a builder of mock server.
+    pub fn builder() -> MockServerBuilder {
+        MockServerBuilder::default()
+    }
+
+    /// Get the endpoint of mock server like `http://127.0.0.1:12345`.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Get all requests that have been received in order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.requests.lock().expect("lock poisoned").clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    routes: Mutex<HashMap<(Method, String), VecDeque<MockResponse>>>,
+    requests: Mutex<Vec<MockRequest>>,
+}
+
+impl State {
+    fn respond(&self, req: &MockRequest) -> MockResponse {
+        let mut routes = self.routes.lock().expect("lock poisoned");
+        let resps = routes.get_mut(&(req.method.clone(), req.uri.path().to_string()));
+
+        match resps {
+            Some(resps) if resps.len() > 1 => resps.pop_front().expect("must have response"),
+            Some(resps) if !resps.is_empty() => resps[0].clone(),
+            _ => MockResponse::new(StatusCode::NOT_FOUND, ""),
+        }
+    }
+}
+
+async fn serve(mut stream: TcpStream, state: Arc<State>) -> Result<()> {
+    let mut buf = Vec::new();
+
+    while let Some(req) = read_request(&mut stream, &mut buf).await? {
+        let resp = state.respond(&req);
+        state.requests.lock().expect("lock poisoned").push(req);
+
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\ncontent-length: {}\r\n",
+            resp.status.as_u16(),
+            resp.status.canonical_reason().unwrap_or_default(),
+            resp.body.len()
+        );
+        for (k, v) in resp.headers.iter() {
+            head.push_str(&format!("{k}: {}\r\n", v.to_str()?));
+        }
+        head.push_str("\r\n");
+
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(&resp.body).await?;
+    }
+
+    Ok(())
+}
+
+/// Read a request from stream.
+///
+/// Return `Ok(None)` if the connection has been closed by client.
+async fn read_request(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Result<Option<MockRequest>> {
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if !read_more(stream, buf).await? {
+            return Ok(None);
+        }
+    };
+    let head = String::from_utf8(buf.drain(..head_end).collect())?;
+
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = Method::from_bytes(request_line.next().unwrap_or_default().as_bytes())?;
+    let uri: Uri = request_line
+        .next()
+        .ok_or_else(|| anyhow!("request line is invalid: {head}"))?
+        .parse()?;
+
+    let mut headers = HeaderMap::new();
+    for line in lines.filter(|v| !v.is_empty()) {
+        let (k, v) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("header line is invalid: {line}"))?;
+        headers.append(
+            HeaderName::from_bytes(k.trim().as_bytes())?,
+            HeaderValue::from_str(v.trim())?,
+        );
+    }
+
+    let content_length = match headers.get(CONTENT_LENGTH) {
+        Some(v) => v.to_str()?.parse::<usize>()?,
+        None => 0,
+    };
+    while buf.len() < content_length {
+        if !read_more(stream, buf).await? {
+            return Err(anyhow!("connection closed before body is fully read"));
+        }
+    }
+    let body = buf.drain(..content_length).collect();
+
+    Ok(Some(MockRequest {
+        method,
+        uri,
+        headers,
+        body,
+    }))
+}
+
+/// Read more data from stream, return `false` if reaches EOF.
+async fn read_more(stream: &mut TcpStream, buf: &mut Vec<u8>) -> Result<bool> {
+    let mut b = [0; 4096];
+    let n = stream.read(&mut b).await?;
+    buf.extend_from_slice(&b[..n]);
+    Ok(n != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use reqwest::Client;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_server_responses_in_order() -> Result<()> {
+        let server = MockServer::builder()
+            .with_responses(
+                Method::GET,
+                "/status",
+                [
+                    MockResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "retry later"),
+                    MockResponse::ok("hello"),
+                ],
+            )
+            .start()
+            .await?;
+
+        let client = Client::new();
+        let url = format!("{}/status?key=value", server.endpoint());
+        for expected in [
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::OK,
+            StatusCode::OK,
+        ] {
+            let resp = client.get(&url).send().await?;
+            assert_eq!(expected, resp.status());
+        }
+
+        let resp = client
+            .post(format!("{}/not_exist", server.endpoint()))
+            .header("x-test", "test")
+            .body("content")
+            .send()
+            .await?;
+        assert_eq!(StatusCode::NOT_FOUND, resp.status());
+
+        let requests = server.requests();
+        assert_eq!(4, requests.len());
+        assert_eq!(Some("key=value"), requests[0].uri.query());
+        assert_eq!(Method::POST, requests[3].method);
+        assert_eq!("/not_exist", requests[3].uri.path());
+        assert_eq!(Some("test"), requests[3].headers["x-test"].to_str().ok());
+        assert_eq!(b"content".to_vec(), requests[3].body);
+        Ok(())
+    }
+
+    #[cfg(all(feature = "services-aws", not(feature = "no_network")))]
+    #[tokio::test]
+    async fn test_mock_server_with_aws_imds() -> Result<()> {
+        let server = MockServer::builder()
+            .with_aws_imds(
+                "test_role",
+                "access_key_id",
+                "secret_access_key",
+                "session_token",
+            )
+            .start()
+            .await?;
+
+        let loader = crate::AwsDefaultLoader::new(Client::new(), crate::AwsConfig::default())
+            .with_ec2_metadata_endpoint(server.endpoint());
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!("secret_access_key", cred.secret_access_key);
+        assert_eq!(Some("session_token"), cred.session_token.as_deref());
+
+        let requests = server.requests();
+        assert_eq!(3, requests.len());
+        assert!(requests[1..]
+            .iter()
+            .all(|v| v.headers["x-aws-ec2-metadata-token"] == "mock_ec2_token"));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "services-azblob", not(feature = "no_network")))]
+    #[tokio::test]
+    async fn test_mock_server_with_azure_imds() -> Result<()> {
+        let server = MockServer::builder()
+            .with_azure_imds("access_token", 4070908800)
+            .start()
+            .await?;
+
+        let loader = crate::AzureStorageLoader::new(crate::AzureStorageConfig {
+            endpoint: Some(format!(
+                "{}/metadata/identity/oauth2/token",
+                server.endpoint()
+            )),
+            ..Default::default()
+        });
+        let cred = loader.load().await?.expect("credential must be loaded");
+        match cred {
+            crate::AzureStorageCredential::BearerToken(token, expires_on) => {
+                assert_eq!("access_token", token);
+                assert_eq!(4070908800, expires_on.timestamp());
+            }
+            _ => panic!("expect bearer token, got {cred:?}"),
+        }
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(requests[0].headers["metadata"], "true");
+        Ok(())
+    }
+
+    #[cfg(all(feature = "services-aliyun", not(feature = "no_network")))]
+    #[tokio::test]
+    async fn test_mock_server_with_aliyun_sts() -> Result<()> {
+        let server = MockServer::builder()
+            .with_aliyun_assume_role_with_oidc(
+                "access_key_id",
+                "access_key_secret",
+                "security_token",
+            )
+            .start()
+            .await?;
+
+        let dir = tempfile::tempdir()?;
+        let token_file = dir.path().join("token");
+        std::fs::write(&token_file, "oidc_token")?;
+
+        let loader = crate::AliyunLoader::new(
+            Client::new(),
+            crate::AliyunConfig {
+                role_arn: Some("acs:ram::123456789012:role/test".to_string()),
+                oidc_provider_arn: Some("acs:ram::123456789012:oidc-provider/test".to_string()),
+                oidc_token_file: Some(token_file.to_string_lossy().to_string()),
+                sts_endpoint: Some(server.endpoint().to_string()),
+                ..Default::default()
+            },
+        );
+        let cred = loader.load().await?.expect("credential must be loaded");
+        assert_eq!("access_key_id", cred.access_key_id);
+        assert_eq!("access_key_secret", cred.access_key_secret);
+        assert_eq!(Some("security_token"), cred.security_token.as_deref());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert!(requests[0]
+            .uri
+            .query()
+            .unwrap_or_default()
+            .contains("Action=AssumeRoleWithOIDC"));
+        Ok(())
+    }
+
+    #[cfg(all(feature = "services-google", not(feature = "no_network")))]
+    #[tokio::test]
+    async fn test_mock_server_with_google_metadata() -> Result<()> {
+        let server = MockServer::builder()
+            .with_google_metadata("default", "access_token")
+            .start()
+            .await?;
+
+        let loader = crate::GoogleTokenLoader::new("scope", Client::new())
+            .with_vm_metadata_endpoint(server.endpoint());
+        let token = loader.load().await?.expect("token must be loaded");
+        assert_eq!("access_token", token.access_token());
+
+        let requests = server.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(Some("scopes=scope"), requests[0].uri.query());
+        assert_eq!(requests[0].headers["metadata-flavor"], "Google");
+        Ok(())
+    }
+}
@@ -45,6 +45,13 @@ pub fn format_iso8601(t: DateTime) -> String {
     t.format_with_items(ISO8601.iter()).to_string()
 }
 
+/// Parse time from ISO8601 like `20220313T072004Z`.
+pub fn parse_iso8601(s: &str) -> Result<DateTime> {
+    Ok(chrono::NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%SZ")
+        .map_err(|err| anyhow!("parse {s} into iso8601 failed for {err:?}"))?
+        .and_utc())
+}
+
 /// HTTP_DATE is a time format like `Sun, 06 Nov 1994 08:49:37 GMT`.
 const HTTP_DATE: &[Item<'static>] = &[
     Item::Fixed(Fixed::ShortWeekdayName),
@@ -115,6 +122,16 @@ mod tests {
         assert_eq!("20220301T081234Z", format_iso8601(t))
     }
 
+    #[test]
+    fn test_parse_iso8601() {
+        let t = test_time();
+        assert_eq!(
+            t,
+            parse_iso8601("20220301T081234Z").expect("must be valid time")
+        );
+        assert!(parse_iso8601("2022-03-01T08:12:34Z").is_err());
+    }
+
     #[test]
     fn test_format_http_date() {
         let t = test_time();
@@ -0,0 +1,35 @@
+//! Warnings about configs that still work but should be migrated.
+
+use std::fmt;
+
+/// Warning reported by `validate_with_warnings` of configs.
+///
+/// Warnings are not fatal, the config could still be used as is. But the
+/// setting has been deprecated by services or is ignored by reqsign, and may
+/// stop working in the future.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// The config field that triggers this warning, like `sts_regional_endpoints`.
+    pub field: &'static str,
+    /// The reason of this warning and how to migrate.
+    pub message: String,
+}
+
+impl Warning {
+    #[cfg_attr(
+        not(any(feature = "services-aws", feature = "services-azblob")),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
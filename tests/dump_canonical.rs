@@ -0,0 +1,77 @@
+//! Tests for `dump-canonical` feature.
+#![cfg(all(feature = "dump-canonical", feature = "services-aws"))]
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use reqsign::AwsCredential;
+use reqsign::AwsV4Signer;
+use sha2::Digest;
+use sha2::Sha256;
+
+#[test]
+fn test_dump_canonical() -> Result<()> {
+    let dumps = Arc::new(Mutex::new(Vec::new()));
+    {
+        let dumps = dumps.clone();
+        reqsign::set_canonical_sink(
+            move |service: &str, canonical_request: &str, string_to_sign: &str| {
+                dumps.lock().unwrap().push((
+                    service.to_string(),
+                    canonical_request.to_string(),
+                    string_to_sign.to_string(),
+                ))
+            },
+        );
+    }
+
+    let mut req = http::Request::get("https://s3.amazonaws.com/bucket/key?list-type=2")
+        .header("x-amz-date", "20240115T100000Z")
+        .body(())?;
+    let cred = AwsCredential {
+        access_key_id: "access_key_id".to_string(),
+        secret_access_key: "secret_access_key".to_string(),
+        ..Default::default()
+    };
+    AwsV4Signer::new("s3", "us-east-1").sign(&mut req, &cred)?;
+
+    // Security tokens must be redacted in both headers and query.
+    let cred = AwsCredential {
+        session_token: Some("secret_session_token".to_string()),
+        ..cred
+    };
+    let signer = AwsV4Signer::new("s3", "us-east-1");
+    let mut req = http::Request::get("https://s3.amazonaws.com/bucket/key").body(())?;
+    signer.sign(&mut req, &cred)?;
+    let mut req = http::Request::get("https://s3.amazonaws.com/bucket/key").body(())?;
+    signer.sign_query(&mut req, Duration::from_secs(3600), &cred)?;
+
+    let dumps = dumps.lock().unwrap();
+    assert_eq!(3, dumps.len());
+    assert!(dumps[1].1.contains("\nx-amz-security-token:<redacted>\n"));
+    assert!(dumps[2].1.contains("X-Amz-Security-Token=<redacted>"));
+    for (_, canonical_request, _) in dumps.iter() {
+        assert!(!canonical_request.contains("secret_session_token"));
+    }
+
+    let (service, canonical_request, string_to_sign) = &dumps[0];
+    assert_eq!("s3", service);
+    assert_eq!(
+        "GET
+/bucket/key
+list-type=2
+host:s3.amazonaws.com
+x-amz-content-sha256:UNSIGNED-PAYLOAD
+x-amz-date:20240115T100000Z
+
+host;x-amz-content-sha256;x-amz-date
+UNSIGNED-PAYLOAD",
+        canonical_request
+    );
+    assert!(string_to_sign.starts_with("AWS4-HMAC-SHA256\n"));
+    assert!(string_to_sign.ends_with(&hex::encode(Sha256::digest(canonical_request))));
+
+    Ok(())
+}
@@ -1 +1,3 @@
+// The storage tests exchange tokens via network.
+#[cfg(not(feature = "no_network"))]
 mod storage;
@@ -0,0 +1,61 @@
+//! Tests for signing with `no_network` feature enabled.
+#![cfg(all(feature = "no_network", feature = "services-aws"))]
+
+use anyhow::Result;
+use http::header::AUTHORIZATION;
+use reqsign::AwsConfig;
+use reqsign::AwsDefaultLoader;
+use reqsign::AwsV4Signer;
+use reqwest::Client;
+
+#[tokio::test]
+async fn test_sign_with_static_credential() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let loader = AwsDefaultLoader::new(
+        Client::new(),
+        AwsConfig {
+            access_key_id: Some("access_key_id".to_string()),
+            secret_access_key: Some("secret_access_key".to_string()),
+            ..Default::default()
+        },
+    );
+    let cred = loader.load().await?.expect("credential must be loaded");
+
+    let mut req = http::Request::get("https://test.s3.amazonaws.com/hello").body("")?;
+    AwsV4Signer::new("s3", "test").sign(&mut req, &cred)?;
+    assert!(req.headers()[AUTHORIZATION]
+        .to_str()?
+        .starts_with("AWS4-HMAC-SHA256 Credential=access_key_id/"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_load_without_static_credential() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let loader = AwsDefaultLoader::new(Client::new(), AwsConfig::default());
+    assert!(loader.load().await?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_load_with_role_arn() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let loader = AwsDefaultLoader::new(
+        Client::new(),
+        AwsConfig {
+            access_key_id: Some("access_key_id".to_string()),
+            secret_access_key: Some("secret_access_key".to_string()),
+            role_arn: Some("arn:aws:iam::123456789012:role/test".to_string()),
+            ..Default::default()
+        },
+    );
+    let err = loader.load().await.err().expect("assume role must fail");
+    assert!(err.to_string().contains("no_network"), "{err}");
+
+    Ok(())
+}